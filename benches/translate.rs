@@ -0,0 +1,261 @@
+//! 翻訳の処理時間の計測（`cargo bench --bench translate [名前]`、名前を指定すればそれを含むものだけ）
+//!
+//! 閾値などを決めるために、処理方法ごとの時間を並べて表示する
+
+use rustc_ja_wrapper::{TranslateEntry, TranslationTable, Translator, bench, translate_message};
+use std::hint::black_box;
+use std::time::Instant;
+
+/// 計測の名前と関数
+const BENCHES: &[(&str, fn())] = &[
+    ("replace_rendered", replace_rendered),
+    ("translate_json_message", translate_json_message),
+    ("translation_table", translation_table),
+    ("literal_index", literal_index),
+    ("translation_table_cache", translation_table_cache),
+    ("convert_json_parallel", convert_json_parallel),
+];
+
+fn main() {
+    // cargo bench は "--bench" を付けて実行するので、"-" で始まる引数は名前として扱わない
+    let filter: Vec<String> = std::env::args()
+        .skip(1)
+        .filter(|a| !a.starts_with('-'))
+        .collect();
+    for (name, bench) in BENCHES {
+        if filter.is_empty() || filter.iter().any(|f| name.contains(f.as_str())) {
+            println!("{name}:");
+            bench();
+        }
+    }
+}
+
+// 翻訳データを JSON から読み込む
+fn entries(json_str: &str) -> Vec<TranslateEntry> {
+    serde_json::from_str(json_str).unwrap()
+}
+
+// 固定の英語文字列のエントリ（count 件）と、そのうち replaced_count 件を置換する rendered と置換対象
+fn rendered_fixture(
+    count: usize,
+    replaced_count: usize,
+) -> (Vec<TranslateEntry>, String, Vec<(String, String)>) {
+    let entries: Vec<TranslateEntry> = (0..count)
+        .map(|i| TranslateEntry {
+            en: format!("message {i} here"),
+            ja: format!("メッセージ{i}番"),
+            ..Default::default()
+        })
+        .collect();
+    let replaced: Vec<(String, String)> = entries
+        .iter()
+        .step_by(count / replaced_count)
+        .take(replaced_count)
+        .map(|e| (e.en.clone(), e.ja.clone()))
+        .collect();
+    let rendered = replaced
+        .iter()
+        .map(|(orig, _)| format!("error: {orig}\n  --> src/main.rs:1:1\n   |\nnote: {orig}\n"))
+        .collect();
+    (entries, rendered, replaced)
+}
+
+// 翻訳データの固定の英語文字列のエントリの件数ごとに、1 件ずつ探す場合と索引で探す場合を比べる
+fn replace_rendered() {
+    for replaced_count in [1, 4, 16] {
+        for count in [1, 4, 16, 64, 256, 1024, 4096] {
+            if count < replaced_count {
+                continue;
+            }
+            let (entries, rendered, replaced) = rendered_fixture(count, replaced_count);
+            let table = TranslationTable::new(entries);
+            let mut times = Vec::new();
+            for index in [None, Some(&table)] {
+                let start = Instant::now();
+                for _ in 0..10000 {
+                    black_box(bench::replace_rendered(&rendered, &replaced, index));
+                }
+                times.push(start.elapsed());
+            }
+            println!(
+                "  {count:>4} entries, {replaced_count:>2} replaced: Linear {:?}, AhoCorasick {:?}",
+                times[0], times[1]
+            );
+        }
+    }
+}
+
+// JSON を複製して翻訳する場合と、その場で翻訳する場合を比べる
+fn translate_json_message() {
+    let translator = Translator::new(entries(
+        r#"[
+            { "en": "mismatched types", "ja": "型が不一致です" },
+            { "en": "expected `{$ty1}`, found `{$ty2}`", "ja": "`{$ty1}`を期待したが、`{$ty2}`が見つかった" }
+        ]"#,
+    ));
+    let span = r#"{"file_name":"src/main.rs","byte_start":1,"byte_end":2,"line_start":2,"line_end":2,"column_start":18,"column_end":21,"is_primary":true,"text":[{"text":"    let x: i32 = \"a\";","highlight_start":18,"highlight_end":21}],"label":"expected `i32`, found `&str`","suggested_replacement":null,"expansion":null}"#;
+    let spans = [span; 8].join(",");
+    let line = format!(
+        r#"{{"$message_type":"diagnostic","message":"mismatched types","code":{{"code":"E0308","explanation":null}},"level":"error","spans":[{spans}],"children":[{{"message":"an unknown note","spans":[{spans}],"children":[],"level":"note","rendered":null}}],"rendered":"error[E0308]: mismatched types\n"}}"#
+    );
+
+    // 1 行ずつ読み込んで翻訳する場合と同じく、読み込みも含めて計る
+    let start = Instant::now();
+    for _ in 0..10000 {
+        let json: serde_json::Value = serde_json::from_str(&line).unwrap();
+        black_box(bench::translate_json_cloned(&json, &translator));
+    }
+    println!("  clone: {:?}", start.elapsed());
+
+    let start = Instant::now();
+    for _ in 0..10000 {
+        let mut json: serde_json::Value = serde_json::from_str(&line).unwrap();
+        bench::translate_json_in_place(&mut json, &translator);
+        black_box(json);
+    }
+    println!("  in place: {:?}", start.elapsed());
+}
+
+// 翻訳データとメッセージの組（同じ形のエントリを count 件並べ、最後のエントリに一致するメッセージを含める）
+fn table_fixture(count: usize) -> (Vec<TranslateEntry>, Vec<String>) {
+    let entries = (0..count)
+        .map(|i| TranslateEntry {
+            en: format!("message {i} about `{{$name}}` with type `{{$ty}}`"),
+            ja: format!("`{{$ty}}`型の`{{$name}}`についてのメッセージ{i}"),
+            ..Default::default()
+        })
+        .collect();
+    let messages = vec![
+        format!("message {} about `x` with type `i32`", count - 1),
+        "message 0 about `s` with type `String`: extra".to_string(),
+        "an unknown message".to_string(),
+    ];
+    (entries, messages)
+}
+
+// 1000 回の翻訳で、毎回正規表現を作る場合と、作っておいた TranslationTable を使う場合を比べる
+fn translation_table() {
+    let (entries, messages) = table_fixture(200);
+    let start = Instant::now();
+    for i in 0..1000 {
+        black_box(translate_message(&messages[i % messages.len()], &entries));
+    }
+    println!("  translate_message: {:?}", start.elapsed());
+
+    let start = Instant::now();
+    let table = TranslationTable::new(entries);
+    for i in 0..1000 {
+        black_box(table.translate(&messages[i % messages.len()]));
+    }
+    println!(
+        "  TranslationTable (including build): {:?}",
+        start.elapsed()
+    );
+}
+
+// 先頭に一致すれば使う固定の英語文字列のエントリ（count 件）と、最後のエントリに一致するメッセージ
+fn literal_fixture(count: usize) -> (Vec<TranslateEntry>, String) {
+    let entries = (0..count)
+        .map(|i| TranslateEntry {
+            en: format!("literal message {i} of the table"),
+            ja: format!("固定のメッセージ{i}番"),
+            exact: Some(false),
+            ..Default::default()
+        })
+        .collect();
+    (
+        entries,
+        format!("literal message {} of the table: extra", count - 1),
+    )
+}
+
+// エントリの件数ごとに、固定の英語文字列の索引を使う場合と、すべて正規表現で試す場合を比べる
+fn literal_index() {
+    for count in [100, 1000, 10000] {
+        let (entries, message) = literal_fixture(count);
+        let mut table = TranslationTable::new(entries);
+        assert_ne!(table.translate(&message), message);
+        let start = Instant::now();
+        for _ in 0..100 {
+            black_box(bench::translate_uncached(&table, &message));
+        }
+        let indexed = start.elapsed();
+
+        bench::without_literal_index(&mut table);
+        let start = Instant::now();
+        for _ in 0..100 {
+            black_box(bench::translate_uncached(&table, &message));
+        }
+        println!(
+            "  {count} entries: index {:?}, regex only {:?}",
+            indexed,
+            start.elapsed()
+        );
+    }
+}
+
+// 同じメッセージを繰り返し翻訳する場合に、覚えておいた翻訳を使うかで比べる
+fn translation_table_cache() {
+    let (entries, messages) = table_fixture(200);
+    let table = TranslationTable::new(entries);
+    let start = Instant::now();
+    for i in 0..10000 {
+        black_box(bench::translate_uncached(
+            &table,
+            &messages[i % messages.len()],
+        ));
+    }
+    println!("  uncached: {:?}", start.elapsed());
+
+    let start = Instant::now();
+    for i in 0..10000 {
+        black_box(table.translate(&messages[i % messages.len()]));
+    }
+    println!("  cached: {:?}", start.elapsed());
+}
+
+// 診断、診断でない JSON、空の行、改行コードが "\r\n" の行を混ぜた count 行の JSONL
+fn jsonl_fixture(count: usize) -> Vec<u8> {
+    let mut data = Vec::new();
+    for i in 0..count {
+        let line = match i % 4 {
+            0 => format!(
+                r#"{{"$message_type":"diagnostic","message":"mismatched types","code":null,"level":"error","spans":[{{"label":"expected `i{i}`, found `&str`"}}],"children":[],"rendered":"error: mismatched types\n"}}"#
+            ),
+            1 => {
+                format!(r#"{{"$message_type":"artifact","artifact":"lib{i}.rlib","emit":"link"}}"#)
+            }
+            2 => String::new(),
+            _ => format!(
+                "{{\"$message_type\":\"diagnostic\",\"message\":\"unknown {i}\",\"level\":\"warning\",\"spans\":[],\"children\":[],\"rendered\":null}}\r"
+            ),
+        };
+        data.extend_from_slice(line.as_bytes());
+        data.push(b'\n');
+    }
+    data
+}
+
+// JSONL の行数ごとに、1 行ずつ翻訳する場合と並行して翻訳する場合を比べる
+fn convert_json_parallel() {
+    let translator = Translator::new(entries(
+        r#"[{ "en": "mismatched types", "ja": "型が不一致です" }]"#,
+    ));
+    for count in [16, 64, 256, 1024, 16384] {
+        let data = jsonl_fixture(count);
+        let start = Instant::now();
+        for _ in 0..10 {
+            black_box(bench::convert_json_lines(data.clone(), &translator, false));
+        }
+        let sequential = start.elapsed();
+        let start = Instant::now();
+        for _ in 0..10 {
+            black_box(bench::convert_json_lines(data.clone(), &translator, true));
+        }
+        println!(
+            "  {count} lines: sequential {:?}, parallel {:?}",
+            sequential,
+            start.elapsed()
+        );
+    }
+}
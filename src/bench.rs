@@ -0,0 +1,59 @@
+//! ベンチマーク（`benches/translate.rs`）から内部の処理を呼び出す関数（公開 API ではない）
+
+use crate::{ReplaceStrategy, TranslationTable, Translator};
+
+/// rendered を置換する（table を指定すれば、エントリの件数によらずその索引で Aho-Corasick 法を使う）
+pub fn replace_rendered(
+    rendered: &str,
+    replaced: &[(String, String)],
+    table: Option<&TranslationTable>,
+) -> String {
+    let strategy = match table.and_then(|t| t.literal.as_ref()) {
+        Some(index) => ReplaceStrategy::AhoCorasick(index),
+        None => ReplaceStrategy::Linear,
+    };
+    crate::replace_rendered(rendered, replaced, strategy)
+}
+
+/// 元の JSON を複製して、その複製を翻訳する
+pub fn translate_json_cloned(
+    json: &serde_json::Value,
+    translator: &Translator,
+) -> serde_json::Value {
+    crate::translate_json_message_with(
+        json,
+        &|m| translator.translate(m),
+        None,
+        None,
+        false,
+        translator.table.replace_strategy(),
+    )
+    .0
+}
+
+/// JSON をその場で翻訳する
+pub fn translate_json_in_place(json: &mut serde_json::Value, translator: &Translator) {
+    crate::translate_json_message_in_place(
+        json,
+        &|m| translator.translate(m),
+        None,
+        None,
+        false,
+        translator.table.replace_strategy(),
+    );
+}
+
+/// 覚えておいた翻訳を使わずに翻訳する
+pub fn translate_uncached(table: &TranslationTable, message: &str) -> String {
+    table.translate_uncached(crate::DEFAULT_LANG, message)
+}
+
+/// 固定の英語文字列の索引を使わずに、すべて正規表現で試すようにする
+pub fn without_literal_index(table: &mut TranslationTable) {
+    table.literal = None;
+}
+
+/// JSONL を翻訳する（parallel なら行数によらず並行して翻訳する）
+pub fn convert_json_lines(data: Vec<u8>, translator: &Translator, parallel: bool) -> Vec<u8> {
+    crate::convert_json_lines(data, translator, parallel)
+}
@@ -0,0 +1,180 @@
+//! ラッパーの設定（`rustc-ja-wrapper.toml`）
+//!
+//! 設定は、組み込みの既定値、設定ファイル、環境変数の順に上書きする。
+//!
+//! ```toml
+//! translate = "my-translations.json"
+//! lang = "ja"
+//! human = true
+//! debug_log = "/tmp/rustc-ja-wrapper-debug.log"
+//! disable = false
+//! ```
+
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+/// 設定ファイルの既定の名前（カレントディレクトリから探す）
+pub const CONFIG_FILE_NAME: &str = "rustc-ja-wrapper.toml";
+
+/// ラッパーの設定
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// 組み込みの翻訳データに重ねて読み込む翻訳ファイル（RUSTC_JA_TRANSLATE）
+    pub translate: Option<PathBuf>,
+    /// 翻訳先の言語（RUSTC_JA_LANG）
+    pub lang: Option<String>,
+    /// 人が読む形式と短い形式の出力も翻訳するか（RUSTC_JA_HUMAN）
+    pub human: bool,
+    /// デバッグ用のログファイル（RUSTC_JA_DEBUG_LOG、空または "1" なら一時ディレクトリ）
+    pub debug_log: Option<PathBuf>,
+    /// 出力をすべて変換せずに書き出すか（RUSTC_JA_DISABLE）
+    pub disable: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            translate: None,
+            lang: None,
+            human: true,
+            debug_log: None,
+            disable: false,
+        }
+    }
+}
+
+impl Config {
+    /// 設定ファイルの内容（TOML）を読み込む（書かれていない項目は既定値にする）
+    pub fn from_toml(toml: &str) -> Result<Config, String> {
+        toml::from_str(toml).map_err(|e| format!("failed to parse config: {}", e.message()))
+    }
+
+    /// 環境変数で設定を上書きする（`var` は環境変数の名前から値を返す関数）
+    pub fn with_env(mut self, var: impl Fn(&str) -> Option<OsString>) -> Config {
+        if let Some(path) = var("RUSTC_JA_TRANSLATE") {
+            self.translate = Some(path.into());
+        }
+        if let Some(lang) = var("RUSTC_JA_LANG") {
+            self.lang = Some(lang.to_string_lossy().into_owned());
+        }
+        if let Some(human) = var("RUSTC_JA_HUMAN") {
+            self.human = is_truthy(&human.to_string_lossy());
+        }
+        if let Some(path) = var("RUSTC_JA_DEBUG_LOG") {
+            self.debug_log = Some(path.into());
+        }
+        if let Some(disable) = var("RUSTC_JA_DISABLE") {
+            self.disable = is_truthy(&disable.to_string_lossy());
+        }
+        self
+    }
+}
+
+/// 読み込む設定ファイルを決める
+/// `explicit`（RUSTC_JA_CONFIG）が指定されていればそのファイル、
+/// なければ `dir` に `rustc-ja-wrapper.toml` があればそのファイルを使う
+pub fn find_config_file(explicit: Option<OsString>, dir: &Path) -> Option<PathBuf> {
+    if let Some(path) = explicit.filter(|p| !p.is_empty()) {
+        return Some(path.into());
+    }
+    let path = dir.join(CONFIG_FILE_NAME);
+    path.is_file().then_some(path)
+}
+
+/// 環境変数の値が有効を表すか（空、"0"、"false"、"no"、"off" 以外）
+pub fn is_truthy(value: &str) -> bool {
+    !matches!(
+        value.trim().to_ascii_lowercase().as_str(),
+        "" | "0" | "false" | "no" | "off"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 名前と値の組から環境変数を返す関数を作る
+    fn vars(pairs: &[(&str, &str)]) -> impl Fn(&str) -> Option<OsString> {
+        let pairs: Vec<(String, String)> = pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        move |name| pairs.iter().find(|(k, _)| k == name).map(|(_, v)| v.into())
+    }
+
+    #[test]
+    fn test_config_precedence() {
+        // 既定値
+        let config = Config::default().with_env(vars(&[]));
+        assert_eq!(config, Config::default());
+        assert!(config.human);
+        assert!(!config.disable);
+
+        // 設定ファイルは既定値を上書きする（書かれていない項目は既定値のまま）
+        let file = Config::from_toml(
+            "translate = \"file.json\"\nlang = \"en\"\nhuman = false\ndisable = true\n",
+        )
+        .unwrap();
+        assert_eq!(file.translate, Some(PathBuf::from("file.json")));
+        assert_eq!(file.lang.as_deref(), Some("en"));
+        assert!(!file.human);
+        assert_eq!(file.debug_log, None);
+        assert!(file.disable);
+
+        // 環境変数は設定ファイルを上書きする（設定されていない項目は設定ファイルのまま）
+        let config = file.clone().with_env(vars(&[
+            ("RUSTC_JA_TRANSLATE", "env.json"),
+            ("RUSTC_JA_HUMAN", "1"),
+            ("RUSTC_JA_DEBUG_LOG", "debug.log"),
+            ("RUSTC_JA_DISABLE", "0"),
+        ]));
+        assert_eq!(config.translate, Some(PathBuf::from("env.json")));
+        assert_eq!(config.lang.as_deref(), Some("en"));
+        assert!(config.human);
+        assert_eq!(config.debug_log, Some(PathBuf::from("debug.log")));
+        assert!(!config.disable);
+    }
+
+    #[test]
+    fn test_config_from_toml_error() {
+        let err = Config::from_toml("unknown = 1\n").unwrap_err();
+        assert!(err.starts_with("failed to parse config: "), "{err}");
+        assert!(Config::from_toml("human = \"yes\"\n").is_err());
+        assert_eq!(Config::from_toml("").unwrap(), Config::default());
+    }
+
+    #[test]
+    fn test_find_config_file() {
+        let dir = std::env::temp_dir().join(format!(
+            "rustc-ja-wrapper-test-{}-config",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        assert_eq!(find_config_file(None, &dir), None);
+
+        std::fs::write(dir.join(CONFIG_FILE_NAME), "").unwrap();
+        assert_eq!(
+            find_config_file(None, &dir),
+            Some(dir.join(CONFIG_FILE_NAME))
+        );
+        // RUSTC_JA_CONFIG の指定はカレントディレクトリのファイルより優先する
+        assert_eq!(
+            find_config_file(Some("other.toml".into()), &dir),
+            Some(PathBuf::from("other.toml"))
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_is_truthy() {
+        for value in ["1", "true", "yes", "ON"] {
+            assert!(is_truthy(value), "{value}");
+        }
+        for value in ["", "0", "false", "No", "off"] {
+            assert!(!is_truthy(value), "{value}");
+        }
+    }
+}
@@ -0,0 +1,160 @@
+//! 実際の診断に対する翻訳の網羅率の集計
+
+/// 翻訳の網羅率の集計結果
+#[derive(Debug, PartialEq)]
+pub struct Coverage {
+    pub translated: usize,
+    pub untranslated: usize,
+    /// 翻訳できなかったメッセージと出現回数（多い順）
+    pub missing: Vec<(String, usize)>,
+}
+
+/// JSONL の診断の各メッセージを翻訳して、翻訳できたものとできなかったものを数える
+pub fn measure_coverage(jsonl: &str, translate: &dyn Fn(&str) -> String) -> Coverage {
+    let mut translated = 0;
+    let mut untranslated = 0;
+    let mut missing: std::collections::HashMap<String, usize> = Default::default();
+    for line in jsonl.lines() {
+        let Ok(json) = serde_json::from_str::<serde_json::Value>(line) else {
+            continue;
+        };
+        if !crate::is_diagnostic(&json) {
+            continue;
+        }
+        for message in crate::json_messages(&json) {
+            if message.is_empty() {
+                continue;
+            }
+            if translate(&message) != message {
+                translated += 1;
+            } else {
+                untranslated += 1;
+                *missing.entry(message).or_default() += 1;
+            }
+        }
+    }
+    let mut missing: Vec<(String, usize)> = missing.into_iter().collect();
+    missing.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    Coverage {
+        translated,
+        untranslated,
+        missing,
+    }
+}
+
+/// 翻訳できなかったメッセージから、`ja` を空にした翻訳データのひな形を作る（出現回数の多い順）
+/// メッセージにプレースホルダや `{{` のような部分があれば、文字どおりに一致するよう `{` と `}` を重ねる
+pub fn untranslated_stubs(coverage: &Coverage) -> Vec<crate::TranslateEntry> {
+    coverage
+        .missing
+        .iter()
+        .map(|(message, _)| crate::TranslateEntry {
+            en: crate::escape_braces(message),
+            ..Default::default()
+        })
+        .collect()
+}
+
+/// 網羅率の集計結果を表示用の文字列にする（翻訳できなかったメッセージは上位 top 件）
+pub fn format_coverage(coverage: &Coverage, top: usize) -> String {
+    let total = coverage.translated + coverage.untranslated;
+    let percent = |n: usize| {
+        if total == 0 {
+            0.0
+        } else {
+            n as f64 * 100.0 / total as f64
+        }
+    };
+    let mut out = format!(
+        "translated:   {} / {} ({:.1}%)\nuntranslated: {} / {} ({:.1}%)\n",
+        coverage.translated,
+        total,
+        percent(coverage.translated),
+        coverage.untranslated,
+        total,
+        percent(coverage.untranslated)
+    );
+    if !coverage.missing.is_empty() {
+        out.push_str("\ntop untranslated:\n");
+        for (message, count) in coverage.missing.iter().take(top) {
+            out.push_str(&format!("{:>6}  {}\n", count, message));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{TranslateEntry, translate_message};
+
+    #[test]
+    fn test_coverage() {
+        let jsonl = concat!(
+            r#"{"$message_type":"diagnostic","message":"mismatched types","spans":[{"label":"an unknown label"}],"children":[],"rendered":null}"#,
+            "\n",
+            r#"{"$message_type":"diagnostic","message":"an unknown message","spans":[{"label":"an unknown label"},{"label":"value moved here"}],"children":[],"rendered":null}"#,
+            "\n",
+            r#"{"$message_type":"artifact","artifact":"a"}"#,
+            "\n",
+        );
+        let entries = vec![
+            TranslateEntry {
+                en: "mismatched types".to_string(),
+                ja: "型が不一致です".to_string(),
+                ..Default::default()
+            },
+            TranslateEntry {
+                en: "value moved here".to_string(),
+                ja: "ここで値を移動しました".to_string(),
+                ..Default::default()
+            },
+        ];
+        let coverage = measure_coverage(jsonl, &|m| translate_message(m, &entries));
+        assert_eq!(
+            coverage,
+            Coverage {
+                translated: 2,
+                untranslated: 3,
+                missing: vec![
+                    ("an unknown label".to_string(), 2),
+                    ("an unknown message".to_string(), 1)
+                ],
+            }
+        );
+        assert_eq!(
+            format_coverage(&coverage, 1),
+            "translated:   2 / 5 (40.0%)\nuntranslated: 3 / 5 (60.0%)\n\ntop untranslated:\n     2  an unknown label\n"
+        );
+    }
+
+    #[test]
+    fn test_untranslated_stubs() {
+        let coverage = Coverage {
+            translated: 0,
+            untranslated: 4,
+            missing: vec![
+                ("an unknown label".to_string(), 2),
+                ("invalid format string `{$x}`".to_string(), 1),
+                ("expected `{`".to_string(), 1),
+            ],
+        };
+        let stubs = untranslated_stubs(&coverage);
+        let json = serde_json::to_string(&stubs).unwrap();
+        assert_eq!(
+            json,
+            r#"[{"en":"an unknown label","ja":""},{"en":"invalid format string `{{$x}}`","ja":""},{"en":"expected `{`","ja":""}]"#
+        );
+
+        // 書き出したひな形は翻訳データとして読み込め、ja を埋めれば元のメッセージに一致する
+        let mut entries: Vec<TranslateEntry> = serde_json::from_str(&json).unwrap();
+        assert_eq!(entries.len(), 3);
+        for entry in &mut entries {
+            assert!(entry.ja.is_empty());
+            entry.ja = "翻訳".to_string();
+        }
+        for (message, _) in &coverage.missing {
+            assert_eq!(translate_message(message, &entries), "翻訳", "{message}");
+        }
+    }
+}
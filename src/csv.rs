@@ -0,0 +1,251 @@
+//! CSV 形式（`en,ja` の 2 列、見出しがあれば他の項目の列も）の翻訳データの読み込み
+//!
+//! 表計算ソフトで編集しやすいように、`"` で囲んだフィールド（`,` や改行を含められる、`""` は `"` を表す）に対応する。
+//! 先頭の行が `en,ja` で始まり、残りも `OPTIONAL_COLUMNS` の名前であれば、見出しとして読み飛ばし、
+//! 3 列目以降はその名前の項目とする（空のフィールドは省略と同じ、`exact` などは `true` か `false`）。
+
+use crate::TranslateEntry;
+
+/// 見出しで指定できる、`en` と `ja` 以外の列
+pub const OPTIONAL_COLUMNS: &[&str] = &[
+    "context",
+    "exact",
+    "regex",
+    "ignore_case",
+    "match_in_backticks",
+    "category",
+    "priority",
+    "min_version",
+    "max_version",
+];
+
+/// CSV 形式を読み込んで翻訳データにする（英語文字列の長い順に並べる）
+/// 正しくない行は読み飛ばし、その内容を警告として返す
+pub fn from_csv(csv: &str) -> (Vec<TranslateEntry>, Vec<String>) {
+    let mut entries = Vec::new();
+    let mut warnings = Vec::new();
+    let mut records = records(csv).into_iter().peekable();
+    let mut columns = vec!["en".to_string(), "ja".to_string()];
+    if let Some(Ok(header)) = records.peek().map(|r| r.fields.as_ref())
+        && header.len() >= 2
+        && header[..2] == columns[..]
+        && header[2..]
+            .iter()
+            .all(|c| OPTIONAL_COLUMNS.contains(&c.as_str()))
+    {
+        columns = header.clone();
+        records.next();
+    }
+    for record in records {
+        let line = record.line;
+        match record.fields {
+            Ok(fields) if fields.len() == 1 && fields[0].is_empty() => {}
+            Ok(fields) if fields.len() == columns.len() => {
+                let mut entry = TranslateEntry::default();
+                let result = columns
+                    .iter()
+                    .zip(fields)
+                    .try_for_each(|(column, value)| set_field(&mut entry, column, value));
+                match result {
+                    Ok(()) => entries.push(entry),
+                    Err(e) => warnings.push(format!("line {}: {} (skipped)", line, e)),
+                }
+            }
+            Ok(fields) => warnings.push(format!(
+                "line {}: expected {} fields, found {} (skipped)",
+                line,
+                columns.len(),
+                fields.len()
+            )),
+            Err(e) => warnings.push(format!("line {}: {} (skipped)", line, e)),
+        }
+    }
+    crate::file::sort_entries(&mut entries);
+    (entries, warnings)
+}
+
+// 列の名前の項目に値を設定する（空のフィールドは省略と同じ）
+fn set_field(entry: &mut TranslateEntry, column: &str, value: String) -> Result<(), String> {
+    let invalid = |value: &str| format!("invalid value {:?} for `{}`", value, column);
+    let flag = |value: &str| match value {
+        "true" => Ok(true),
+        "false" => Ok(false),
+        _ => Err(invalid(value)),
+    };
+    let text = |value: String| (!value.is_empty()).then_some(value);
+    match column {
+        "en" => entry.en = value,
+        "ja" => entry.ja = value,
+        _ if value.is_empty() => {}
+        "context" => entry.context = text(value),
+        "exact" => entry.exact = Some(flag(&value)?),
+        "regex" => entry.regex = flag(&value)?,
+        "ignore_case" => entry.ignore_case = flag(&value)?,
+        "match_in_backticks" => entry.match_in_backticks = flag(&value)?,
+        "category" => entry.category = text(value),
+        "priority" => entry.priority = value.parse().map_err(|_| invalid(&value))?,
+        "min_version" => entry.min_version = text(value),
+        "max_version" => entry.max_version = text(value),
+        _ => return Err(format!("unknown column `{}`", column)),
+    }
+    Ok(())
+}
+
+// CSV の 1 行分（引用符の中の改行を含む）
+struct Record {
+    // 開始行の行番号（1 から）
+    line: usize,
+    fields: Result<Vec<String>, &'static str>,
+}
+
+// CSV を行（レコード）に分ける
+fn records(csv: &str) -> Vec<Record> {
+    let mut records = Vec::new();
+    let mut chars = csv.chars().peekable();
+    let mut line = 1;
+    while chars.peek().is_some() {
+        let start = line;
+        let mut fields = Vec::new();
+        let mut field = String::new();
+        let mut error = None;
+        // 引用符で始まったフィールドを読み込み中か
+        let mut quoted = false;
+        // フィールドの先頭か
+        let mut at_start = true;
+        // 引用符で囲んだフィールドが閉じた後か（後には "," か改行しか来ない）
+        let mut closed = false;
+        loop {
+            let Some(c) = chars.next() else {
+                if quoted {
+                    error.get_or_insert("unterminated quoted field");
+                }
+                break;
+            };
+            if c == '\n' {
+                line += 1;
+            }
+            if quoted {
+                match c {
+                    '"' if chars.peek() == Some(&'"') => {
+                        chars.next();
+                        field.push('"');
+                    }
+                    '"' => {
+                        quoted = false;
+                        closed = true;
+                    }
+                    _ => field.push(c),
+                }
+                continue;
+            }
+            match c {
+                '"' if at_start => {
+                    quoted = true;
+                    at_start = false;
+                }
+                ',' => {
+                    fields.push(std::mem::take(&mut field));
+                    at_start = true;
+                    closed = false;
+                }
+                '\n' => break,
+                '\r' if chars.peek() == Some(&'\n') => {}
+                _ if closed => {
+                    error.get_or_insert("unexpected character after quoted field");
+                }
+                '"' => {
+                    error.get_or_insert("unexpected quote in field");
+                }
+                _ => {
+                    field.push(c);
+                    at_start = false;
+                }
+            }
+        }
+        fields.push(field);
+        records.push(Record {
+            line: start,
+            fields: error.map_or(Ok(fields), Err),
+        });
+    }
+    records
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_csv() {
+        let csv = "en,ja\r\n\
+            mismatched types,型が合いません\r\n\
+            \"expected `{$ty1}`, found `{$ty2}`\",\"`{$ty1}`を期待したが、`{$ty2}`が見つかった\"\r\n\
+            \"first line\nsecond \"\"line\"\"\",\"1行目\n2行目\"\r\n\
+            only one field\r\n\
+            \"bad\"quote,x\r\n\
+            bad\"quote,x\r\n\
+            \r\n\
+            value moved here,ここで値を移動しました\r\n";
+        let (entries, warnings) = from_csv(csv);
+        let pairs: Vec<_> = entries
+            .iter()
+            .map(|e| (e.en.as_str(), e.ja.as_str()))
+            .collect();
+        assert_eq!(
+            pairs,
+            [
+                (
+                    "expected `{$ty1}`, found `{$ty2}`",
+                    "`{$ty1}`を期待したが、`{$ty2}`が見つかった"
+                ),
+                ("first line\nsecond \"line\"", "1行目\n2行目"),
+                ("mismatched types", "型が合いません"),
+                ("value moved here", "ここで値を移動しました"),
+            ]
+        );
+        assert_eq!(
+            warnings,
+            [
+                "line 7: expected 2 fields, found 1 (skipped)",
+                "line 8: unexpected character after quoted field (skipped)",
+                "line 9: unexpected quote in field (skipped)",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_from_csv_without_header() {
+        let (entries, warnings) = from_csv("en,英語\n\"unterminated,x\n");
+        assert_eq!(entries.len(), 1);
+        assert_eq!(
+            (entries[0].en.as_str(), entries[0].ja.as_str()),
+            ("en", "英語")
+        );
+        assert_eq!(warnings, ["line 2: unterminated quoted field (skipped)"]);
+    }
+
+    #[test]
+    fn test_from_csv_optional_columns() {
+        let csv = "en,ja,exact,context,priority\n\
+            borrow of moved value,移動された値の借用しました,false,,\n\
+            expected,期待される,,mismatched types,2\n\
+            value moved here,ここで値を移動しました,yes,,\n\
+            too,few\n";
+        let (entries, warnings) = from_csv(csv);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].en, "borrow of moved value");
+        assert_eq!(entries[0].exact, Some(false));
+        assert_eq!(entries[0].context, None);
+        assert_eq!(entries[1].en, "expected");
+        assert_eq!(entries[1].exact, None);
+        assert_eq!(entries[1].context.as_deref(), Some("mismatched types"));
+        assert_eq!(entries[1].priority, 2);
+        assert_eq!(
+            warnings,
+            [
+                "line 4: invalid value \"yes\" for `exact` (skipped)",
+                "line 5: expected 5 fields, found 2 (skipped)",
+            ]
+        );
+    }
+}
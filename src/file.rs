@@ -0,0 +1,827 @@
+//! 翻訳ファイル（JSON）の読み込みと検査
+
+use crate::TranslateEntry;
+
+/// 翻訳ファイルの形式
+/// エントリの配列か、カテゴリ名（"borrow", "types" など）をキーにしてエントリの配列をまとめたオブジェクト
+#[derive(Debug)]
+pub enum TranslateFile {
+    Flat(Vec<TranslateEntry>),
+    Grouped(std::collections::BTreeMap<String, Vec<TranslateEntry>>),
+}
+
+impl TranslateFile {
+    /// JSON を読み込む（最初の文字が `{` ならオブジェクト、それ以外は配列として読み込むので、
+    /// 正しくないエントリがあれば、その位置と原因がエラーに出る）
+    pub fn from_json_str(json_str: &str) -> Result<Self, serde_json::Error> {
+        if json_str.trim_start().starts_with('{') {
+            serde_json::from_str(json_str).map(TranslateFile::Grouped)
+        } else {
+            serde_json::from_str(json_str).map(TranslateFile::Flat)
+        }
+    }
+}
+
+/// 翻訳データを読み込み、エントリの一覧にする
+/// カテゴリ分けされたエントリは、`category` を省略していればそのカテゴリ名を `category` にする
+/// categories が指定された場合、カテゴリのあるエントリはそのカテゴリのもののみ残す（`filter_by_category` を参照）
+pub fn parse_translations(
+    json_str: &str,
+    categories: Option<&[String]>,
+) -> Result<Vec<TranslateEntry>, serde_json::Error> {
+    let file = TranslateFile::from_json_str(json_str)?;
+    let entries: Vec<TranslateEntry> = match file {
+        TranslateFile::Flat(entries) => entries,
+        TranslateFile::Grouped(groups) => groups
+            .into_iter()
+            .flat_map(|(category, entries)| {
+                entries.into_iter().map(move |mut e| {
+                    e.category.get_or_insert_with(|| category.clone());
+                    e
+                })
+            })
+            .collect(),
+    };
+    let mut entries = filter_by_category(entries, categories, false);
+    sort_entries(&mut entries);
+    Ok(entries)
+}
+
+/// categories が指定された場合、カテゴリがそのいずれでもないエントリを除く
+/// カテゴリのないエントリは残す（strict なら除く）
+pub fn filter_by_category(
+    entries: Vec<TranslateEntry>,
+    categories: Option<&[String]>,
+    strict: bool,
+) -> Vec<TranslateEntry> {
+    let Some(categories) = categories else {
+        return entries;
+    };
+    entries
+        .into_iter()
+        .filter(|e| {
+            e.category
+                .as_ref()
+                .map_or(!strict, |category| categories.contains(category))
+        })
+        .collect()
+}
+
+/// 翻訳データを読み込み、エントリの一覧と警告を返す（`parse_translations` と同じく並べ替える）
+/// JSON として読み込めない場合は、エントリは空にして警告を返す
+/// 英語文字列が空のエントリは、どのメッセージにも一致してしまうので、警告して読み飛ばす
+/// `en` と翻訳でプレースホルダが一致しないエントリは警告し、翻訳に `en` にないプレースホルダが
+/// ある場合は `{$name}` がそのまま出力されてしまうので読み飛ばす
+pub fn load_translations(
+    json_str: &str,
+    categories: Option<&[String]>,
+) -> (Vec<TranslateEntry>, Vec<String>) {
+    let entries = match parse_translations(json_str, categories) {
+        Ok(entries) => entries,
+        Err(e) => {
+            return (
+                Vec::new(),
+                vec![format!("failed to parse translations: {}", e)],
+            );
+        }
+    };
+    let mut warnings = Vec::new();
+    let entries = entries
+        .into_iter()
+        .filter(|e| {
+            if e.en.is_empty() {
+                warnings.push(format!(
+                    "skipped an entry with an empty `en` (ja: {:?})",
+                    e.ja
+                ));
+                return false;
+            }
+            for bound in [&e.min_version, &e.max_version].into_iter().flatten() {
+                if parse_rustc_version(bound).is_none() {
+                    warnings.push(format!(
+                        "ignored an invalid version {:?} (en: {:?})",
+                        bound, e.en
+                    ));
+                }
+            }
+            let mut keep = true;
+            for mismatch in placeholder_mismatches(e) {
+                if let PlaceholderMismatch::Extra { .. } = mismatch {
+                    warnings.push(format!("skipped an entry: {}", mismatch));
+                    keep = false;
+                } else {
+                    warnings.push(mismatch.to_string());
+                }
+            }
+            keep
+        })
+        .collect();
+    (entries, warnings)
+}
+
+/// rustc のバージョンを読み取る（"1.80"、"1.80.0"、"rustc 1.80.0-nightly (...)" など、patch は省略時 0）
+pub fn parse_rustc_version(version: &str) -> Option<(u64, u64, u64)> {
+    static VERSION_RE: once_cell::sync::Lazy<regex::Regex> =
+        once_cell::sync::Lazy::new(|| regex::Regex::new(r"(\d+)\.(\d+)(?:\.(\d+))?").unwrap());
+    let caps = VERSION_RE.captures(version)?;
+    let number = |i: usize| caps.get(i).map_or(Some(0), |m| m.as_str().parse().ok());
+    Some((number(1)?, number(2)?, number(3)?))
+}
+
+/// rustc のバージョンの範囲（`min_version` と `max_version`、"1.80" のように patch を省略できる）を指定したエントリがあるか
+pub fn has_version_range(entries: &[TranslateEntry]) -> bool {
+    entries
+        .iter()
+        .any(|e| e.min_version.is_some() || e.max_version.is_some())
+}
+
+/// rustc のバージョンが範囲に入らないエントリを除く（読み取れない範囲の指定は無視する）
+/// バージョンが分からない（None や読み取れない）場合は除かない
+pub fn filter_by_version(
+    entries: Vec<TranslateEntry>,
+    version: Option<&str>,
+) -> Vec<TranslateEntry> {
+    let Some(version) = version.and_then(parse_rustc_version) else {
+        return entries;
+    };
+    entries
+        .into_iter()
+        .filter(|e| {
+            let min = e.min_version.as_deref().and_then(parse_rustc_version);
+            let max = e.max_version.as_deref().and_then(max_version_bound);
+            min.is_none_or(|min| min <= version) && max.is_none_or(|max| version <= max)
+        })
+        .collect()
+}
+
+// max_version の上限（"1.79" のように patch を省略した場合は 1.79 のどの patch も含める）
+fn max_version_bound(bound: &str) -> Option<(u64, u64, u64)> {
+    let (major, minor, patch) = parse_rustc_version(bound)?;
+    let has_patch = bound.trim().split('.').count() > 2;
+    Some((major, minor, if has_patch { patch } else { u64::MAX }))
+}
+
+// 英語文字列の長いものを先、短いものを後に並べ替える
+// 同じ長さでは priority の大きいもの、context を持つものの順に先に試す（それも同じなら元の順）
+pub(crate) fn sort_entries(entries: &mut [TranslateEntry]) {
+    entries.sort_by_key(|e| {
+        (
+            std::cmp::Reverse(e.en.len()),
+            std::cmp::Reverse(e.priority),
+            e.context.is_none(),
+        )
+    });
+}
+
+// 同じエントリとみなすためのキー（英語文字列と文脈）
+fn entry_key(entry: &TranslateEntry) -> (String, Option<String>) {
+    (entry.en.clone(), entry.context.clone())
+}
+
+/// 同じ英語文字列（と文脈）のエントリは、最後のものだけ残す（残したエントリの順は変えない）
+/// 残したエントリと、除いたエントリを返す
+pub fn dedup_translations(
+    entries: Vec<TranslateEntry>,
+) -> (Vec<TranslateEntry>, Vec<TranslateEntry>) {
+    let mut seen = std::collections::HashSet::new();
+    let (mut kept, mut dropped): (Vec<_>, Vec<_>) = entries
+        .into_iter()
+        .rev()
+        .partition(|e| seen.insert(entry_key(e)));
+    kept.reverse();
+    dropped.reverse();
+    (kept, dropped)
+}
+
+/// 翻訳データに別の翻訳データを重ねる
+/// 同じ英語文字列（と文脈）のエントリは置き換え、新しいエントリは追加して、英語文字列の長い順に並べ直す
+pub fn merge_translations(
+    mut base: Vec<TranslateEntry>,
+    overrides: Vec<TranslateEntry>,
+) -> Vec<TranslateEntry> {
+    let mut index: std::collections::HashMap<(String, Option<String>), usize> = base
+        .iter()
+        .enumerate()
+        .map(|(i, e)| (entry_key(e), i))
+        .collect();
+    for entry in overrides {
+        match index.get(&entry_key(&entry)) {
+            Some(&i) => base[i] = entry,
+            None => {
+                index.insert(entry_key(&entry), base.len());
+                base.push(entry);
+            }
+        }
+    }
+    sort_entries(&mut base);
+    base
+}
+
+/// 英語と日本語を入れ替えた翻訳データを作る（日本語が長いものを先に試す）
+/// 自由な日本語の文から英語に戻すことになるので、固定の文字列やプレースホルダが少ないもの以外は正確ではない
+pub fn reverse_entries(entries: &[TranslateEntry]) -> Vec<TranslateEntry> {
+    let mut reversed: Vec<TranslateEntry> = entries
+        .iter()
+        .filter(|e| !e.ja.is_empty() && !e.regex)
+        .map(|e| TranslateEntry {
+            en: e.ja.clone(),
+            ja: e.en.clone(),
+            ignore_case: e.ignore_case,
+            exact: e.exact,
+            ..Default::default()
+        })
+        .collect();
+    sort_entries(&mut reversed);
+    reversed
+}
+
+/// 翻訳ファイルの検査で見つかった問題
+#[derive(Debug, PartialEq)]
+pub enum CheckIssue {
+    Error(String),
+    Warning(String),
+}
+
+/// 翻訳ファイルの内容を検査する
+/// - JSON として読み込めない（エラー、該当する行を示す）
+/// - 翻訳にあるプレースホルダが英語にない、または英語にあるプレースホルダが翻訳にない（エラー）
+/// - 同じ英語文字列のエントリが複数ある、英語文字列が空（警告）
+/// - lang の翻訳がない、言語コードの形でない不明なキーがある（警告、`"jx"` のような書き間違いは翻訳がないことで分かる）
+pub fn check_translations(json_str: &str, lang: &str) -> Vec<CheckIssue> {
+    let file = match TranslateFile::from_json_str(json_str) {
+        Ok(file) => file,
+        Err(e) => {
+            let line = json_str
+                .lines()
+                .nth(e.line().saturating_sub(1))
+                .unwrap_or("");
+            return vec![CheckIssue::Error(format!(
+                "{}\n{:>5} | {}\n      | {}^",
+                e,
+                e.line(),
+                line,
+                " ".repeat(e.column().saturating_sub(1))
+            ))];
+        }
+    };
+    let entries: Vec<TranslateEntry> = match file {
+        TranslateFile::Flat(entries) => entries,
+        TranslateFile::Grouped(groups) => groups.into_values().flatten().collect(),
+    };
+
+    let mut issues = Vec::new();
+    let mut seen = std::collections::HashSet::new();
+    for entry in &entries {
+        if entry.en.is_empty() {
+            issues.push(CheckIssue::Warning(format!(
+                "empty `en` (ja: {:?})",
+                entry.ja
+            )));
+            continue;
+        }
+        if !seen.insert(entry_key(entry)) {
+            issues.push(CheckIssue::Warning(format!(
+                "duplicate `en`: {:?}",
+                entry.en
+            )));
+        }
+        for key in entry.langs.keys().filter(|k| !is_lang_code(k)) {
+            issues.push(CheckIssue::Warning(format!(
+                "unknown key `{}`: {:?}",
+                key, entry.en
+            )));
+        }
+        if entry.text_for(lang).is_none_or(str::is_empty) {
+            issues.push(CheckIssue::Warning(format!(
+                "no translation for `{}`: {:?}",
+                lang, entry.en
+            )));
+        }
+        if entry.regex {
+            issues.extend(check_regex_entry(entry));
+            continue;
+        }
+        for kind in crate::placeholder_kinds(&entry.en) {
+            if crate::placeholder_class(kind).is_none() {
+                issues.push(CheckIssue::Error(format!(
+                    "unknown placeholder kind `{}` in `en`: {:?}",
+                    kind, entry.en
+                )));
+            }
+        }
+        issues.extend(
+            placeholder_mismatches(entry)
+                .into_iter()
+                .map(|m| CheckIssue::Error(m.to_string())),
+        );
+    }
+    issues
+}
+
+// 翻訳の言語コードの形か（"ja"、"ko"、"zh-Hant"、"pt_BR" など）
+fn is_lang_code(key: &str) -> bool {
+    let mut parts = key.split(['-', '_']);
+    parts
+        .next()
+        .is_some_and(|l| (2..=3).contains(&l.len()) && l.bytes().all(|b| b.is_ascii_lowercase()))
+        && parts.all(|p| !p.is_empty() && p.bytes().all(|b| b.is_ascii_alphanumeric()))
+}
+
+/// `en` と翻訳でプレースホルダが一致しないこと
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum PlaceholderMismatch {
+    /// 翻訳に `en` のプレースホルダがない（一致した文字列が翻訳から抜け落ちる）
+    Missing {
+        lang: String,
+        name: String,
+        en: String,
+    },
+    /// 翻訳に `en` にないプレースホルダがある（`{$name}` がそのまま出力される）
+    Extra {
+        lang: String,
+        name: String,
+        en: String,
+    },
+}
+
+impl std::fmt::Display for PlaceholderMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PlaceholderMismatch::Missing { lang, name, en } => write!(
+                f,
+                "`en` has placeholder `{{${}}}` that is not in `{}`: {:?}",
+                name, lang, en
+            ),
+            PlaceholderMismatch::Extra { lang, name, en } => write!(
+                f,
+                "`{}` has placeholder `{{${}}}` that is not in `en`: {:?}",
+                lang, name, en
+            ),
+        }
+    }
+}
+
+// `en` と各言語の翻訳で、プレースホルダの名前が一致しないものを返す（正規表現のエントリは調べない）
+// 複数形の翻訳は複数形の英語（省略時は `en`）と比べ、言語は "plural.ja" のように表す
+fn placeholder_mismatches(entry: &TranslateEntry) -> Vec<PlaceholderMismatch> {
+    let mut mismatches = Vec::new();
+    if entry.regex {
+        return mismatches;
+    }
+    let en_names = crate::placeholder_names(&entry.en);
+    // 複数形があるエントリの単数形は `{$count}` が 1 の場合のみ使うので、`{$count}` は省略できる
+    let singular_omittable = entry.plural.is_some();
+    let mut targets: Vec<(String, &[&str], &str, bool)> = translations_of(entry)
+        .map(|(lang, text)| {
+            (
+                lang.to_string(),
+                en_names.as_slice(),
+                text,
+                singular_omittable,
+            )
+        })
+        .collect();
+    let plural_en_names = entry
+        .plural
+        .as_ref()
+        .map(|p| crate::placeholder_names(p.en.as_deref().unwrap_or(&entry.en)))
+        .unwrap_or_default();
+    if let Some(plural) = &entry.plural {
+        targets.extend(
+            plural
+                .texts
+                .iter()
+                .filter(|(_, text)| !text.is_empty())
+                .map(|(lang, text)| {
+                    (
+                        format!("plural.{}", lang),
+                        plural_en_names.as_slice(),
+                        text.as_str(),
+                        false,
+                    )
+                }),
+        );
+    }
+    for (lang, en_names, text, count_omittable) in targets {
+        let names = crate::placeholder_names(text);
+        for name in names.iter().filter(|n| !en_names.contains(n)) {
+            mismatches.push(PlaceholderMismatch::Extra {
+                lang: lang.clone(),
+                name: name.to_string(),
+                en: entry.en.clone(),
+            });
+        }
+        let omittable = |n: &str| count_omittable && n == crate::COUNT_PLACEHOLDER;
+        for name in en_names
+            .iter()
+            .filter(|n| !names.contains(n) && !omittable(n))
+        {
+            mismatches.push(PlaceholderMismatch::Missing {
+                lang: lang.clone(),
+                name: name.to_string(),
+                en: entry.en.clone(),
+            });
+        }
+    }
+    mismatches
+}
+
+// エントリの各言語の翻訳（空のものは未翻訳なので除く）
+fn translations_of(entry: &TranslateEntry) -> impl Iterator<Item = (&str, &str)> {
+    std::iter::once(("ja", entry.ja.as_str()))
+        .chain(entry.langs.iter().map(|(l, t)| (l.as_str(), t.as_str())))
+        .filter(|(_, text)| !text.is_empty())
+}
+
+// 正規表現のエントリを検査する
+// 正規表現として正しくない、または翻訳が存在しないグループを参照している（エラー）
+fn check_regex_entry(entry: &TranslateEntry) -> Vec<CheckIssue> {
+    let re = match regex::Regex::new(&entry.en) {
+        Ok(re) => re,
+        Err(_) => {
+            return vec![CheckIssue::Error(format!("invalid regex: {:?}", entry.en))];
+        }
+    };
+    let groups = re.captures_len() - 1;
+    let mut issues = Vec::new();
+    for (lang, text) in translations_of(entry) {
+        for name in crate::placeholder_names(text) {
+            let is_group = name
+                .parse::<usize>()
+                .is_ok_and(|n| (1..=groups).contains(&n))
+                || re.capture_names().flatten().any(|n| n == name);
+            if !is_group {
+                issues.push(CheckIssue::Error(format!(
+                    "`{}` has placeholder `{{${}}}` that is not a group in `en`: {:?}",
+                    lang, name, entry.en
+                )));
+            }
+        }
+    }
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dedup_translations() {
+        let entries = parse_translations(
+            r#"[
+                { "en": "mismatched types", "ja": "古い翻訳" },
+                { "en": "value moved here", "ja": "ここで値を移動しました" },
+                { "en": "mismatched types", "ja": "型が不一致です" },
+                { "en": "mismatched types", "ja": "文脈つき", "context": "E0308" }
+            ]"#,
+            None,
+        )
+        .unwrap();
+        let (kept, dropped) = dedup_translations(entries);
+        let ja: Vec<&str> = kept.iter().map(|e| e.ja.as_str()).collect();
+        assert_eq!(ja, ["文脈つき", "ここで値を移動しました", "型が不一致です"]);
+        assert_eq!(dropped.len(), 1);
+        assert_eq!(dropped[0].ja, "古い翻訳");
+    }
+
+    #[test]
+    fn test_filter_by_version() {
+        let json_str = r#"[
+            { "en": "always", "ja": "常に" },
+            { "en": "old", "ja": "古い", "max_version": "1.79" },
+            { "en": "new", "ja": "新しい", "min_version": "1.80.0" },
+            { "en": "range", "ja": "範囲", "min_version": "1.78", "max_version": "1.80.1" }
+        ]"#;
+        let (entries, warnings) = load_translations(json_str, None);
+        assert!(warnings.is_empty(), "{warnings:?}");
+        assert!(has_version_range(&entries));
+
+        let ens = |version: Option<&str>| -> Vec<String> {
+            let mut ens: Vec<String> = filter_by_version(entries.clone(), version)
+                .into_iter()
+                .map(|e| e.en)
+                .collect();
+            ens.sort();
+            ens
+        };
+        assert_eq!(ens(Some("1.79.9")), ["always", "old", "range"]);
+        assert_eq!(
+            ens(Some("rustc 1.80.0 (051478957 2024-07-21)")),
+            ["always", "new", "range"]
+        );
+        assert_eq!(ens(Some("1.81.0-nightly")), ["always", "new"]);
+        assert_eq!(ens(Some("1.77")), ["always", "old"]);
+        // バージョンが分からない場合は除かない
+        assert_eq!(ens(None), ["always", "new", "old", "range"]);
+        assert_eq!(ens(Some("unknown")), ["always", "new", "old", "range"]);
+
+        assert_eq!(
+            parse_rustc_version("rustc 1.95.0-nightly"),
+            Some((1, 95, 0))
+        );
+        assert_eq!(parse_rustc_version("1.80"), Some((1, 80, 0)));
+        assert_eq!(parse_rustc_version("latest"), None);
+
+        let (_, warnings) =
+            load_translations(r#"[{ "en": "a", "ja": "あ", "min_version": "new" }]"#, None);
+        assert_eq!(warnings.len(), 1, "{warnings:?}");
+    }
+
+    #[test]
+    fn test_load_translations() {
+        let (entries, warnings) = load_translations(
+            r#"[
+                { "en": "mismatched types", "ja": "型が不一致です" },
+                { "en": "", "ja": "空" }
+            ]"#,
+            None,
+        );
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].en, "mismatched types");
+        assert_eq!(
+            warnings,
+            ["skipped an entry with an empty `en` (ja: \"空\")"]
+        );
+
+        // プレースホルダが一致しない（`ja` にないものは警告のみ、`en` にないものは読み飛ばす）
+        let (entries, warnings) = load_translations(
+            r#"[
+                { "en": "unused variable: `{$name}`", "ja": "変数が使われていません" },
+                { "en": "value moved here", "ja": "`{$name}`をここで移動しました" }
+            ]"#,
+            None,
+        );
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].en, "unused variable: `{$name}`");
+        assert_eq!(
+            warnings,
+            [
+                "`en` has placeholder `{$name}` that is not in `ja`: \"unused variable: `{$name}`\"",
+                "skipped an entry: `ja` has placeholder `{$name}` that is not in `en`: \"value moved here\"",
+            ]
+        );
+
+        // 不正な JSON
+        let (entries, warnings) = load_translations(r#"[{ "en": "mismatched types" "#, None);
+        assert!(entries.is_empty());
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].starts_with("failed to parse translations: "));
+    }
+
+    #[test]
+    fn test_parse_translations_priority() {
+        // 同じ長さで、どちらも "expected `i32`, found `i32`" に一致する
+        let json_str = r#"[
+            { "en": "expected `{$ty}`, found `i32`", "ja": "A" },
+            { "en": "expected `i32`, found `{$ty}`", "ja": "B", "priority": 1 },
+            { "en": "expected `u32`, found `{$ty}`", "ja": "C" }
+        ]"#;
+        let entries = parse_translations(json_str, None).unwrap();
+        let ja: Vec<&str> = entries.iter().map(|e| e.ja.as_str()).collect();
+        assert_eq!(ja, ["B", "A", "C"]);
+        assert_eq!(
+            crate::translate_message("expected `i32`, found `i32`", &entries),
+            "B"
+        );
+    }
+
+    #[test]
+    fn test_parse_translations_grouped() {
+        let json_str = r#"{
+            "borrow": [
+                { "en": "value moved here", "ja": "ここで値を移動しました" }
+            ],
+            "types": [
+                { "en": "mismatched types", "ja": "型が不一致です" },
+                { "en": "expected due to this", "ja": "これにより期待される" }
+            ]
+        }"#;
+
+        // すべてのカテゴリが平坦化され、長い順に並ぶ
+        let entries = parse_translations(json_str, None).unwrap();
+        let en: Vec<&str> = entries.iter().map(|e| e.en.as_str()).collect();
+        assert_eq!(
+            en,
+            [
+                "expected due to this",
+                "value moved here",
+                "mismatched types"
+            ]
+        );
+
+        // カテゴリで絞り込む
+        let categories = vec!["borrow".to_string()];
+        let entries = parse_translations(json_str, Some(&categories)).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].ja, "ここで値を移動しました");
+
+        assert_eq!(entries[0].category.as_deref(), Some("borrow"));
+
+        // 配列形式でカテゴリを省略したエントリはカテゴリ指定に関係なく読み込む
+        let json_str = r#"[{ "en": "mismatched types", "ja": "型が不一致です" }]"#;
+        assert_eq!(
+            parse_translations(json_str, Some(&categories))
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_filter_by_category() {
+        let json_str = r#"{
+            "borrow": [
+                { "en": "value moved here", "ja": "ここで値を移動しました" },
+                { "en": "unused variable: `{$name}`", "ja": "変数が使われていません: `{$name}`", "category": "lints" }
+            ],
+            "types": [{ "en": "mismatched types", "ja": "型が不一致です" }]
+        }"#;
+        let flat_str = r#"[
+            { "en": "borrow of moved value", "ja": "移動された値の借用しました", "category": "borrow" },
+            { "en": "expected due to this", "ja": "これにより期待される" }
+        ]"#;
+        let mut entries = parse_translations(json_str, None).unwrap();
+        entries.extend(parse_translations(flat_str, None).unwrap());
+        assert_eq!(entries.len(), 5);
+
+        let ens = |categories: &[&str], strict: bool| -> Vec<String> {
+            let categories: Vec<String> = categories.iter().map(|c| c.to_string()).collect();
+            let mut ens: Vec<String> =
+                filter_by_category(entries.clone(), Some(&categories), strict)
+                    .into_iter()
+                    .map(|e| e.en)
+                    .collect();
+            ens.sort();
+            ens
+        };
+        // エントリの category はグループのカテゴリより優先する
+        assert_eq!(
+            ens(&["borrow"], false),
+            [
+                "borrow of moved value",
+                "expected due to this",
+                "value moved here"
+            ]
+        );
+        assert_eq!(
+            ens(&["lints", "types"], false),
+            [
+                "expected due to this",
+                "mismatched types",
+                "unused variable: `{$name}`"
+            ]
+        );
+        // strict ならカテゴリのないエントリも除く
+        assert_eq!(
+            ens(&["borrow"], true),
+            ["borrow of moved value", "value moved here"]
+        );
+        assert!(ens(&[], true).is_empty());
+        assert_eq!(filter_by_category(entries.clone(), None, true).len(), 5);
+
+        // 読み込み時の絞り込みは、配列形式のエントリの category にも使う
+        let categories = vec!["types".to_string()];
+        let entries = parse_translations(flat_str, Some(&categories)).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].en, "expected due to this");
+    }
+
+    #[test]
+    fn test_merge_translations() {
+        let base = parse_translations(
+            r#"[
+                { "en": "mismatched types", "ja": "型が不一致です" },
+                { "en": "value moved here", "ja": "ここで値を移動しました" }
+            ]"#,
+            None,
+        )
+        .unwrap();
+        let overrides = parse_translations(
+            r#"[
+                { "en": "value moved here", "ja": "ここで値が移動しました" },
+                { "en": "expected due to this", "ja": "これにより期待される" }
+            ]"#,
+            None,
+        )
+        .unwrap();
+
+        let merged = merge_translations(base, overrides);
+        let pairs: Vec<(&str, &str)> = merged
+            .iter()
+            .map(|e| (e.en.as_str(), e.ja.as_str()))
+            .collect();
+        assert_eq!(
+            pairs,
+            [
+                ("expected due to this", "これにより期待される"),
+                ("mismatched types", "型が不一致です"),
+                ("value moved here", "ここで値が移動しました"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_check_translations() {
+        // 組み込みの翻訳データには問題がない
+        assert_eq!(
+            check_translations(include_str!("../assets/translate.ja.json"), "ja"),
+            []
+        );
+
+        // JSON として読み込めない
+        let issues = check_translations("[\n    { \"en\": \"a\", \"ja\": }\n]", "ja");
+        let [CheckIssue::Error(msg)] = &issues[..] else {
+            panic!("unexpected issues: {:?}", issues);
+        };
+        assert!(msg.contains("    2 |     { \"en\": \"a\", \"ja\": }"));
+
+        // 型の正しくないエントリは、その位置と原因を示す
+        for json_str in [
+            "[\n    { \"en\": \"a\", \"ja\": \"あ\" },\n    { \"en\": \"b\", \"ja\": 5 }\n]",
+            "{\n  \"types\": [\n    { \"en\": \"b\", \"ja\": 5 }\n  ]\n}",
+        ] {
+            let issues = check_translations(json_str, "ja");
+            let [CheckIssue::Error(msg)] = &issues[..] else {
+                panic!("unexpected issues: {:?}", issues);
+            };
+            assert!(msg.starts_with("invalid type: integer `5`"), "{msg}");
+            assert!(
+                msg.contains("    3 |     { \"en\": \"b\", \"ja\": 5 }"),
+                "{msg}"
+            );
+        }
+
+        let issues = check_translations(
+            r#"[
+                { "en": "mismatched types", "jx": "型が不一致です" },
+                { "en": "expected", "ja": "期待される", "comment": "note" },
+                { "en": "value moved here", "ko": "여기서 값이 이동됨", "zh-Hant": "值在此處移動" }
+            ]"#,
+            "ja",
+        );
+        assert_eq!(
+            issues,
+            [
+                CheckIssue::Warning("no translation for `ja`: \"mismatched types\"".to_string()),
+                CheckIssue::Warning("unknown key `comment`: \"expected\"".to_string()),
+                CheckIssue::Warning("no translation for `ja`: \"value moved here\"".to_string()),
+            ]
+        );
+
+        let issues = check_translations(
+            r#"[
+                { "en": "unused variable: `{$name}`", "ja": "変数が使われていません: `{$nmae}`" },
+                { "en": "expected `{$ty}`", "ja": "期待される型", "ko": "`{$ty}` 예상" },
+                { "en": "mismatched types", "ja": "型が不一致です" },
+                { "en": "mismatched types", "ja": "型が合いません" },
+                { "en": "", "ja": "空" },
+                { "en": "(\\d+) errors", "ja": "{$1}個のエラー{$2}", "regex": true },
+                { "en": "(\\d+ errors", "ja": "エラー", "regex": true },
+                { "en": "cannot find `{$name:ident}` in `{$path:module}`", "ja": "`{$path}`に`{$name}`が見つかりません" },
+                { "en": "{$count} previous error", "ja": "先行するエラー", "plural": { "en": "{$count} previous errors", "ja": "先行する{$count}個のエラー" } },
+                { "en": "{$count} warning emitted", "ja": "警告が出ました", "plural": { "en": "{$count} warnings emitted", "ja": "複数の警告が出ました" } },
+                { "en": "{$count} error in `{$name}`", "ja": "`{$name}`のエラー", "plural": { "ja": "`{$nmae}`の{$count}個のエラー" } }
+            ]"#,
+            "ja",
+        );
+        assert_eq!(
+            issues,
+            [
+                CheckIssue::Error(
+                    "`ja` has placeholder `{$nmae}` that is not in `en`: \"unused variable: `{$name}`\""
+                        .to_string()
+                ),
+                CheckIssue::Error(
+                    "`en` has placeholder `{$name}` that is not in `ja`: \"unused variable: `{$name}`\""
+                        .to_string()
+                ),
+                CheckIssue::Error(
+                    "`en` has placeholder `{$ty}` that is not in `ja`: \"expected `{$ty}`\"".to_string()
+                ),
+                CheckIssue::Warning("duplicate `en`: \"mismatched types\"".to_string()),
+                CheckIssue::Warning("empty `en` (ja: \"空\")".to_string()),
+                CheckIssue::Error(
+                    "`ja` has placeholder `{$2}` that is not a group in `en`: \"(\\\\d+) errors\""
+                        .to_string()
+                ),
+                CheckIssue::Error("invalid regex: \"(\\\\d+ errors\"".to_string()),
+                CheckIssue::Error(
+                    "unknown placeholder kind `module` in `en`: \"cannot find `{$name:ident}` in `{$path:module}`\""
+                        .to_string()
+                ),
+                CheckIssue::Error(
+                    "`en` has placeholder `{$count}` that is not in `plural.ja`: \"{$count} warning emitted\""
+                        .to_string()
+                ),
+                CheckIssue::Error(
+                    "`plural.ja` has placeholder `{$nmae}` that is not in `en`: \"{$count} error in `{$name}`\""
+                        .to_string()
+                ),
+                CheckIssue::Error(
+                    "`en` has placeholder `{$name}` that is not in `plural.ja`: \"{$count} error in `{$name}`\""
+                        .to_string()
+                ),
+            ]
+        );
+    }
+}
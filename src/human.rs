@@ -0,0 +1,341 @@
+//! 人が読む形式（`--error-format=human`、rustc の既定）と短い形式（`--error-format=short`）の出力の翻訳
+
+use crate::{TranslateEntry, TranslationTable, strip_ansi};
+
+/// 人が読む形式の出力を日本語に翻訳する
+/// 翻訳対象は以下の部分で、ソースコードの行や下線（`^^^^` や `----`）などはそのまま残す
+/// - `error[E0382]: ` や `warning: ` などの見出しに続くメッセージ
+/// - `= note: ` や `= help: ` に続くメッセージ
+/// - 下線に続くラベル
+///
+/// cargo 自身の出力（`warning: unused manifest key: ...` や `error: could not compile ...` など）も
+/// 同じ見出しの形式なので、同じ翻訳データで翻訳する
+pub fn translate_human(text: &str, translations: &[TranslateEntry]) -> String {
+    let table = TranslationTable::new(translations.to_vec());
+    translate_human_with(text, &|m| table.translate(m))
+}
+
+/// 短い形式の出力を日本語に翻訳する
+/// `src/main.rs:4:5: error[E0382]: ` のような位置と見出しに続くメッセージを翻訳し、位置はそのまま残す
+pub fn translate_short(text: &str, translations: &[TranslateEntry]) -> String {
+    let table = TranslationTable::new(translations.to_vec());
+    translate_short_with(text, &|m| table.translate(m))
+}
+
+/// 保存しておいた rustc の出力などのテキストを、1 行ずつ日本語に翻訳する（改行コードはそのまま残す）
+/// 短い形式の位置と見出しで始まる行は `translate_short`、それ以外の行は `translate_human` と同じく翻訳し、
+/// 空の行や翻訳対象でない行はそのまま残す
+pub fn translate_lines(input: &str, table: &TranslationTable) -> String {
+    let translate = |m: &str| table.translate(m);
+    translate_lines_with(input, &|line| {
+        if LOCATION_RE.is_match(line) {
+            translate_short_line(line, &translate)
+        } else {
+            translate_human_line(line, &translate)
+        }
+    })
+}
+
+// 見出し（"error[E0382]: ..." など）
+static HEADER_RE: once_cell::sync::Lazy<regex::Regex> = once_cell::sync::Lazy::new(|| {
+    regex::Regex::new(r"^((?:error|warning|note|help)(?:\[\w+\])?: )(.+)$").unwrap()
+});
+
+// 人が読む形式の出力を、指定した関数で翻訳する（改行コードはそのまま残す）
+pub(crate) fn translate_human_with(text: &str, translate: &dyn Fn(&str) -> String) -> String {
+    translate_lines_with(text, &|line| translate_human_line(line, translate))
+}
+
+// 短い形式の出力を、指定した関数で翻訳する（改行コードはそのまま残す）
+pub(crate) fn translate_short_with(text: &str, translate: &dyn Fn(&str) -> String) -> String {
+    translate_lines_with(text, &|line| translate_short_line(line, translate))
+}
+
+// 各行を改行コードを除いて変換する
+fn translate_lines_with(text: &str, translate_line: &dyn Fn(&str) -> String) -> String {
+    text.split_inclusive('\n')
+        .map(|line| {
+            let body = line.trim_end_matches(['\n', '\r']);
+            let ending = &line[body.len()..];
+            format!("{}{}", translate_colored_line(body, translate_line), ending)
+        })
+        .collect()
+}
+
+// 色付きの出力（ANSI のエスケープシーケンスを含む行）は、エスケープシーケンスを除いた文字列で翻訳する
+// 翻訳で変わらなかった部分と変わった部分の境界のエスケープシーケンスは元の位置に残し、
+// 変わった部分の途中にあったものは翻訳後の文字列の後ろに残す
+fn translate_colored_line(line: &str, translate_line: &dyn Fn(&str) -> String) -> String {
+    if !line.contains('\x1b') {
+        return translate_line(line);
+    }
+    let (plain, positions) = strip_ansi(line);
+    let translated = translate_line(&plain);
+    if plain.is_empty() || translated == plain {
+        return line.to_string();
+    }
+
+    // 翻訳前後で共通の先頭と末尾のバイト数（文字境界で区切る）
+    let prefix = plain
+        .char_indices()
+        .zip(translated.chars())
+        .find(|((_, a), b)| a != b)
+        .map_or(plain.len().min(translated.len()), |((i, _), _)| i);
+    let max_suffix = plain.len().min(translated.len()) - prefix;
+    let suffix: usize = plain[prefix..]
+        .chars()
+        .rev()
+        .zip(translated[prefix..].chars().rev())
+        .take_while(|(a, b)| a == b)
+        .map(|(c, _)| c.len_utf8())
+        .scan(0, |total, len| {
+            *total += len;
+            (*total <= max_suffix).then_some(len)
+        })
+        .sum();
+
+    // 変わった部分の元の行での範囲
+    let end_of_plain = positions[plain.len() - 1] + 1;
+    let start = positions.get(prefix).copied().unwrap_or(end_of_plain);
+    let end = if suffix > 0 {
+        positions[plain.len() - suffix]
+    } else {
+        end_of_plain
+    }
+    .max(start);
+
+    let mut out = String::with_capacity(line.len() + translated.len());
+    out.push_str(&line[..start]);
+    out.push_str(&translated[prefix..translated.len() - suffix]);
+    for esc in crate::ANSI_RE.find_iter(&line[start..end]) {
+        out.push_str(esc.as_str());
+    }
+    out.push_str(&line[end..]);
+    out
+}
+
+// 短い形式の位置と見出し（"src/main.rs:4:5: error[E0382]: ..." など）
+static LOCATION_RE: once_cell::sync::Lazy<regex::Regex> = once_cell::sync::Lazy::new(|| {
+    regex::Regex::new(r"^(\S.*?:\d+:\d+: (?:error|warning|note|help)(?:\[\w+\])?: )(.+)$").unwrap()
+});
+
+// 短い形式の 1 行分を翻訳する
+fn translate_short_line(line: &str, translate: &dyn Fn(&str) -> String) -> String {
+    for re in [&*LOCATION_RE, &*HEADER_RE] {
+        if let Some(caps) = re.captures(line) {
+            return format!("{}{}", &caps[1], translate(&caps[2]));
+        }
+    }
+    line.to_string()
+}
+
+// 人が読む形式の 1 行分を翻訳する
+fn translate_human_line(line: &str, translate: &dyn Fn(&str) -> String) -> String {
+    // 補足（"  = note: ..." など）
+    static NOTE_RE: once_cell::sync::Lazy<regex::Regex> = once_cell::sync::Lazy::new(|| {
+        regex::Regex::new(r"^(\s*= (?:note|help|warning): )(.+)$").unwrap()
+    });
+    // ラベル（行番号のない "  |" に続く、下線などの後の文字列）
+    static LABEL_RE: once_cell::sync::Lazy<regex::Regex> = once_cell::sync::Lazy::new(|| {
+        regex::Regex::new(r"^(\s*\|[ |^\-_/\\+~]*)([^ |^\-_/\\+~].*)$").unwrap()
+    });
+
+    for re in [&*HEADER_RE, &*NOTE_RE] {
+        if let Some(caps) = re.captures(line) {
+            return format!("{}{}", &caps[1], translate(&caps[2]));
+        }
+    }
+    if let Some(caps) = LABEL_RE.captures(line) {
+        return format!("{}{}", &caps[1], translate_label(&caps[2], translate));
+    }
+    line.to_string()
+}
+
+// ラベルを翻訳する（"help: ..." のような提案は、前置きの後を翻訳する）
+fn translate_label(label: &str, translate: &dyn Fn(&str) -> String) -> String {
+    for prefix in ["help: ", "note: "] {
+        if let Some(rest) = label.strip_prefix(prefix) {
+            return format!("{}{}", prefix, translate(rest));
+        }
+    }
+    translate(label)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_translate_human() {
+        let translations: Vec<TranslateEntry> = serde_json::from_str(
+            r#"[
+                { "en": "move occurs because `{$name}` has type `{$ty}`, which does not implement the `Copy` trait", "ja": "`{$ty}`型の`{$name}`は`Copy`トレイトを実装していないので、移動します" },
+                { "en": "consider cloning the value if the performance cost is acceptable", "ja": "複製コストが許容できるなら、複製することを検討してください" },
+                { "en": "value borrowed here after move", "ja": "移動後の値をここで借用しました" },
+                { "en": "borrow of moved value", "ja": "移動された値の借用しました", "exact": false },
+                { "en": "value moved here", "ja": "ここで値を移動しました" },
+                { "en": "unused variable: `{$name}`", "ja": "変数が使われていません: `{$name}`" },
+                { "en": "if this is intentional, prefix it with an underscore", "ja": "意図的ならアンダースコアを前に付けて下さい", "exact": false },
+                { "en": "`#[{$name}]` on by default", "ja": "`#[{$name}]`はデフォルトで有効です" }
+            ]"#,
+        )
+        .unwrap();
+
+        // rustc が出力したものをそのまま使う
+        let text = r#"warning: unused variable: `b`
+ --> src/main.rs:4:9
+  |
+4 |     let b = a[10];
+  |         ^ help: if this is intentional, prefix it with an underscore: `_b`
+  |
+  = note: `#[warn(unused_variables)]` on by default
+
+error[E0382]: borrow of moved value: `s1`
+ --> src/main.rs:5:28
+  |
+2 |     let s1 = String::from("hello");
+  |         -- move occurs because `s1` has type `String`, which does not implement the `Copy` trait
+3 |     let s2 = s1;
+  |              -- value moved here
+4 |
+5 |     println!("{}, world!", s1);
+  |                            ^^ value borrowed here after move
+  |
+help: consider cloning the value if the performance cost is acceptable
+  |
+3 |     let s2 = s1.clone();
+  |                ++++++++
+
+error: aborting due to 1 previous error; 1 warning emitted
+
+For more information about this error, try `rustc --explain E0382`.
+"#;
+        let expected = r#"warning: 変数が使われていません: `b`
+ --> src/main.rs:4:9
+  |
+4 |     let b = a[10];
+  |         ^ help: 意図的ならアンダースコアを前に付けて下さい: `_b`
+  |
+  = note: `#[warn(unused_variables)]`はデフォルトで有効です
+
+error[E0382]: 移動された値の借用しました: `s1`
+ --> src/main.rs:5:28
+  |
+2 |     let s1 = String::from("hello");
+  |         -- `String`型の`s1`は`Copy`トレイトを実装していないので、移動します
+3 |     let s2 = s1;
+  |              -- ここで値を移動しました
+4 |
+5 |     println!("{}, world!", s1);
+  |                            ^^ 移動後の値をここで借用しました
+  |
+help: 複製コストが許容できるなら、複製することを検討してください
+  |
+3 |     let s2 = s1.clone();
+  |                ++++++++
+
+error: aborting due to 1 previous error; 1 warning emitted
+
+For more information about this error, try `rustc --explain E0382`.
+"#;
+        assert_eq!(translate_human(text, &translations), expected);
+    }
+
+    #[test]
+    fn test_translate_human_stacked_labels() {
+        let translations: Vec<TranslateEntry> = serde_json::from_str(
+            r#"[
+                { "en": "mismatched types", "ja": "型が不一致です" },
+                { "en": "expected due to this", "ja": "これにより期待される" },
+                { "en": "expected `{$ty1}`, found `{$ty2}`", "ja": "`{$ty1}`を期待したが、`{$ty2}`が見つかった" }
+            ]"#,
+        )
+        .unwrap();
+
+        let text = "error[E0308]: mismatched types\r\n --> src/main.rs:2:18\r\n  |\r\n2 |     let x: i32 = \"a\";\r\n  |            ---   ^^^ expected `i32`, found `&str`\r\n  |            |\r\n  |            expected due to this\r\n";
+        let expected = "error[E0308]: 型が不一致です\r\n --> src/main.rs:2:18\r\n  |\r\n2 |     let x: i32 = \"a\";\r\n  |            ---   ^^^ `i32`を期待したが、`&str`が見つかった\r\n  |            |\r\n  |            これにより期待される\r\n";
+        assert_eq!(translate_human(text, &translations), expected);
+    }
+
+    #[test]
+    fn test_translate_human_colored() {
+        let translations: Vec<TranslateEntry> = serde_json::from_str(
+            r#"[
+                { "en": "mismatched types", "ja": "型が不一致です" },
+                { "en": "expected `{$ty1}`, found `{$ty2}`", "ja": "`{$ty1}`を期待したが、`{$ty2}`が見つかった" },
+                { "en": "expected due to this", "ja": "これにより期待される" }
+            ]"#,
+        )
+        .unwrap();
+
+        // "--color=always" で rustc が出力したもの
+        let text = "\x1b[0m\x1b[1m\x1b[38;5;9merror[E0308]\x1b[0m\x1b[0m\x1b[1m: mismatched types\x1b[0m\n\x1b[0m \x1b[0m\x1b[0m\x1b[1m\x1b[38;5;12m--> \x1b[0m\x1b[0msrc/main.rs:2:18\x1b[0m\n\x1b[0m  \x1b[0m\x1b[0m\x1b[1m\x1b[38;5;12m|\x1b[0m\n\x1b[0m\x1b[1m\x1b[38;5;12m2\x1b[0m\x1b[0m \x1b[0m\x1b[0m\x1b[1m\x1b[38;5;12m|\x1b[0m\x1b[0m \x1b[0m\x1b[0m    let x: i32 = \"a\";\x1b[0m\n\x1b[0m  \x1b[0m\x1b[0m\x1b[1m\x1b[38;5;12m|\x1b[0m\x1b[0m            \x1b[0m\x1b[0m\x1b[1m\x1b[38;5;12m---\x1b[0m\x1b[0m   \x1b[0m\x1b[0m\x1b[1m\x1b[38;5;9m^^^\x1b[0m\x1b[0m \x1b[0m\x1b[0m\x1b[1m\x1b[38;5;9mexpected `i32`, found `&str`\x1b[0m\n\x1b[0m  \x1b[0m\x1b[0m\x1b[1m\x1b[38;5;12m|\x1b[0m\x1b[0m            \x1b[0m\x1b[0m\x1b[1m\x1b[38;5;12m|\x1b[0m\n\x1b[0m  \x1b[0m\x1b[0m\x1b[1m\x1b[38;5;12m|\x1b[0m\x1b[0m            \x1b[0m\x1b[0m\x1b[1m\x1b[38;5;12mexpected due to this\x1b[0m\n";
+        let expected = "\x1b[0m\x1b[1m\x1b[38;5;9merror[E0308]\x1b[0m\x1b[0m\x1b[1m: 型が不一致です\x1b[0m\n\x1b[0m \x1b[0m\x1b[0m\x1b[1m\x1b[38;5;12m--> \x1b[0m\x1b[0msrc/main.rs:2:18\x1b[0m\n\x1b[0m  \x1b[0m\x1b[0m\x1b[1m\x1b[38;5;12m|\x1b[0m\n\x1b[0m\x1b[1m\x1b[38;5;12m2\x1b[0m\x1b[0m \x1b[0m\x1b[0m\x1b[1m\x1b[38;5;12m|\x1b[0m\x1b[0m \x1b[0m\x1b[0m    let x: i32 = \"a\";\x1b[0m\n\x1b[0m  \x1b[0m\x1b[0m\x1b[1m\x1b[38;5;12m|\x1b[0m\x1b[0m            \x1b[0m\x1b[0m\x1b[1m\x1b[38;5;12m---\x1b[0m\x1b[0m   \x1b[0m\x1b[0m\x1b[1m\x1b[38;5;9m^^^\x1b[0m\x1b[0m \x1b[0m\x1b[0m\x1b[1m\x1b[38;5;9m`i32`を期待したが、`&str`が見つかった\x1b[0m\n\x1b[0m  \x1b[0m\x1b[0m\x1b[1m\x1b[38;5;12m|\x1b[0m\x1b[0m            \x1b[0m\x1b[0m\x1b[1m\x1b[38;5;12m|\x1b[0m\n\x1b[0m  \x1b[0m\x1b[0m\x1b[1m\x1b[38;5;12m|\x1b[0m\x1b[0m            \x1b[0m\x1b[0m\x1b[1m\x1b[38;5;12mこれにより期待される\x1b[0m\n";
+        assert_eq!(translate_human(text, &translations), expected);
+
+        // 翻訳する部分の先頭のエスケープシーケンスは前に、途中のものは翻訳後の文字列の後ろに残す
+        let text = "\x1b[1merror\x1b[0m: \x1b[4mmismatched\x1b[24m types\x1b[0m\n";
+        let expected = "\x1b[1merror\x1b[0m: \x1b[4m型が不一致です\x1b[24m\x1b[0m\n";
+        assert_eq!(translate_human(text, &translations), expected);
+    }
+
+    #[test]
+    fn test_translate_short() {
+        let translations: Vec<TranslateEntry> = serde_json::from_str(
+            r#"[
+                { "en": "borrow of moved value", "ja": "移動された値の借用しました", "exact": false },
+                { "en": "unused variable: `{$name}`", "ja": "変数が使われていません: `{$name}`" }
+            ]"#,
+        )
+        .unwrap();
+
+        let text = "src/main.rs:4:9: warning: unused variable: `b`\nsrc/main.rs:5:28: error[E0382]: borrow of moved value: `s1`\nerror: aborting due to 1 previous error; 1 warning emitted\n";
+        let expected = "src/main.rs:4:9: warning: 変数が使われていません: `b`\nsrc/main.rs:5:28: error[E0382]: 移動された値の借用しました: `s1`\nerror: aborting due to 1 previous error; 1 warning emitted\n";
+        assert_eq!(translate_short(text, &translations), expected);
+    }
+
+    #[test]
+    fn test_translate_lines() {
+        let table = TranslationTable::new(
+            serde_json::from_str(
+                r#"[
+                    { "en": "borrow of moved value", "ja": "移動された値の借用しました", "exact": false },
+                    { "en": "unused variable: `{$name}`", "ja": "変数が使われていません: `{$name}`" },
+                    { "en": "value moved here", "ja": "ここで値を移動しました" },
+                    { "en": "consider cloning the value", "ja": "値の複製を検討してください" }
+                ]"#,
+            )
+            .unwrap(),
+        );
+
+        // 人が読む形式と短い形式の混ざったログ（空の行、字下げ、"\r\n" の行を含む）
+        let input = concat!(
+            "error[E0382]: borrow of moved value: `s1`\n",
+            "  --> src/main.rs:5:28\n",
+            "   |\n",
+            "4  |     let s2 = s1;\n",
+            "   |              -- value moved here\n",
+            "   = help: consider cloning the value\r\n",
+            "\n",
+            "src/main.rs:4:9: warning: unused variable: `b`\n",
+            "    indented text is not a message\n",
+            "\n",
+            "borrow of moved value",
+        );
+        let expected = concat!(
+            "error[E0382]: 移動された値の借用しました: `s1`\n",
+            "  --> src/main.rs:5:28\n",
+            "   |\n",
+            "4  |     let s2 = s1;\n",
+            "   |              -- ここで値を移動しました\n",
+            "   = help: 値の複製を検討してください\r\n",
+            "\n",
+            "src/main.rs:4:9: warning: 変数が使われていません: `b`\n",
+            "    indented text is not a message\n",
+            "\n",
+            "borrow of moved value",
+        );
+        assert_eq!(translate_lines(input, &table), expected);
+        assert_eq!(translate_lines("", &table), "");
+    }
+}
@@ -0,0 +1,2988 @@
+//! RUST のコンパイルエラーメッセージを日本語に翻訳するライブラリ
+
+#[doc(hidden)]
+pub mod bench;
+pub mod config;
+pub mod coverage;
+pub mod csv;
+pub mod file;
+mod human;
+pub mod po;
+
+pub use human::{translate_human, translate_lines, translate_short};
+
+/// 既定の翻訳先の言語
+pub const DEFAULT_LANG: &str = "ja";
+
+/// 翻訳データの型定義
+#[derive(Debug, Clone, Default, serde::Deserialize, serde::Serialize)]
+pub struct TranslateEntry {
+    pub en: String,
+    /// 日本語の翻訳（日本語以外の翻訳ファイルでは省略できる）
+    #[serde(default)]
+    pub ja: String,
+    /// 大文字と小文字を区別せずに照合する
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub ignore_case: bool,
+    /// メッセージ全体にこの文字列が含まれる場合のみ使う（同じ英語文字列を文脈で訳し分ける）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context: Option<String>,
+    /// en を正規表現として扱う（ja では `{$1}` などでグループを参照する）
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub regex: bool,
+    /// メッセージ全体が en に一致する場合のみ使う（false なら、一致しなかった残りの文字列を翻訳の後に付ける）
+    /// 省略時は、プレースホルダを含まないエントリ（正規表現のエントリは除く）のみ true とする
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exact: Option<bool>,
+    /// `{$count}` の値が 1 以外の場合の英語と翻訳（"errors" のような複数形を同じエントリで扱う）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub plural: Option<PluralForm>,
+    /// バッククォートで囲まれた部分（識別子や型など）の一部にも、プレースホルダ以外の文字列を一致させる
+    /// false なら、囲まれた部分の途中で始まる、または終わるような一致は使わない
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub match_in_backticks: bool,
+    /// 英語文字列が同じ長さのエントリの間で、大きいものを先に試す
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub priority: i32,
+    /// エントリのカテゴリ（"borrow" など、カテゴリ分けした翻訳ファイルでは省略するとそのカテゴリ）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    /// このバージョン以降の rustc でのみ使う（"1.80" や "1.80.0" の形式、その版を含む）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_version: Option<String>,
+    /// このバージョンまでの rustc でのみ使う（その版を含む）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_version: Option<String>,
+    /// 日本語以外の翻訳（キーは "ko" などの言語コード）
+    #[serde(flatten)]
+    pub langs: std::collections::BTreeMap<String, String>,
+}
+
+/// 数によって変わる形（`{$count}` の値が 1 以外の場合に使う）
+#[derive(Debug, Clone, Default, serde::Deserialize, serde::Serialize)]
+pub struct PluralForm {
+    /// 複数形の英語（省略時は、単数形の英語にのみ一致する）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub en: Option<String>,
+    /// 言語ごとの複数形の翻訳（キーは "ja" などの言語コード、省略した言語は単数形の翻訳を使う）
+    #[serde(flatten)]
+    pub texts: std::collections::BTreeMap<String, String>,
+}
+
+/// 数を表すプレースホルダの名前
+const COUNT_PLACEHOLDER: &str = "count";
+
+// 値が 0 か（既定値のフィールドを書き出さないため）
+fn is_zero(n: &i32) -> bool {
+    *n == 0
+}
+
+impl TranslateEntry {
+    /// 指定した言語の翻訳を返す
+    pub fn text_for(&self, lang: &str) -> Option<&str> {
+        if lang == DEFAULT_LANG {
+            Some(&self.ja)
+        } else {
+            self.langs.get(lang).map(String::as_str)
+        }
+    }
+
+    /// 数（`{$count}` の値）に合わせて、指定した言語の翻訳を返す
+    /// 数が 1 以外で、その言語の複数形があればそれを使う
+    pub fn text_for_count(&self, lang: &str, count: Option<&str>) -> Option<&str> {
+        if let Some(plural) = &self.plural
+            && count.is_some_and(|c| c != "1")
+            && let Some(text) = plural.texts.get(lang)
+        {
+            return Some(text);
+        }
+        self.text_for(lang)
+    }
+
+    /// メッセージ全体が一致する必要があるか（exact の指定、省略時はプレースホルダの有無で決める）
+    pub fn is_exact(&self) -> bool {
+        self.exact
+            .unwrap_or(!self.regex && placeholder_names(&self.en).is_empty())
+    }
+
+    /// このエントリをメッセージに使えるか（context があれば、メッセージに含まれるか）
+    pub fn applies_to(&self, message: &str) -> bool {
+        self.context
+            .as_ref()
+            .is_none_or(|c| message.contains(c.as_str()))
+    }
+}
+
+// JSON内のメッセージを日本語に翻訳する
+// 翻訳対象は以下のフィールド（値が null の場合には何もしない）
+// - "message"
+// - "spans[].label"
+// - "children[].message"
+// - "children[].spans[].label"
+// また "rendered" フィールドの中身について、各メッセージの翻訳前と同じ文字列が含まれている場合には、翻訳後文字列に置き換える
+// （"rendered" が null の場合や存在しない場合は、そのままにする）
+// JSONフォーマットの形式は以下を参照
+// - <https://doc.rust-lang.org/rustc/json.html>
+// 同じ翻訳データで何度も翻訳する場合は、`TranslationTable::translate_json` の方が速い
+pub fn translate_json_message(
+    json: &serde_json::Value,
+    translations: &[TranslateEntry],
+) -> serde_json::Value {
+    TranslationTable::new(translations.to_vec()).translate_json(json)
+}
+
+/// JSON内の翻訳対象のメッセージを、出現順に返す（対象は `translate_json_message` と同じ）
+pub fn json_messages(json: &serde_json::Value) -> Vec<String> {
+    let messages = std::cell::RefCell::new(Vec::new());
+    translate_json_message_with(
+        json,
+        &|m| {
+            messages.borrow_mut().push(m.to_string());
+            m.to_string()
+        },
+        Some(0),
+        None,
+        false,
+        ReplaceStrategy::Linear,
+    );
+    messages.into_inner()
+}
+
+/// 翻訳の件数
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TranslateStats {
+    /// 翻訳できたメッセージ
+    pub translated: usize,
+    /// 翻訳できなかった（空でない）メッセージ
+    pub untranslated: usize,
+    /// 診断の行
+    pub lines: usize,
+}
+
+impl TranslateStats {
+    // 件数を足し合わせる
+    fn add(&mut self, other: TranslateStats) {
+        self.translated += other.translated;
+        self.untranslated += other.untranslated;
+        self.lines += other.lines;
+    }
+}
+
+impl std::fmt::Display for TranslateStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "translated={} untranslated={} lines={}",
+            self.translated, self.untranslated, self.lines
+        )
+    }
+}
+
+// JSON内のメッセージを、指定した関数で翻訳する（翻訳できたメッセージとできなかったメッセージの件数も返す）
+// 元の JSON は変えずに、複製を翻訳して返す
+fn translate_json_message_with(
+    json: &serde_json::Value,
+    translate: &dyn Fn(&str) -> String,
+    max_rendered: Option<usize>,
+    bilingual: Option<Bilingual>,
+    primary_spans_only: bool,
+    strategy: ReplaceStrategy,
+) -> (serde_json::Value, TranslateStats) {
+    let mut new_json = json.clone();
+    let stats = translate_json_message_in_place(
+        &mut new_json,
+        translate,
+        max_rendered,
+        bilingual,
+        primary_spans_only,
+        strategy,
+    );
+    (new_json, stats)
+}
+
+// JSON内のメッセージを、指定した関数でその場で翻訳する（翻訳できたメッセージとできなかったメッセージの件数を返す）
+// 翻訳できたフィールドと rendered だけを書き換える（翻訳できたものがなければ何も変えない）
+// rendered が max_rendered バイトを超える場合、rendered は翻訳しない
+// bilingual を指定した場合は、翻訳前の英語と翻訳後の文字列を併記する
+// primary_spans_only なら、spans[].label は `is_primary` が true のもののみ翻訳する
+// rendered は翻訳データに合わせた strategy で置換する
+fn translate_json_message_in_place(
+    json: &mut serde_json::Value,
+    translate: &dyn Fn(&str) -> String,
+    max_rendered: Option<usize>,
+    bilingual: Option<Bilingual>,
+    primary_spans_only: bool,
+    strategy: ReplaceStrategy,
+) -> TranslateStats {
+    let mut stats = TranslateStats::default();
+    let mut replaced = Vec::new();
+
+    // 文字列のフィールドを翻訳する（値が null の場合などは何もしない）
+    let mut translate_field = |value: &mut serde_json::Value| {
+        let Some(orig) = value.as_str() else {
+            return;
+        };
+        let translated = translate(orig);
+        if translated == orig {
+            if !orig.is_empty() {
+                stats.untranslated += 1;
+            }
+            return;
+        }
+        stats.translated += 1;
+        let orig = orig.to_string();
+        *value = serde_json::Value::String(match bilingual {
+            Some(bilingual) => bilingual.join(&orig, &translated),
+            None => translated.clone(),
+        });
+        replaced.push((orig, translated));
+    };
+    // spans[].label を翻訳する
+    fn translate_labels(
+        spans: Option<&mut serde_json::Value>,
+        primary_spans_only: bool,
+        translate_field: &mut dyn FnMut(&mut serde_json::Value),
+    ) {
+        if let Some(serde_json::Value::Array(spans)) = spans {
+            for span in spans {
+                if primary_spans_only
+                    && span.get("is_primary") != Some(&serde_json::Value::Bool(true))
+                {
+                    continue;
+                }
+                if let Some(label) = span.get_mut("label") {
+                    translate_field(label);
+                }
+            }
+        }
+    }
+
+    // message
+    if let Some(message) = json.get_mut("message") {
+        translate_field(message);
+    }
+
+    // spans[].label
+    translate_labels(
+        json.get_mut("spans"),
+        primary_spans_only,
+        &mut translate_field,
+    );
+
+    // children[].message, children[].spans[].label
+    if let Some(serde_json::Value::Array(children)) = json.get_mut("children") {
+        for child in children {
+            if let Some(child_msg) = child.get_mut("message") {
+                translate_field(child_msg);
+            }
+            translate_labels(
+                child.get_mut("spans"),
+                primary_spans_only,
+                &mut translate_field,
+            );
+        }
+    }
+
+    // rendered の置換（null の場合や存在しない場合は、文字列を作らずにそのままにする）
+    if !replaced.is_empty()
+        && let Some(serde_json::Value::String(rendered)) = json.get_mut("rendered")
+        && max_rendered.is_none_or(|max| rendered.len() <= max)
+    {
+        *rendered = match bilingual {
+            None => replace_rendered(rendered, &replaced, strategy),
+            Some(Bilingual::Inline) => {
+                let replaced: Vec<(String, String)> = replaced
+                    .iter()
+                    .map(|(orig, translated)| {
+                        (orig.clone(), Bilingual::Inline.join(orig, translated))
+                    })
+                    .collect();
+                replace_rendered(rendered, &replaced, strategy)
+            }
+            Some(Bilingual::Line) => {
+                let replacer = RenderedReplacer::new(&replaced, strategy);
+                append_translated_lines(rendered, &|line| replacer.replace_rendered(line))
+            }
+        };
+    }
+    stats
+}
+
+/// コンパイルエラーのJSONであれば、各種フィールドを翻訳する（診断でなければそのまま返す）
+/// cargo の `--message-format=json` の `compiler-message` であれば、その中の診断を翻訳する
+/// `future_incompat` であれば、`future_incompat_report[].diagnostic` の診断を翻訳する（他の部分はそのまま残す）
+/// 古い rustc のように `$message_type` がなくても、診断の形であれば翻訳する（[`is_diagnostic`] を参照）
+pub fn convert_json_error_line(
+    mut json: serde_json::Value,
+    translator: &Translator,
+) -> serde_json::Value {
+    convert_json_value(&mut json, translator);
+    json
+}
+
+/// JSON が診断か（`$message_type` が `"diagnostic"` であるもの）
+/// `$message_type` がない場合は、古い rustc などの出力とみなし、
+/// 文字列の `message` と `level`、配列の `spans` がそろっている場合のみ診断とする
+pub fn is_diagnostic(json: &serde_json::Value) -> bool {
+    match json.get("$message_type") {
+        Some(message_type) => message_type == "diagnostic",
+        None => {
+            json.get("message").is_some_and(|m| m.is_string())
+                && json.get("level").is_some_and(|l| l.is_string())
+                && json.get("spans").is_some_and(|s| s.is_array())
+        }
+    }
+}
+
+// convert_json_error_line と同じ変換をその場でする（変わったかを返す）
+fn convert_json_value(json: &mut serde_json::Value, translator: &Translator) -> bool {
+    if json.get("reason").is_some_and(|r| r == "compiler-message")
+        && let Some(message) = json.get_mut("message")
+    {
+        return convert_json_value(message, translator);
+    }
+    if is_diagnostic(json) {
+        return convert_diagnostic(json, translator);
+    }
+    let message_type = json.get("$message_type").and_then(|m| m.as_str());
+    if message_type == Some("future_incompat")
+        && let Some(serde_json::Value::Array(report)) = json.get_mut("future_incompat_report")
+    {
+        let mut changed = false;
+        for item in report {
+            if let Some(diagnostic) = item.get_mut("diagnostic") {
+                changed |= convert_diagnostic(diagnostic, translator);
+            }
+        }
+        return changed;
+    }
+    false
+}
+
+// 診断の各種フィールドをその場で翻訳する（翻訳しない level の診断はそのまま残す）
+// 翻訳できたフィールドがあるか、翻訳前の診断を埋め込んだ場合は true を返す
+fn convert_diagnostic(json: &mut serde_json::Value, translator: &Translator) -> bool {
+    let level = json.get("level").and_then(|l| l.as_str());
+    if !json.is_object() || !translator.translates_level(level) {
+        return false;
+    }
+    // 翻訳前の診断を埋め込む場合のみ複製する
+    let original = translator.embed_original.then(|| json.clone());
+    let mut stats = translate_json_message_in_place(
+        json,
+        &|m| translator.translate(m),
+        translator.max_rendered,
+        translator.bilingual,
+        translator.primary_spans_only,
+        translator.table.replace_strategy(),
+    );
+    if let Some(total) = &translator.stats {
+        stats.lines = 1;
+        total.lock().unwrap().add(stats);
+    }
+    if let Some(original) = original {
+        embed_original(json, original);
+        return true;
+    }
+    stats.translated > 0
+}
+
+/// 翻訳前の英語と翻訳後の文字列を併記する形式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bilingual {
+    /// 翻訳後の文字列の後に、括弧で英語を付ける（`型が不一致です (mismatched types)`）
+    Inline,
+    /// 英語の行の後に、翻訳後の行を追加する
+    Line,
+}
+
+impl Bilingual {
+    /// 翻訳前の英語と翻訳後の文字列を併記する
+    pub fn join(self, en: &str, translated: &str) -> String {
+        match self {
+            Bilingual::Inline => format!("{} ({})", translated, en),
+            Bilingual::Line => format!("{}\n{}", en, translated),
+        }
+    }
+}
+
+// 各行を改行コードを除いて変換し、変わった行は元の行の後に変換後の行を追加する
+fn append_translated_lines(text: &str, translate_line: &dyn Fn(&str) -> String) -> String {
+    let mut out = String::with_capacity(text.len() * 2);
+    for line in text.split_inclusive('\n') {
+        let body = line.trim_end_matches(['\n', '\r']);
+        let ending = &line[body.len()..];
+        out.push_str(line);
+        let translated = translate_line(body);
+        if translated != body {
+            if ending.is_empty() {
+                out.push('\n');
+            }
+            out.push_str(&translated);
+            out.push_str(ending);
+        }
+    }
+    out
+}
+
+// 翻訳後の JSON に、翻訳前の診断を "original" キーとして埋め込む
+fn embed_original(translated: &mut serde_json::Value, original: serde_json::Value) {
+    if let serde_json::Value::Object(obj) = translated {
+        obj.insert("original".to_string(), original);
+    }
+}
+
+/// コンパイラの JSONL の出力を翻訳する
+/// 変わらなかった行や空の行は元の行をそのまま使い、JSON として読み込めない場合は全体をそのまま返す
+/// UTF-8 として読み込めない行は、その行だけをそのまま使う
+/// 各行の改行コード（`\n` か `\r\n`）と、末尾の改行の有無も入力に合わせる
+/// 行が多い場合は、複数のスレッドで並行して翻訳する（出力の順は入力と同じ）
+pub fn convert_json_error_format(data: Vec<u8>, translator: &Translator) -> Vec<u8> {
+    let parallel = rayon::current_num_threads() > 1
+        && data.iter().filter(|&&b| b == b'\n').count() >= PARALLEL_LINES_THRESHOLD;
+    convert_json_lines(data, translator, parallel)
+}
+
+/// これ以上の行の JSONL は、複数のスレッドで並行して翻訳する
+/// 1 行の翻訳は短いので、行が少ないとスレッドに分ける手間の方が大きい
+/// （`cargo bench --bench translate -- convert_json_parallel` で比べられる）
+const PARALLEL_LINES_THRESHOLD: usize = 1024;
+
+// JSONL の各行を翻訳して、元の順につなげる（parallel なら rayon のスレッドプールで翻訳する）
+fn convert_json_lines(data: Vec<u8>, translator: &Translator, parallel: bool) -> Vec<u8> {
+    use rayon::prelude::*;
+    let lines: Vec<&[u8]> = data.split_inclusive(|&b| b == b'\n').collect();
+    let converted: Option<Vec<std::borrow::Cow<[u8]>>> = if parallel {
+        lines
+            .par_iter()
+            .map(|line| convert_json_raw_line(line, translator))
+            .collect()
+    } else {
+        lines
+            .iter()
+            .map(|line| convert_json_raw_line(line, translator))
+            .collect()
+    };
+    // JSON として読み込めない行があれば、何もしない
+    let Some(converted) = converted else {
+        return data;
+    };
+    let mut out = Vec::with_capacity(data.len());
+    for line in converted {
+        out.extend_from_slice(&line);
+    }
+    out
+}
+
+// JSONL の 1 行（改行コードを含む）を翻訳する（JSON として読み込めなければ None）
+// 変わらなかった行や空の行、UTF-8 として読み込めない行は、元の行をそのまま使う
+fn convert_json_raw_line<'a>(
+    raw_line: &'a [u8],
+    translator: &Translator,
+) -> Option<std::borrow::Cow<'a, [u8]>> {
+    use std::borrow::Cow;
+    // UTF-8として解釈できない行はそのまま使う
+    let Ok(line) = std::str::from_utf8(raw_line) else {
+        return Some(Cow::Borrowed(raw_line));
+    };
+    // 各行の改行コード（"\n" か "\r\n"）はそのまま残す
+    let body = line
+        .strip_suffix('\n')
+        .map_or(line, |l| l.strip_suffix('\r').unwrap_or(l));
+    let ending = &line[body.len()..];
+    if body.is_empty() {
+        return Some(Cow::Borrowed(raw_line));
+    }
+    // 各行をJSONとしてパース
+    let mut converted = serde_json::from_str::<serde_json::Value>(body).ok()?;
+    // 変わらなかった行は、元の行をそのまま使う
+    if !convert_json_value(&mut converted, translator) {
+        return Some(Cow::Borrowed(raw_line));
+    }
+    // 変換後をJSON文字列化
+    let mut out = serde_json::to_string(&converted).ok()?.into_bytes();
+    out.extend_from_slice(ending.as_bytes());
+    Some(Cow::Owned(out))
+}
+
+/// メッセージを変換するフック
+/// `Some` を返した場合はその結果を使い、`None` の場合は翻訳データで翻訳する
+pub type TransformFn = Box<dyn Fn(&str) -> Option<String> + Send + Sync>;
+
+/// 翻訳できなかったメッセージを受け取る関数
+pub type MissingFn = Box<dyn Fn(&str) + Send + Sync>;
+
+// 人が読む形式か短い形式の出力を、メッセージを翻訳する関数で翻訳する関数
+type TextTranslateFn = fn(&str, &dyn Fn(&str) -> String) -> String;
+
+/// 翻訳データと変換フックを持つ翻訳器
+pub struct Translator {
+    table: TranslationTable,
+    transform: Option<TransformFn>,
+    missing: Option<MissingFn>,
+    embed_original: bool,
+    lang: String,
+    max_rendered: Option<usize>,
+    bilingual: Option<Bilingual>,
+    levels: Option<Vec<String>>,
+    primary_spans_only: bool,
+    stats: Option<std::sync::Mutex<TranslateStats>>,
+}
+
+impl Translator {
+    /// 翻訳データから翻訳器を作る（エントリは指定された順に試す）
+    pub fn new(entries: Vec<TranslateEntry>) -> Self {
+        Translator {
+            table: TranslationTable::new(entries),
+            transform: None,
+            missing: None,
+            embed_original: false,
+            lang: DEFAULT_LANG.to_string(),
+            max_rendered: None,
+            bilingual: None,
+            levels: None,
+            primary_spans_only: false,
+            stats: None,
+        }
+    }
+
+    /// rendered がこのバイト数を超える場合は、rendered を翻訳しない（他のフィールドは翻訳する）
+    pub fn with_max_rendered(mut self, max_rendered: usize) -> Self {
+        self.max_rendered = Some(max_rendered);
+        self
+    }
+
+    /// `translate` で使う翻訳先の言語を設定する
+    pub fn with_lang(mut self, lang: &str) -> Self {
+        self.lang = lang.to_string();
+        self
+    }
+
+    /// 翻訳データより先に試す変換フックを設定する
+    pub fn with_transform(mut self, transform: TransformFn) -> Self {
+        self.transform = Some(transform);
+        self
+    }
+
+    /// `convert_json_error_line` で、`level` がこのいずれか（"error" や "warning" など）の診断のみ翻訳する
+    pub fn with_levels(mut self, levels: Vec<String>) -> Self {
+        self.levels = Some(levels);
+        self
+    }
+
+    // この level の診断を翻訳するか（with_levels を指定しなければすべて翻訳する）
+    fn translates_level(&self, level: Option<&str>) -> bool {
+        self.levels
+            .as_ref()
+            .is_none_or(|levels| level.is_some_and(|l| levels.iter().any(|v| v == l)))
+    }
+
+    /// JSON の spans[].label は、`is_primary` が true のもののみ翻訳する（他のラベルは rendered でも英語のまま）
+    pub fn with_primary_spans_only(mut self) -> Self {
+        self.primary_spans_only = true;
+        self
+    }
+
+    /// 翻訳前の英語と翻訳後の文字列を併記する
+    pub fn with_bilingual(mut self, bilingual: Bilingual) -> Self {
+        self.bilingual = Some(bilingual);
+        self
+    }
+
+    /// `convert_json_error_line` で、翻訳前の診断を "original" キーとして埋め込む
+    pub fn with_embed_original(mut self) -> Self {
+        self.embed_original = true;
+        self
+    }
+
+    /// `convert_json_error_line` で翻訳した診断とメッセージの件数を数える（`stats` で取り出す）
+    pub fn with_stats(mut self) -> Self {
+        self.stats = Some(Default::default());
+        self
+    }
+
+    /// これまでに数えた件数（`with_stats` を指定しなければ None）
+    pub fn stats(&self) -> Option<TranslateStats> {
+        self.stats.as_ref().map(|s| *s.lock().unwrap())
+    }
+
+    /// 翻訳できなかった（空でない）メッセージを渡す関数を設定する
+    pub fn with_missing(mut self, missing: MissingFn) -> Self {
+        self.missing = Some(missing);
+        self
+    }
+
+    /// メッセージを翻訳する
+    pub fn translate(&self, message: &str) -> String {
+        self.translate_for(&self.lang, message)
+    }
+
+    /// メッセージを指定した言語に翻訳する
+    pub fn translate_for(&self, lang: &str, message: &str) -> String {
+        if let Some(transform) = &self.transform
+            && let Some(transformed) = transform(message)
+        {
+            return transformed;
+        }
+        let translated = self.table.translate_for(lang, message);
+        if let Some(missing) = &self.missing
+            && !message.is_empty()
+            && translated == message
+        {
+            missing(message);
+        }
+        translated
+    }
+
+    /// 人が読む形式の出力を翻訳する（対象は `translate_human` と同じ）
+    pub fn translate_human(&self, text: &str) -> String {
+        self.translate_text_with(text, human::translate_human_with)
+    }
+
+    /// 短い形式の出力を翻訳する（対象は `translate_short` と同じ）
+    pub fn translate_short(&self, text: &str) -> String {
+        self.translate_text_with(text, human::translate_short_with)
+    }
+
+    // 人が読む形式か短い形式の出力を、指定した関数で翻訳する（併記する場合はその形式にする）
+    fn translate_text_with(&self, text: &str, translate_text: TextTranslateFn) -> String {
+        match self.bilingual {
+            None => translate_text(text, &|m| self.translate(m)),
+            Some(Bilingual::Inline) => translate_text(text, &|m| {
+                let translated = self.translate(m);
+                if translated == m {
+                    translated
+                } else {
+                    Bilingual::Inline.join(m, &translated)
+                }
+            }),
+            Some(Bilingual::Line) => {
+                append_translated_lines(text, &|line| translate_text(line, &|m| self.translate(m)))
+            }
+        }
+    }
+
+    /// JSON内のメッセージを翻訳する（対象は `translate_json_message` と同じ）
+    pub fn translate_json(&self, json: &serde_json::Value) -> serde_json::Value {
+        translate_json_message_with(
+            json,
+            &|m| self.translate(m),
+            self.max_rendered,
+            self.bilingual,
+            self.primary_spans_only,
+            self.table.replace_strategy(),
+        )
+        .0
+    }
+}
+
+/// rendered の置換方法（翻訳データを作るときに、固定の英語文字列のエントリの件数から選ぶ）
+#[derive(Debug, Clone, Copy)]
+enum ReplaceStrategy<'a> {
+    /// 置換対象ごとに文字列を探し、最も前で見つかったものを置換する
+    Linear,
+    /// 固定の英語文字列の索引（Aho-Corasick 法）で rendered を 1 回走査してそれらを同時に探し、
+    /// 索引にない置換対象（プレースホルダを含むエントリで翻訳したものなど）だけ 1 件ずつ探す
+    AhoCorasick(&'a LiteralIndex),
+}
+
+impl ReplaceStrategy<'_> {
+    /// 固定の英語文字列のエントリがこれ以上あれば Aho-Corasick 法を使う
+    /// `cargo bench --bench translate -- replace_rendered` では、エントリの件数による差はほとんどなく、
+    /// 索引で探す置換対象が 1 件なら 1 件ずつ探す方が少し速く、4 件で同じ、16 件で索引が約 2 倍速い
+    /// 索引で探す置換対象はエントリの件数を超えないので、索引が速くなりうる件数から使う
+    const AHO_CORASICK_THRESHOLD: usize = 16;
+}
+
+// rendered に含まれる翻訳前の文字列を、翻訳後の文字列に置き換える
+// ソースコードの行と位置の行は、利用者のコードやパスが偶然一致しても壊さないよう置き換えない
+// それ以外の行（見出し、補足、下線に続くラベルなど）は、続いた行ごとにまとめて置き換える
+fn replace_rendered(
+    rendered: &str,
+    replaced: &[(String, String)],
+    strategy: ReplaceStrategy,
+) -> String {
+    RenderedReplacer::new(replaced, strategy).replace_rendered(rendered)
+}
+
+// rendered のソースコードの行（"2 |     let x = 1;"、提案の "2 +     let x = 1;" など）か、
+// 位置の行（" --> src/main.rs:2:18"、"  ::: src/lib.rs:1:1"）か
+fn is_source_line(line: &str) -> bool {
+    static SOURCE_LINE_RE: once_cell::sync::Lazy<regex::Regex> = once_cell::sync::Lazy::new(|| {
+        regex::Regex::new(r"^\s*(?:\d+\s*[|+~-](?:\s|$)|(?:-->|:::)\s)").unwrap()
+    });
+    if line.contains('\x1b') {
+        return SOURCE_LINE_RE.is_match(&strip_ansi(line).0);
+    }
+    SOURCE_LINE_RE.is_match(line)
+}
+
+// rendered の置換対象（1 つの診断で翻訳した文字列の組）
+// 置換方法に合わせて、どの置換対象を索引で探すかは rendered ごとではなく 1 回だけ調べておく
+struct RenderedReplacer<'a> {
+    /// 置換対象（短い文字列が長い文字列の一部を先に置き換えないように、翻訳前の文字列の長い順）
+    pairs: Vec<&'a (String, String)>,
+    /// pairs の位置ごとに、1 件ずつ探すか（索引にないか）
+    linear: Vec<bool>,
+    /// 索引（Aho-Corasick 法の場合のみ）と、索引で探す翻訳前の文字列の pairs の位置
+    /// 同じ翻訳前の文字列が複数あれば、1 件ずつ探す場合と同じく最初のものを使う
+    indexed: Option<(&'a LiteralIndex, std::collections::HashMap<&'a str, usize>)>,
+}
+
+impl<'a> RenderedReplacer<'a> {
+    fn new(replaced: &'a [(String, String)], strategy: ReplaceStrategy<'a>) -> Self {
+        let mut pairs: Vec<&(String, String)> = replaced
+            .iter()
+            .filter(|(orig, trans)| !orig.is_empty() && orig != trans)
+            .collect();
+        pairs.sort_by_key(|(orig, _)| std::cmp::Reverse(orig.len()));
+        let mut linear = vec![true; pairs.len()];
+        let mut indexed = None;
+        if let ReplaceStrategy::AhoCorasick(index) = strategy {
+            let mut origs = std::collections::HashMap::new();
+            for (i, (orig, _)) in pairs.iter().enumerate() {
+                if index.contains(orig) {
+                    origs.entry(orig.as_str()).or_insert(i);
+                    linear[i] = false;
+                }
+            }
+            if !origs.is_empty() {
+                indexed = Some((index, origs));
+            }
+        }
+        RenderedReplacer {
+            pairs,
+            linear,
+            indexed,
+        }
+    }
+
+    // rendered を置換する（ソースコードの行と位置の行は置き換えない）
+    fn replace_rendered(&self, rendered: &str) -> String {
+        if self.pairs.is_empty() {
+            return rendered.to_string();
+        }
+        let mut new_rendered = String::with_capacity(rendered.len());
+        let mut start = 0;
+        let mut pos = 0;
+        for line in rendered.split_inclusive('\n') {
+            if is_source_line(line) {
+                self.replace_text(&rendered[start..pos], &mut new_rendered);
+                new_rendered.push_str(line);
+                start = pos + line.len();
+            }
+            pos += line.len();
+        }
+        self.replace_text(&rendered[start..], &mut new_rendered);
+        new_rendered
+    }
+
+    // 文字列に含まれる翻訳前の文字列を、翻訳後の文字列に置き換えて out に追加する
+    // "use" のような英数字だけの翻訳前の文字列は、"used" のような識別子の一部は置き換えない
+    // ANSI のエスケープシーケンスを含む場合は、それを除いた文字列で一致する位置を探して元の文字列の位置に戻し、
+    // 置換範囲の途中にあったエスケープシーケンスは翻訳後の文字列の後ろに残す
+    fn replace_text(&self, text: &str, out: &mut String) {
+        if !text.contains('\x1b') {
+            let mut last = 0;
+            for (start, end, i) in self.find(text) {
+                out.push_str(&text[last..start]);
+                out.push_str(&self.pairs[i].1);
+                last = end;
+            }
+            out.push_str(&text[last..]);
+            return;
+        }
+        let (plain, positions) = strip_ansi(text);
+        let mut last = 0;
+        for (start, end, i) in self.find(&plain) {
+            let start = positions[start];
+            let end = positions[end - 1] + 1;
+            out.push_str(slice_at_char_boundaries(text, last, start));
+            out.push_str(&self.pairs[i].1);
+            let inner = slice_at_char_boundaries(text, start, end);
+            for esc in ANSI_RE.find_iter(inner) {
+                out.push_str(esc.as_str());
+            }
+            last = end;
+        }
+        out.push_str(slice_at_char_boundaries(text, last, text.len()));
+    }
+
+    // 置き換える範囲（開始、終了、pairs の位置）を、左から順に重ならないように返す
+    // 左から 1 回だけ走査し、置換後の文字列は再び置換しない（同じ位置では長いものを優先する）
+    // 各置換対象の次に見つかる位置を覚えておき、置換範囲と重なったものだけ探し直す
+    // 索引にある置換対象は 1 件ずつではなく索引でまとめて探す（どちらの置換方法でも結果は同じ）
+    fn find(&self, text: &str) -> Vec<(usize, usize, usize)> {
+        let pairs = &self.pairs;
+        let find_from = |orig: &str, from: usize| whole_word_matches(text, orig, from).next();
+        let find_indexed = |from: usize| {
+            self.indexed
+                .as_ref()
+                .and_then(|(index, origs)| index.next_replacement(text, origs, from))
+        };
+        let mut next: Vec<Option<usize>> = pairs
+            .iter()
+            .zip(&self.linear)
+            .map(|((orig, _), &linear)| if linear { find_from(orig, 0) } else { None })
+            .collect();
+        let mut next_indexed = find_indexed(0);
+        let mut replacements = Vec::new();
+        let mut last = 0;
+        loop {
+            for (pos, (orig, _)) in next.iter_mut().zip(pairs) {
+                if pos.is_some_and(|p| p < last) {
+                    *pos = find_from(orig, last);
+                }
+            }
+            if next_indexed.is_some_and(|(start, _, _)| start < last) {
+                next_indexed = find_indexed(last);
+            }
+            // 同じ位置では長いもの（pairs は長い順なので、同じ長さなら最初のもの）を選ぶ
+            let Some((start, std::cmp::Reverse(end), i)) = next
+                .iter()
+                .enumerate()
+                .filter_map(|(i, pos)| pos.map(|p| (p, std::cmp::Reverse(p + pairs[i].0.len()), i)))
+                .chain(next_indexed.map(|(start, end, i)| (start, std::cmp::Reverse(end), i)))
+                .min()
+            else {
+                break;
+            };
+            replacements.push((start, end, i));
+            last = end;
+        }
+        replacements
+    }
+}
+
+// text の from 以降で orig が一致する位置のうち、単語の一部でないものを、重なるものも含めて前から順に返す
+fn whole_word_matches<'a>(
+    text: &'a str,
+    orig: &'a str,
+    mut from: usize,
+) -> impl Iterator<Item = usize> + 'a {
+    std::iter::from_fn(move || {
+        loop {
+            let start = from + text.get(from..)?.find(orig)?;
+            from = start + text[start..].chars().next().map_or(1, char::len_utf8);
+            if is_whole_word(text, start, start + orig.len()) {
+                return Some(start);
+            }
+        }
+    })
+}
+
+// text の start..end が英数字だけの場合、前後が英数字（と "_"）でなければ true を返す（英数字だけでなければ常に true）
+// 翻訳後の日本語に隣接する場合も置き換えられるよう、前後は ASCII の英数字のみ調べる
+fn is_whole_word(text: &str, start: usize, end: usize) -> bool {
+    let is_word = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let Some(orig) = text.get(start..end) else {
+        return true;
+    };
+    if orig.is_empty() || !orig.chars().all(is_word) {
+        return true;
+    }
+    !text[..start].chars().next_back().is_some_and(is_word)
+        && !text[end..].chars().next().is_some_and(is_word)
+}
+
+// ANSI のエスケープシーケンス（"\x1b[1m" など）の正規表現
+pub(crate) static ANSI_RE: once_cell::sync::Lazy<regex::Regex> =
+    once_cell::sync::Lazy::new(|| regex::Regex::new(r"\x1b\[[0-9;?]*[ -/]*[@-~]").unwrap());
+
+// ANSI のエスケープシーケンスを除いた文字列と、その各バイトの元の文字列でのバイト位置を返す
+pub(crate) fn strip_ansi(s: &str) -> (String, Vec<usize>) {
+    let mut plain = String::with_capacity(s.len());
+    let mut positions = Vec::with_capacity(s.len());
+    let mut last = 0;
+    for m in ANSI_RE.find_iter(s) {
+        plain.push_str(&s[last..m.start()]);
+        positions.extend(last..m.start());
+        last = m.end();
+    }
+    plain.push_str(&s[last..]);
+    positions.extend(last..s.len());
+    (plain, positions)
+}
+
+// index 以下で最も近い文字境界の位置を返す
+fn floor_char_boundary(s: &str, index: usize) -> usize {
+    if index >= s.len() {
+        return s.len();
+    }
+    (0..=index)
+        .rev()
+        .find(|&i| s.is_char_boundary(i))
+        .unwrap_or(0)
+}
+
+// start..end の範囲を文字境界に切り下げて取り出す（マルチバイト文字の途中を指定しても panic しない）
+// 隣接する範囲を同じ位置で区切れば、取り出した部分をつなげると元の範囲と一致する
+fn slice_at_char_boundaries(s: &str, start: usize, end: usize) -> &str {
+    let start = floor_char_boundary(s, start);
+    let end = floor_char_boundary(s, end).max(start);
+    &s[start..end]
+}
+
+/// メッセージを日本語に翻訳する（エントリは指定された順に試す）
+/// 同じ翻訳データで何度も翻訳する場合は、`TranslationTable` の方が速い
+pub fn translate_message(message: &str, translations: &[TranslateEntry]) -> String {
+    TranslationTable::new(translations.to_vec()).translate(message)
+}
+
+/// 正規表現をあらかじめ作っておいた翻訳データ
+/// 同じ翻訳データで何度も翻訳する場合は、`translate_message` よりこちらが速い
+/// プレースホルダを含まないエントリにメッセージ全体が一致する場合は、そのエントリを優先する
+///
+/// ```
+/// use rustc_ja_wrapper::TranslationTable;
+///
+/// let table = TranslationTable::from_json_str(
+///     r#"[{ "en": "expected `{$ty1}`, found `{$ty2}`", "ja": "`{$ty1}`を期待したが、`{$ty2}`が見つかった" }]"#,
+/// )
+/// .unwrap();
+/// assert_eq!(
+///     table.translate("expected `i32`, found `&str`"),
+///     "`i32`を期待したが、`&str`が見つかった"
+/// );
+/// ```
+pub struct TranslationTable {
+    entries: Vec<TranslateEntry>,
+    /// 英語文字列から作った正規表現と、そのエントリの位置（正規表現を作れないエントリは除く）
+    patterns: Vec<(regex::Regex, usize)>,
+    /// プレースホルダを含まない英語文字列と、そのエントリの位置（同じ文字列は最初のもの）
+    /// context を持つエントリと同じ英語文字列は、文脈を調べる必要があるので含めない
+    exact: std::collections::HashMap<String, usize>,
+    /// 固定の英語文字列のエントリの索引（エントリがなければ None）
+    literal: Option<LiteralIndex>,
+    /// rendered の置換にも索引を使うか（索引のエントリが多い場合のみ）
+    replace_with_index: bool,
+    /// 言語ごとの翻訳済みのメッセージ（同じメッセージが何度も出力されるため）
+    /// 言語、メッセージの順に `&str` で引くので、覚えていれば文字列を作らずに済む
+    cache: std::sync::Mutex<std::collections::HashMap<String, MessageCache>>,
+}
+
+/// 翻訳済みのメッセージを 1 つの世代に覚えておく最大の件数（言語ごと）
+const CACHE_CAPACITY: usize = 4096;
+
+/// 1 つの言語の翻訳済みのメッセージ（キーはメッセージ）
+/// 新しい世代が上限に達したら古い世代にして、それまでの古い世代を忘れる
+/// 古い世代で見つかったものは新しい世代に移すので、繰り返し出力されるメッセージは上限を超えても忘れない
+#[derive(Default)]
+struct MessageCache {
+    current: std::collections::HashMap<String, String>,
+    previous: std::collections::HashMap<String, String>,
+}
+
+impl MessageCache {
+    fn get(&mut self, message: &str) -> Option<String> {
+        if let Some(result) = self.current.get(message) {
+            return Some(result.clone());
+        }
+        let (message, result) = self.previous.remove_entry(message)?;
+        self.insert(message, result.clone());
+        Some(result)
+    }
+
+    fn insert(&mut self, message: String, result: String) {
+        if self.current.len() >= CACHE_CAPACITY {
+            self.previous = std::mem::take(&mut self.current);
+        }
+        self.current.insert(message, result);
+    }
+}
+
+impl TranslationTable {
+    /// 翻訳データから作る（エントリは指定された順に試す、英語文字列が空のエントリは使わない）
+    pub fn new(entries: Vec<TranslateEntry>) -> Self {
+        let patterns = entries
+            .iter()
+            .enumerate()
+            .filter(|(_, e)| !e.en.is_empty())
+            .flat_map(|(i, e)| compile_patterns(e).into_iter().map(move |re| (re, i)))
+            .collect();
+        let with_context: std::collections::HashSet<&str> = entries
+            .iter()
+            .filter(|e| e.context.is_some())
+            .map(|e| e.en.as_str())
+            .collect();
+        let mut exact = std::collections::HashMap::new();
+        for (i, e) in entries.iter().enumerate() {
+            if !e.en.is_empty()
+                && !e.ignore_case
+                && !e.regex
+                && !with_context.contains(e.en.as_str())
+                && !PLACEHOLDER_RE.is_match(&e.en)
+            {
+                exact.entry(e.en.clone()).or_insert(i);
+            }
+        }
+        let literal = LiteralIndex::new(&entries);
+        let replace_with_index = literal
+            .as_ref()
+            .is_some_and(|index| index.entries.len() >= ReplaceStrategy::AHO_CORASICK_THRESHOLD);
+        TranslationTable {
+            entries,
+            patterns,
+            exact,
+            literal,
+            replace_with_index,
+            cache: Default::default(),
+        }
+    }
+
+    // rendered の置換方法
+    fn replace_strategy(&self) -> ReplaceStrategy<'_> {
+        match &self.literal {
+            Some(index) if self.replace_with_index => ReplaceStrategy::AhoCorasick(index),
+            _ => ReplaceStrategy::Linear,
+        }
+    }
+
+    /// 翻訳データのエントリから作る
+    /// 同じ英語文字列（と文脈）のエントリは最後のものだけ残し、英語文字列の長い順に並べ替える
+    ///
+    /// ```
+    /// use rustc_ja_wrapper::{TranslateEntry, TranslationTable};
+    ///
+    /// let entry = |en: &str, ja: &str| TranslateEntry {
+    ///     en: en.to_string(),
+    ///     ja: ja.to_string(),
+    ///     ..Default::default()
+    /// };
+    /// let table = TranslationTable::from_entries(vec![
+    ///     entry("unused variable: `{$name}`", "変数が使われていません: `{$name}`"),
+    ///     entry("unused variable: `{$name}` here", "ここで変数が使われていません: `{$name}`"),
+    ///     entry("unused variable: `{$name}`", "未使用の変数: `{$name}`"),
+    /// ]);
+    /// assert_eq!(table.entries().len(), 2);
+    /// assert_eq!(table.translate("unused variable: `x` here"), "ここで変数が使われていません: `x`");
+    /// assert_eq!(table.translate("unused variable: `x`"), "未使用の変数: `x`");
+    /// ```
+    pub fn from_entries(entries: Vec<TranslateEntry>) -> Self {
+        let (mut entries, _) = file::dedup_translations(entries);
+        file::sort_entries(&mut entries);
+        TranslationTable::new(entries)
+    }
+
+    /// 翻訳ファイル（JSON）の内容から作る（`from_entries` と同じく重複を除いて並べ替える）
+    /// カテゴリ分けされた翻訳ファイルは、すべてのカテゴリのエントリを使う
+    ///
+    /// ```
+    /// use rustc_ja_wrapper::TranslationTable;
+    ///
+    /// let table = TranslationTable::from_json_str(
+    ///     r#"{ "types": [{ "en": "mismatched types", "ja": "型が不一致です" }] }"#,
+    /// )
+    /// .unwrap();
+    /// assert_eq!(table.translate("mismatched types"), "型が不一致です");
+    /// assert!(TranslationTable::from_json_str("not json").is_err());
+    /// ```
+    pub fn from_json_str(json_str: &str) -> Result<Self, serde_json::Error> {
+        Ok(TranslationTable::from_entries(file::parse_translations(
+            json_str, None,
+        )?))
+    }
+
+    /// 翻訳ファイル（JSON）を読み込んで作る（JSON として読み込めなければ `InvalidData` のエラー）
+    ///
+    /// ```no_run
+    /// use rustc_ja_wrapper::TranslationTable;
+    ///
+    /// let table = TranslationTable::from_file("assets/translate.ja.json")?;
+    /// println!("{}", table.translate("mismatched types"));
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn from_file(path: impl AsRef<std::path::Path>) -> std::io::Result<Self> {
+        let json_str = std::fs::read_to_string(path)?;
+        TranslationTable::from_json_str(&json_str)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+    }
+
+    /// 翻訳データのエントリ
+    pub fn entries(&self) -> &[TranslateEntry] {
+        &self.entries
+    }
+
+    /// JSON内のメッセージを日本語に翻訳する（対象は `translate_json_message` と同じ）
+    ///
+    /// ```
+    /// use rustc_ja_wrapper::TranslationTable;
+    ///
+    /// let table = TranslationTable::from_json_str(r#"[{ "en": "mismatched types", "ja": "型が不一致です" }]"#).unwrap();
+    /// let json = serde_json::json!({ "message": "mismatched types", "spans": [], "children": [], "rendered": null });
+    /// assert_eq!(table.translate_json(&json)["message"], "型が不一致です");
+    /// ```
+    pub fn translate_json(&self, json: &serde_json::Value) -> serde_json::Value {
+        translate_json_message_with(
+            json,
+            &|m| self.translate(m),
+            None,
+            None,
+            false,
+            self.replace_strategy(),
+        )
+        .0
+    }
+
+    /// メッセージを日本語に翻訳する（結果は `translate_message` と同じ）
+    pub fn translate(&self, message: &str) -> String {
+        self.translate_for(DEFAULT_LANG, message)
+    }
+
+    /// メッセージを指定した言語に翻訳する（その言語の翻訳がないエントリは使わない）
+    /// 一度翻訳したメッセージは覚えておき、同じメッセージでは照合を省く
+    pub fn translate_for(&self, lang: &str, message: &str) -> String {
+        if let Some(result) = self
+            .cache
+            .lock()
+            .unwrap()
+            .get_mut(lang)
+            .and_then(|cache| cache.get(message))
+        {
+            return result;
+        }
+        let result = self.translate_uncached(lang, message);
+        self.cache
+            .lock()
+            .unwrap()
+            .entry(lang.to_string())
+            .or_default()
+            .insert(message.to_string(), result.clone());
+        result
+    }
+
+    // 覚えておいた翻訳を使わずに翻訳する
+    fn translate_uncached(&self, lang: &str, message: &str) -> String {
+        if let Some(&i) = self.exact.get(message)
+            && let Some(ja_str) = self.entries[i].text_for(lang)
+        {
+            return fill_placeholders(ja_str, |_| None);
+        }
+        let candidates = self.literal.as_ref().map(|index| index.candidates(message));
+        for (re, i) in &self.patterns {
+            let entry = &self.entries[*i];
+            if let (Some(index), Some(candidates)) = (&self.literal, &candidates)
+                && index.indexed[*i]
+                && candidates.binary_search(i).is_err()
+            {
+                continue;
+            }
+            if entry.text_for(lang).is_none() || !entry.applies_to(message) {
+                continue;
+            }
+            if let Some(result) = apply_pattern(re, entry, lang, message) {
+                return result;
+            }
+        }
+        message.to_string()
+    }
+}
+
+/// 固定の英語文字列（プレースホルダや "{{" などを含まない）のエントリを、
+/// メッセージの先頭に一致するものだけに絞り込む索引
+/// Aho-Corasick 法ですべての英語文字列を同時に、メッセージの先頭からアンカー付きで探すので、
+/// エントリが多くても先頭の英語文字列が続く部分を走査するだけで済む
+/// regex、ignore_case、複数形のエントリは含めない（正規表現ですべて試す）
+/// エントリが多い場合は、rendered の置換にも使う（[`ReplaceStrategy::AhoCorasick`]）
+#[derive(Debug)]
+struct LiteralIndex {
+    /// 英語文字列をすべて探すオートマトン（同じ英語文字列は 1 つのパターンにする）
+    automaton: aho_corasick::AhoCorasick,
+    /// 各パターンのエントリの位置（同じ英語文字列のエントリはすべて）
+    entries: Vec<Vec<usize>>,
+    /// エントリの位置ごとに、索引に含めたか
+    indexed: Vec<bool>,
+}
+
+impl LiteralIndex {
+    fn new(entries: &[TranslateEntry]) -> Option<Self> {
+        let indexed: Vec<bool> = entries
+            .iter()
+            .map(|e| {
+                !e.en.is_empty()
+                    && !e.regex
+                    && !e.ignore_case
+                    && e.plural.is_none()
+                    && !PLACEHOLDER_RE.is_match(&e.en)
+            })
+            .collect();
+        // 一致するパターンは位置ごとに 1 つだけ報告されるので、同じ英語文字列はまとめる
+        let mut patterns: Vec<&str> = Vec::new();
+        let mut literal: Vec<Vec<usize>> = Vec::new();
+        let mut pattern_of = std::collections::HashMap::new();
+        for i in (0..entries.len()).filter(|&i| indexed[i]) {
+            let en = entries[i].en.as_str();
+            let pattern = *pattern_of.entry(en).or_insert_with(|| {
+                patterns.push(en);
+                literal.push(Vec::new());
+                patterns.len() - 1
+            });
+            literal[pattern].push(i);
+        }
+        if patterns.is_empty() {
+            return None;
+        }
+        let automaton = aho_corasick::AhoCorasick::builder()
+            .match_kind(aho_corasick::MatchKind::LeftmostLongest)
+            .start_kind(aho_corasick::StartKind::Both)
+            .build(&patterns)
+            .ok()?;
+        Some(LiteralIndex {
+            automaton,
+            entries: literal,
+            indexed,
+        })
+    }
+
+    // text の start から始まる英語文字列に一致する範囲を、長い順にすべて返す
+    // アンカー付きで検索するので、start から英語文字列が続く範囲しか走査しない
+    fn matches_at<'a>(
+        &'a self,
+        text: &'a str,
+        start: usize,
+    ) -> impl Iterator<Item = aho_corasick::Match> + 'a {
+        let mut end = text.len();
+        std::iter::from_fn(move || {
+            let input = aho_corasick::Input::new(text)
+                .range(start..end)
+                .anchored(aho_corasick::Anchored::Yes);
+            let m = self.automaton.find(input)?;
+            // 次はこれより短いものを探す（英語文字列は空でないので end は start 以上）
+            end = m.end() - 1;
+            Some(m)
+        })
+    }
+
+    // text がいずれかの英語文字列と同じか（先頭に一致する最も長いものが全体と同じか）
+    fn contains(&self, text: &str) -> bool {
+        self.matches_at(text, 0)
+            .next()
+            .is_some_and(|m| m.end() == text.len())
+    }
+
+    // text の from 以降で、英語文字列のうち replaced（翻訳前の文字列と置換対象の位置）にあるものが
+    // 単語として一致する最初の範囲と置換対象の位置（同じ位置では長いものを優先する）
+    // 一致した位置に使えるものがなければ次の文字から探し直すので、重なる短いものも候補になる
+    fn next_replacement(
+        &self,
+        text: &str,
+        replaced: &std::collections::HashMap<&str, usize>,
+        mut from: usize,
+    ) -> Option<(usize, usize, usize)> {
+        loop {
+            let start = self
+                .automaton
+                .find(aho_corasick::Input::new(text).range(from..))?
+                .start();
+            let found = self.matches_at(text, start).find_map(|m| {
+                let i = replaced.get(&text[start..m.end()])?;
+                is_whole_word(text, start, m.end()).then_some((start, m.end(), *i))
+            });
+            if found.is_some() {
+                return found;
+            }
+            from = start + text[start..].chars().next().map_or(1, char::len_utf8);
+        }
+    }
+
+    // 英語文字列がメッセージの先頭に一致するエントリの位置（昇順、なければ割り当てない）
+    fn candidates(&self, message: &str) -> Vec<usize> {
+        let mut candidates: Vec<usize> = self
+            .matches_at(message, 0)
+            .flat_map(|m| self.entries[m.pattern().as_usize()].iter().copied())
+            .collect();
+        candidates.sort_unstable();
+        candidates
+    }
+}
+
+// プレースホルダ用の正規表現（"{$name}" か、一致する文字の種類を付けた "{$name:ident}" など）
+// 文字としての "{" と "}" を表す "{{" と "}}" にも一致する（その場合はグループ 1 がない）
+static PLACEHOLDER_RE: once_cell::sync::Lazy<regex::Regex> =
+    once_cell::sync::Lazy::new(|| regex::Regex::new(r"\{\{|\}\}|\{\$(\w+)(?::(\w+))?\}").unwrap());
+
+/// 文字列をそのまま `en` や翻訳に使えるようにする
+/// プレースホルダや `{{` のような部分を含む場合のみ、すべての `{` と `}` を `{{` と `}}` にする
+pub fn escape_braces(text: &str) -> String {
+    if PLACEHOLDER_RE.is_match(text) {
+        text.replace('{', "{{").replace('}', "}}")
+    } else {
+        text.to_string()
+    }
+}
+
+// PLACEHOLDER_RE の一致が "{{" か "}}" であれば、その文字を返す
+fn escaped_brace(caps: &regex::Captures) -> Option<char> {
+    match &caps[0] {
+        "{{" => Some('{'),
+        "}}" => Some('}'),
+        _ => None,
+    }
+}
+
+// 翻訳後の文字列のプレースホルダを値で置き換え、"{{" と "}}" を "{" と "}" にする
+// 種類の指定がないプレースホルダのみ置き換え、値のないものはそのまま残す
+// 置き換えた値の中の "{$name}" などは置き換えない
+fn fill_placeholders<'a>(text: &str, value: impl Fn(&str) -> Option<&'a str>) -> String {
+    PLACEHOLDER_RE
+        .replace_all(text, |caps: &regex::Captures| {
+            if let Some(c) = escaped_brace(caps) {
+                return c.to_string();
+            }
+            caps.get(2)
+                .is_none()
+                .then(|| value(&caps[1]))
+                .flatten()
+                .unwrap_or(&caps[0])
+                .to_string()
+        })
+        .into_owned()
+}
+
+/// プレースホルダの種類（`{$name:ident}` の `ident` など）に一致する正規表現（未知の種類なら None）
+/// - `ident`: 識別子（`r#type` のような生識別子を含む）
+/// - `path`: `std::fmt::Display` のようなパス
+/// - `any`: 改行を含む任意の文字列
+pub fn placeholder_class(kind: &str) -> Option<&'static str> {
+    match kind {
+        "ident" => Some(r"(?:r#)?\w+"),
+        "path" => Some(r"[\w:]+"),
+        "any" => Some(r"[\s\S]+?"),
+        _ => None,
+    }
+}
+
+// 文字列に含まれるプレースホルダの種類（`{$name:ident}` の `ident` など）を、出現順に返す
+pub(crate) fn placeholder_kinds(text: &str) -> Vec<&str> {
+    PLACEHOLDER_RE
+        .captures_iter(text)
+        .filter_map(|caps| caps.get(2).map(|m| m.as_str()))
+        .collect()
+}
+
+/// 文字列に含まれるプレースホルダ（`{$name}` や `{$name:ident}`）の名前を、出現順に返す
+pub fn placeholder_names(text: &str) -> Vec<&str> {
+    PLACEHOLDER_RE
+        .captures_iter(text)
+        .filter_map(|caps| caps.get(1).map(|m| m.as_str()))
+        .collect()
+}
+
+// エントリの英語文字列から、メッセージと照合する正規表現を作る（ignore_case なら大文字と小文字を区別しない）
+// regex のエントリは英語文字列をそのまま正規表現として使い、正しくない正規表現は除く
+// 最後のグループは残りの文字列（exact なら必ず空）
+// 複数形の英語文字列があれば、その正規表現も作る（"errors" が "error" の後に続くと誤らないよう、長い方を先にする）
+fn compile_patterns(entry: &TranslateEntry) -> Vec<regex::Regex> {
+    let plural_en = entry.plural.as_ref().and_then(|p| p.en.as_deref());
+    let mut en_strs: Vec<&str> = std::iter::once(entry.en.as_str())
+        .chain(plural_en)
+        .collect();
+    en_strs.sort_by_key(|en_str| std::cmp::Reverse(en_str.len()));
+    en_strs
+        .into_iter()
+        .filter_map(|en_str| compile_en(entry, en_str))
+        .collect()
+}
+
+// エントリの設定（ignore_case、regex、exact）で、英語文字列 en_str から正規表現を作る
+fn compile_en(entry: &TranslateEntry, en_str: &str) -> Option<regex::Regex> {
+    let flags = if entry.ignore_case { "(?i)" } else { "" };
+    let rest = if entry.is_exact() { "()" } else { "(.*)" };
+    if entry.regex {
+        return regex::Regex::new(&format!("{}^(?:{}){}$", flags, en_str, rest)).ok();
+    }
+
+    // プレースホルダ以外の部分をエスケープしつつ、プレースホルダは名前付きグループに変換
+    // "{{" と "}}" は文字としての "{" と "}" に一致させる
+    let mut re_str = String::new();
+    let mut last = 0;
+    let mut seen = std::collections::HashMap::new();
+    for caps in PLACEHOLDER_RE.captures_iter(en_str) {
+        let m = caps.get(0).unwrap();
+        // プレースホルダ前の部分をエスケープ
+        re_str.push_str(&regex::escape(&en_str[last..m.start()]));
+        last = m.end();
+        if let Some(c) = escaped_brace(&caps) {
+            re_str.push_str(&regex::escape(&c.to_string()));
+            continue;
+        }
+        // プレースホルダ部分を名前付きグループに
+        // 同じ名前の 2 回目以降は別名のグループにして、一致した後で同じ値か確かめる
+        // （regex クレートは後方参照に対応していないため）
+        // 種類の指定があれば、その種類の文字列のみに一致させる（未知の種類なら正規表現を作らない）
+        let name = caps.get(1).unwrap().as_str();
+        let class = match caps.get(2) {
+            Some(kind) => placeholder_class(kind.as_str())?,
+            None => ".+?",
+        };
+        let count = seen.entry(name).or_insert(0);
+        *count += 1;
+        if *count == 1 {
+            re_str.push_str(&format!("(?P<{}>{})", name, class));
+        } else {
+            re_str.push_str(&format!(
+                "(?P<{}{}_{}>{})",
+                REPEAT_PREFIX, count, name, class
+            ));
+        }
+    }
+    // 残りの部分をエスケープ
+    re_str.push_str(&regex::escape(&en_str[last..]));
+
+    // 末尾に「.*」を追加して先頭一致＋残り文字列取得（exact なら全体一致）
+    regex::Regex::new(&format!("{}^{}{}$", flags, re_str, rest)).ok()
+}
+
+// 繰り返し現れるプレースホルダの 2 回目以降のグループ名の接頭辞（"__repeat2_name" など）
+const REPEAT_PREFIX: &str = "__repeat";
+
+// 繰り返し現れるプレースホルダのグループ名であれば、元のプレースホルダ名を返す
+fn repeated_placeholder(group: &str) -> Option<&str> {
+    let rest = group.strip_prefix(REPEAT_PREFIX)?;
+    let (_, name) = rest.split_once('_')?;
+    Some(name)
+}
+
+// メッセージが正規表現に一致すれば、エントリの指定した言語の翻訳後の文字列を返す
+// 同じプレースホルダが繰り返し現れる場合、すべて同じ値でなければ一致しないものとする
+// `{$count}` の値が 1 以外なら、複数形の翻訳を使う
+fn apply_pattern(
+    re: &regex::Regex,
+    entry: &TranslateEntry,
+    lang: &str,
+    message: &str,
+) -> Option<String> {
+    let caps = re.captures(message)?;
+    let count = caps.name(COUNT_PLACEHOLDER).map(|m| m.as_str());
+    let ja_str = entry.text_for_count(lang, count)?;
+    for group in re.capture_names().flatten() {
+        if let Some(name) = repeated_placeholder(group)
+            && caps.name(group).map(|m| m.as_str()) != caps.name(name).map(|m| m.as_str())
+        {
+            return None;
+        }
+    }
+    if !entry.match_in_backticks && splits_backticks(&caps, message) {
+        return None;
+    }
+    // ja側のプレースホルダをキャプチャ値で置換
+    // 番号で参照するグループもある（最後のグループは残り文字列なので除く）
+    let mut result = fill_placeholders(ja_str, |name| {
+        let group = match name.parse::<usize>() {
+            Ok(i) if (1..caps.len() - 1).contains(&i) => caps.get(i),
+            Ok(_) => None,
+            Err(_) if repeated_placeholder(name).is_some() => None,
+            Err(_) => caps.name(name),
+        };
+        group.map(|m| m.as_str())
+    });
+    // 追加: パターン外の残り文字列を末尾に追加
+    if let Some(extra) = caps.get(caps.len() - 1) {
+        let extra_str = extra.as_str();
+        if !extra_str.is_empty() {
+            result.push_str(extra_str);
+        }
+    }
+    Some(result)
+}
+
+// メッセージ中のバッククォートで囲まれた部分（前後のバッククォートの位置の組）
+fn backtick_regions(message: &str) -> Vec<(usize, usize)> {
+    let ticks: Vec<usize> = message.match_indices('`').map(|(i, _)| i).collect();
+    ticks
+        .chunks_exact(2)
+        .map(|pair| (pair[0], pair[1]))
+        .collect()
+}
+
+// 一致のうちプレースホルダ（グループ）以外の部分が、バッククォートで囲まれた部分にかかり、
+// かつ翻訳する範囲（残りの文字列より前）がその部分の途中で始まる、または終わるか
+fn splits_backticks(caps: &regex::Captures, message: &str) -> bool {
+    let whole = caps.get(0).unwrap().range();
+    let end = caps
+        .get(caps.len() - 1)
+        .map_or(whole.end, |rest| rest.start());
+    let mut groups: Vec<std::ops::Range<usize>> =
+        caps.iter().skip(1).flatten().map(|m| m.range()).collect();
+    groups.sort_by_key(|r| r.start);
+    // グループの間の部分（英語文字列の固定の部分）
+    let mut literals = Vec::new();
+    let mut pos = whole.start;
+    for group in groups {
+        if group.start > pos {
+            literals.push(pos..group.start);
+        }
+        pos = pos.max(group.end);
+    }
+    if whole.end > pos {
+        literals.push(pos..whole.end);
+    }
+    backtick_regions(message).into_iter().any(|(open, close)| {
+        let touches = literals.iter().any(|l| l.start < close && l.end > open + 1);
+        touches && !(whole.start <= open && end > close)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_translate_message_simple() {
+        // テスト用の翻訳データ
+        let test_translate_entries: &[TranslateEntry] = &[
+            TranslateEntry { en: "hello".to_string(), ja: "こんにちは".to_string(), ..Default::default() },
+            TranslateEntry { en: "error: {$name}".to_string(), ja: "エラー: {$name}".to_string(), ..Default::default() },
+            TranslateEntry { en: "borrow of moved value".to_string(), ja: "移動された値の借用".to_string(), ..Default::default() },
+            TranslateEntry {
+                en: "move occurs because `{$name}` has type `{$ty}`, which does not implement the `Copy` trait".to_string(),
+                ja: "`{$ty}` 型の `{$name}` は `Copy` トレイトを実装していないので、移動します".to_string(),
+                ..Default::default()
+            },
+        ];
+
+        assert_eq!(
+            translate_message("hello", test_translate_entries),
+            "こんにちは"
+        );
+        assert_eq!(
+            translate_message("error: foo", test_translate_entries),
+            "エラー: foo"
+        );
+        assert_eq!(
+            translate_message("not found", test_translate_entries),
+            "not found"
+        );
+        assert_eq!(
+            translate_message("borrow of moved value", test_translate_entries),
+            "移動された値の借用"
+        );
+        assert_eq!(
+            translate_message(
+                "move occurs because `s1` has type `String`, which does not implement the `Copy` trait",
+                test_translate_entries
+            ),
+            "`String` 型の `s1` は `Copy` トレイトを実装していないので、移動します"
+        );
+    }
+
+    #[test]
+    fn test_translate_json_message_message_field() {
+        let test_translate_entries: &[TranslateEntry] = &[
+            TranslateEntry {
+                en: "borrow of moved value".to_string(),
+                ja: "移動された値の借用".to_string(),
+                exact: Some(false),
+                ..Default::default()
+            },
+            TranslateEntry {
+                en: "value moved here".to_string(),
+                ja: "ここで値を移動".to_string(),
+                ..Default::default()
+            },
+            TranslateEntry {
+                en: "value borrowed here after move".to_string(),
+                ja: "移動後の値をここで借用".to_string(),
+                ..Default::default()
+            },
+            TranslateEntry {
+                en: "consider cloning the value if the performance cost is acceptable".to_string(),
+                ja: "複製コストが許容できるなら、クローンすることを検討してください".to_string(),
+                ..Default::default()
+            },
+        ];
+        let json = serde_json::json!({
+            "message": "borrow of moved value: `s1`",
+            "spans": [
+                {
+                    "label": "value moved here",
+                },
+                {
+                    "label": "value borrowed here after move",
+                },
+            ],
+            "children": [
+                {
+                    "message": "consider cloning the value if the performance cost is acceptable",
+                    "spans": [
+                        {
+                            "label": "hello",
+                        }
+                    ],
+                },
+            ],
+            "rendered": "borrow of moved value: `s1`\nvalue moved here\nvalue borrowed here after move\nconsider cloning the value if the performance cost is acceptable",
+        });
+        let translated = translate_json_message(&json, test_translate_entries);
+        let expected_json = serde_json::json!({
+            "message": "移動された値の借用: `s1`",
+            "spans": [
+                {
+                    "label": "ここで値を移動",
+                },
+                {
+                    "label": "移動後の値をここで借用",
+                },
+            ],
+            "children": [
+                {
+                    "message": "複製コストが許容できるなら、クローンすることを検討してください",
+                    "spans": [
+                        {
+                            "label": "hello",
+                        }
+                    ],
+                },
+            ],
+            "rendered": "移動された値の借用: `s1`\nここで値を移動\n移動後の値をここで借用\n複製コストが許容できるなら、クローンすることを検討してください",
+        });
+        assert_eq!(translated.get("message"), expected_json.get("message"));
+        assert_eq!(translated.get("spans"), expected_json.get("spans"));
+        assert_eq!(translated.get("children"), expected_json.get("children"));
+        assert_eq!(translated.get("rendered"), expected_json.get("rendered"));
+    }
+
+    // テスト用の rendered と置換対象を作る
+    fn rendered_fixture(count: usize) -> (String, Vec<(String, String)>) {
+        let replaced: Vec<(String, String)> = (0..count)
+            .map(|i| (format!("message {i} here"), format!("メッセージ{i}番")))
+            .collect();
+        let rendered = replaced
+            .iter()
+            .map(|(orig, _)| format!("error: {orig}\n  --> src/main.rs:1:1\n   |\n"))
+            .collect::<String>()
+            .repeat(4);
+        (rendered, replaced)
+    }
+
+    #[test]
+    fn test_replace_rendered_strategies_match() {
+        for count in [1, 3, 20, 70] {
+            let (rendered, replaced) = rendered_fixture(count);
+            let linear = replace_rendered(&rendered, &replaced, ReplaceStrategy::Linear);
+            // 索引にない置換対象は 1 件ずつ探す
+            let index = replaced_index(&replaced);
+            let half_index = replaced_index(&replaced[..count.div_ceil(2)]);
+            for index in [&index, &half_index] {
+                let aho_corasick =
+                    replace_rendered(&rendered, &replaced, ReplaceStrategy::AhoCorasick(index));
+                assert_eq!(linear, aho_corasick);
+            }
+            assert!(linear.contains("メッセージ0番"));
+            assert!(!linear.contains("message 0 here"));
+        }
+
+        // 単語の一部なので除いた一致（"xab" の "ab"）と重なる、短い置換対象も試す
+        let replaced = vec![
+            ("ab".to_string(), "エービー".to_string()),
+            ("b c".to_string(), "ビーシー".to_string()),
+        ];
+        for (rendered, expected) in [
+            ("xab c\n", "xaビーシー\n"),
+            ("\x1b[1mxab c\x1b[0m\n", "\x1b[1mxaビーシー\x1b[0m\n"),
+        ] {
+            let index = replaced_index(&replaced);
+            for strategy in [
+                ReplaceStrategy::Linear,
+                ReplaceStrategy::AhoCorasick(&index),
+            ] {
+                assert_eq!(
+                    replace_rendered(rendered, &replaced, strategy),
+                    expected,
+                    "{strategy:?}"
+                );
+            }
+        }
+
+        // 固定の英語文字列のエントリの件数から、翻訳データを作るときに置換方法を選ぶ
+        let literal = |count: usize| -> Vec<TranslateEntry> {
+            (0..count)
+                .map(|i| TranslateEntry {
+                    en: format!("message {i} here"),
+                    ja: format!("メッセージ{i}番"),
+                    ..Default::default()
+                })
+                .chain([TranslateEntry {
+                    en: "expected `{$ty}`".to_string(),
+                    ja: "`{$ty}`を期待した".to_string(),
+                    ..Default::default()
+                }])
+                .collect()
+        };
+        let threshold = ReplaceStrategy::AHO_CORASICK_THRESHOLD;
+        let small = TranslationTable::new(literal(threshold - 1));
+        let large = TranslationTable::new(literal(threshold));
+        assert!(matches!(small.replace_strategy(), ReplaceStrategy::Linear));
+        assert!(matches!(
+            large.replace_strategy(),
+            ReplaceStrategy::AhoCorasick(_)
+        ));
+        let json = serde_json::json!({
+            "message": "message 1 here",
+            "spans": [{ "label": "expected `i32`" }],
+            "children": [{ "message": "message 0 here", "spans": [] }],
+            "rendered": "error: message 1 here\n  |     ^^^ expected `i32`\nnote: message 0 here\n",
+        });
+        let translated = large.translate_json(&json);
+        assert_eq!(
+            translated["rendered"],
+            "error: メッセージ1番\n  |     ^^^ `i32`を期待した\nnote: メッセージ0番\n"
+        );
+        assert_eq!(translated, small.translate_json(&json));
+    }
+
+    // 置換対象の翻訳前の文字列を英語文字列にした索引（Aho-Corasick 法で置換するため）
+    fn replaced_index(replaced: &[(String, String)]) -> LiteralIndex {
+        let entries: Vec<TranslateEntry> = replaced
+            .iter()
+            .map(|(en, ja)| TranslateEntry {
+                en: en.clone(),
+                ja: ja.clone(),
+                ..Default::default()
+            })
+            .collect();
+        LiteralIndex::new(&entries).unwrap()
+    }
+
+    // 翻訳データとメッセージの組（同じ形のエントリを count 件並べ、最後のエントリに一致するメッセージを含める）
+    fn table_fixture(count: usize) -> (Vec<TranslateEntry>, Vec<String>) {
+        let entries = (0..count)
+            .map(|i| TranslateEntry {
+                en: format!("message {i} about `{{$name}}` with type `{{$ty}}`"),
+                ja: format!("`{{$ty}}`型の`{{$name}}`についてのメッセージ{i}"),
+                ..Default::default()
+            })
+            .collect();
+        let messages = vec![
+            format!("message {} about `x` with type `i32`", count - 1),
+            "message 0 about `s` with type `String`: extra".to_string(),
+            "an unknown message".to_string(),
+        ];
+        (entries, messages)
+    }
+
+    #[test]
+    fn test_translation_table() {
+        let (entries, messages) = table_fixture(16);
+        let table = TranslationTable::new(entries.clone());
+        for message in &messages {
+            assert_eq!(
+                table.translate(message),
+                translate_message(message, &entries)
+            );
+        }
+        assert_eq!(
+            table.translate(&messages[0]),
+            "`i32`型の`x`についてのメッセージ15"
+        );
+        assert_eq!(table.entries().len(), 16);
+    }
+
+    #[test]
+    fn test_translator_with_missing() {
+        let entries = vec![TranslateEntry {
+            en: "value moved here".to_string(),
+            ja: "ここで値を移動しました".to_string(),
+            ..Default::default()
+        }];
+        let missing = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = missing.clone();
+        let translator = Translator::new(entries)
+            .with_missing(Box::new(move |m| sink.lock().unwrap().push(m.to_string())));
+
+        let json = serde_json::json!({
+            "message": "unknown message",
+            "spans": [{ "label": "value moved here" }, { "label": "" }, { "label": null }],
+            "children": [{ "message": "another unknown", "spans": [] }],
+        });
+        translator.translate_json(&json);
+        assert_eq!(
+            *missing.lock().unwrap(),
+            ["unknown message", "another unknown"]
+        );
+    }
+
+    #[test]
+    fn test_translate_message_ignore_case() {
+        let entries: Vec<TranslateEntry> = serde_json::from_str(
+            r#"[
+                { "en": "error: {$name}", "ja": "エラー: {$name}", "ignore_case": true },
+                { "en": "mismatched types", "ja": "型が不一致です" }
+            ]"#,
+        )
+        .unwrap();
+        // プレースホルダには元の文字列を使う
+        assert_eq!(translate_message("Error: Foo", &entries), "エラー: Foo");
+        assert_eq!(translate_message("ERROR: foo", &entries), "エラー: foo");
+        // 指定のないエントリは区別する
+        assert_eq!(
+            translate_message("Mismatched types", &entries),
+            "Mismatched types"
+        );
+
+        let table = TranslationTable::new(entries);
+        assert_eq!(table.translate("Error: Foo"), "エラー: Foo");
+        assert_eq!(table.translate("Mismatched types"), "Mismatched types");
+    }
+
+    #[test]
+    fn test_translate_message_placeholder_class() {
+        let entries: Vec<TranslateEntry> = serde_json::from_str(
+            r#"[
+                { "en": "cannot find value `{$name:ident}` in this scope", "ja": "このスコープに値`{$name}`が見つかりません" },
+                { "en": "the trait `{$trait:path}` is not implemented", "ja": "トレイト`{$trait}`が実装されていません" },
+                { "en": "expected `{$ty}`, found `{$found}`", "ja": "`{$ty}`を期待したが、`{$found}`が見つかった" },
+                { "en": "the following code:\n{$code:any}\nis invalid", "ja": "次のコードは正しくありません:\n{$code}" },
+                { "en": "unknown kind `{$x:unknown}`", "ja": "使われない" }
+            ]"#,
+        )
+        .unwrap();
+        let table = TranslationTable::new(entries.clone());
+        let translators: [&dyn Fn(&str) -> String; 2] =
+            [&|m| translate_message(m, &entries), &|m| table.translate(m)];
+        for translate in translators {
+            // ident
+            assert_eq!(
+                translate("cannot find value `r#type` in this scope"),
+                "このスコープに値`r#type`が見つかりません"
+            );
+            assert_eq!(
+                translate("cannot find value `a.b` in this scope"),
+                "cannot find value `a.b` in this scope"
+            );
+            // path
+            assert_eq!(
+                translate("the trait `std::fmt::Display` is not implemented"),
+                "トレイト`std::fmt::Display`が実装されていません"
+            );
+            assert_eq!(
+                translate("the trait `Fn(i32)` is not implemented"),
+                "the trait `Fn(i32)` is not implemented"
+            );
+            // 種類の指定がなければ、これまでどおり（改行以外の任意の文字列）
+            assert_eq!(
+                translate("expected `Vec<i32>`, found `&str`"),
+                "`Vec<i32>`を期待したが、`&str`が見つかった"
+            );
+            // any は改行を含む値にも一致する
+            assert_eq!(
+                translate("the following code:\nlet x = 1;\nlet y = 2;\nis invalid"),
+                "次のコードは正しくありません:\nlet x = 1;\nlet y = 2;"
+            );
+            // 未知の種類のエントリは使わない
+            assert_eq!(translate("unknown kind `a`"), "unknown kind `a`");
+        }
+        assert_eq!(
+            placeholder_names("`{$name:ident}` and `{$ty}`"),
+            ["name", "ty"]
+        );
+    }
+
+    #[test]
+    fn test_translate_escaped_braces() {
+        let entries: Vec<TranslateEntry> = serde_json::from_str(
+            r#"[
+                { "en": "invalid format string: expected `'}}'`, found `{$c}`", "ja": "フォーマット文字列が正しくありません: `'}}'`を期待したが、`{$c}`が見つかった" },
+                { "en": "if you intended to print `{{`, you can escape it", "ja": "`{{`を表示したい場合は、エスケープできます" },
+                { "en": "literal `{{$name}}` in `{$fmt}`", "ja": "`{$fmt}`の中の文字としての`{{$name}}`" },
+                { "en": "value `{$v}` is invalid", "ja": "値`{$v}`は正しくありません" }
+            ]"#,
+        )
+        .unwrap();
+        let translator = Translator::new(entries);
+        let translate = |m: &str| translator.translate(m);
+
+        // en の "{{" と "}}" は文字としての "{" と "}" に一致する
+        assert_eq!(
+            translate("invalid format string: expected `'}'`, found `'a'`"),
+            "フォーマット文字列が正しくありません: `'}'`を期待したが、`'a'`が見つかった"
+        );
+        assert_eq!(
+            translate("if you intended to print `{`, you can escape it"),
+            "`{`を表示したい場合は、エスケープできます"
+        );
+        assert_eq!(
+            translate("invalid format string: expected `'}}'`, found `'a'`"),
+            "invalid format string: expected `'}}'`, found `'a'`"
+        );
+
+        // ja の "{{$name}}" は置き換えず、文字としての "{$name}" にする
+        assert_eq!(
+            translate("literal `{$name}` in `println!`"),
+            "`println!`の中の文字としての`{$name}`"
+        );
+        // 置き換えた値の中のプレースホルダのような文字列は、そのまま残す
+        assert_eq!(
+            translate("value `{$v}` is invalid"),
+            "値`{$v}`は正しくありません"
+        );
+
+        assert!(placeholder_names("`{{$name}}` and `{{`").is_empty());
+    }
+
+    #[test]
+    fn test_translate_message_backticks() {
+        let entries: Vec<TranslateEntry> = serde_json::from_str(
+            r#"[
+                { "en": "{$name} type", "ja": "{$name}の型", "exact": false },
+                { "en": "consider adding a `main` function", "ja": "`main`関数を追加してください" },
+                { "en": "expected `{$ty}`", "ja": "`{$ty}`を期待した", "exact": false },
+                { "en": "{$name} is never used", "ja": "{$name}は使われていません" },
+                { "en": "{$name} is never read`", "ja": "{$name}は読まれていません`", "match_in_backticks": true }
+            ]"#,
+        )
+        .unwrap();
+        let table = TranslationTable::new(entries.clone());
+        let translators: [&dyn Fn(&str) -> String; 2] =
+            [&|m| translate_message(m, &entries), &|m| table.translate(m)];
+        for translate in translators {
+            // バッククォートの中の識別子の一部には一致させない
+            assert_eq!(translate("`my type` alias"), "`my type` alias");
+            assert_eq!(translate("`x is never used`"), "`x is never used`");
+            assert_eq!(translate("my type"), "myの型");
+            // バッククォートも含めて全体に一致するならよい
+            assert_eq!(
+                translate("consider adding a `main` function"),
+                "`main`関数を追加してください"
+            );
+            assert_eq!(
+                translate("expected `i32`, found `u8`"),
+                "`i32`を期待した, found `u8`"
+            );
+            assert_eq!(translate("`x` is never used"), "`x`は使われていません");
+            // match_in_backticks のエントリは、バッククォートの中の一部にも一致させる
+            assert_eq!(translate("`x is never read`"), "`xは読まれていません`");
+        }
+    }
+
+    #[test]
+    fn test_translate_message_plural() {
+        let entries: Vec<TranslateEntry> = serde_json::from_str(
+            r#"[
+                {
+                    "en": "aborting due to {$count} previous error",
+                    "ja": "直前のエラーのため中止します",
+                    "plural": {
+                        "en": "aborting due to {$count} previous errors",
+                        "ja": "直前の{$count}個のエラーのため中止します"
+                    }
+                }
+            ]"#,
+        )
+        .unwrap();
+        let table = TranslationTable::new(entries.clone());
+        let translators: [&dyn Fn(&str) -> String; 2] =
+            [&|m| translate_message(m, &entries), &|m| table.translate(m)];
+        for translate in translators {
+            assert_eq!(
+                translate("aborting due to 0 previous errors"),
+                "直前の0個のエラーのため中止します"
+            );
+            assert_eq!(
+                translate("aborting due to 1 previous error"),
+                "直前のエラーのため中止します"
+            );
+            assert_eq!(
+                translate("aborting due to 12 previous errors"),
+                "直前の12個のエラーのため中止します"
+            );
+            // 数と英語の形が合わなくても、数で翻訳を選ぶ
+            assert_eq!(
+                translate("aborting due to 2 previous error"),
+                "直前の2個のエラーのため中止します"
+            );
+        }
+    }
+
+    #[test]
+    fn test_translate_message_empty_en() {
+        let entries: Vec<TranslateEntry> = serde_json::from_str(
+            r#"[
+                { "en": "mismatched types", "ja": "型が不一致です" },
+                { "en": "", "ja": "空", "exact": false },
+                { "en": "", "ja": "空" },
+                { "en": "value moved here", "ja": "ここで値を移動しました" }
+            ]"#,
+        )
+        .unwrap();
+        let table = TranslationTable::new(entries.clone());
+        let translators: [&dyn Fn(&str) -> String; 2] =
+            [&|m| translate_message(m, &entries), &|m| table.translate(m)];
+        for translate in translators {
+            assert_eq!(translate("mismatched types"), "型が不一致です");
+            assert_eq!(translate("value moved here"), "ここで値を移動しました");
+            assert_eq!(translate("an unknown message"), "an unknown message");
+            assert_eq!(translate(""), "");
+        }
+    }
+
+    #[test]
+    fn test_translate_message_exact() {
+        let entries: Vec<TranslateEntry> = serde_json::from_str(
+            r#"[
+                { "en": "expected", "ja": "期待される" },
+                { "en": "unused variable: `{$name}`", "ja": "変数が使われていません: `{$name}`" },
+                { "en": "value moved here", "ja": "ここで値を移動しました", "exact": false },
+                { "en": "expected `{$ty}`", "ja": "`{$ty}`を期待した", "exact": true }
+            ]"#,
+        )
+        .unwrap();
+        let table = TranslationTable::new(entries.clone());
+        let translators: [&dyn Fn(&str) -> String; 2] =
+            [&|m| translate_message(m, &entries), &|m| table.translate(m)];
+        for translate in translators {
+            // プレースホルダのないエントリは、長いメッセージの先頭には一致しない
+            assert_eq!(translate("expected"), "期待される");
+            assert_eq!(
+                translate("expected one of `,` or `}`, found `x`"),
+                "expected one of `,` or `}`, found `x`"
+            );
+            // プレースホルダのあるエントリは、残りの文字列を付ける
+            assert_eq!(
+                translate("unused variable: `x` (in this macro)"),
+                "変数が使われていません: `x` (in this macro)"
+            );
+            // exact の指定が優先される
+            assert_eq!(
+                translate("value moved here, in previous iteration of loop"),
+                "ここで値を移動しました, in previous iteration of loop"
+            );
+            assert_eq!(translate("expected `i32`"), "`i32`を期待した");
+            assert_eq!(translate("expected `i32` here"), "expected `i32` here");
+        }
+    }
+
+    #[test]
+    fn test_translate_message_context() {
+        let entries: Vec<TranslateEntry> = serde_json::from_str(
+            r#"[
+                { "en": "expected", "ja": "期待される型", "context": "mismatched types", "exact": false },
+                { "en": "expected", "ja": "期待される", "exact": false }
+            ]"#,
+        )
+        .unwrap();
+        let table = TranslationTable::new(entries.clone());
+        let translators: [&dyn Fn(&str) -> String; 2] =
+            [&|m| translate_message(m, &entries), &|m| table.translate(m)];
+        for translate in translators {
+            // 文脈が一致する
+            assert_eq!(
+                translate("expected: mismatched types"),
+                "期待される型: mismatched types"
+            );
+            // 文脈が一致しない
+            assert_eq!(translate("expected"), "期待される");
+            assert_eq!(translate("expected `;`"), "期待される `;`");
+        }
+    }
+
+    #[test]
+    fn test_translate_message_regex() {
+        let entries: Vec<TranslateEntry> = serde_json::from_str(
+            r#"[
+                { "en": "aborting due to (\\d+) previous errors?", "ja": "{$1}個のエラーのため中止します", "regex": true },
+                { "en": "(\\d+) (warning|error)s? emitted", "ja": "{$1}件の{$2}が出力されました", "regex": true },
+                { "en": "invalid (pattern", "ja": "不正", "regex": true },
+                { "en": "mismatched types", "ja": "型が不一致です" }
+            ]"#,
+        )
+        .unwrap();
+        let table = TranslationTable::new(entries.clone());
+        let translators: [&dyn Fn(&str) -> String; 2] =
+            [&|m| translate_message(m, &entries), &|m| table.translate(m)];
+        for translate in translators {
+            assert_eq!(
+                translate("aborting due to 3 previous errors"),
+                "3個のエラーのため中止します"
+            );
+            assert_eq!(
+                translate("aborting due to 1 previous error; 1 warning emitted"),
+                "1個のエラーのため中止します; 1 warning emitted"
+            );
+            assert_eq!(
+                translate("2 warnings emitted"),
+                "2件のwarningが出力されました"
+            );
+            // 正しくない正規表現のエントリは使わない
+            assert_eq!(translate("invalid (pattern"), "invalid (pattern");
+            assert_eq!(translate("mismatched types"), "型が不一致です");
+        }
+    }
+
+    #[test]
+    fn test_translate_message_repeated_placeholder() {
+        let entries: Vec<TranslateEntry> = serde_json::from_str(
+            r#"[
+                { "en": "cannot borrow `{$name}` as mutable because `{$name}` is also borrowed as immutable", "ja": "`{$name}`は不変で借用されているので、`{$name}`を可変で借用できません" }
+            ]"#,
+        )
+        .unwrap();
+        let table = TranslationTable::new(entries.clone());
+        let translators: [&dyn Fn(&str) -> String; 2] =
+            [&|m| translate_message(m, &entries), &|m| table.translate(m)];
+        for translate in translators {
+            assert_eq!(
+                translate("cannot borrow `v` as mutable because `v` is also borrowed as immutable"),
+                "`v`は不変で借用されているので、`v`を可変で借用できません"
+            );
+            // 同じプレースホルダの値が異なれば一致しない
+            let message = "cannot borrow `v` as mutable because `w` is also borrowed as immutable";
+            assert_eq!(translate(message), message);
+        }
+    }
+
+    #[test]
+    fn test_translation_table_exact() {
+        let entries: Vec<TranslateEntry> = serde_json::from_str(
+            r#"[
+                { "en": "unused variable: `{$name}`", "ja": "変数が使われていません: `{$name}`" },
+                { "en": "mismatched types", "ja": "型が不一致です", "ko": "타입이 일치하지 않습니다" },
+                { "en": "value moved here", "ja": "ここで値を移動しました", "exact": false }
+            ]"#,
+        )
+        .unwrap();
+        let table = TranslationTable::new(entries);
+        assert_eq!(table.exact.len(), 2);
+
+        // 完全一致
+        assert_eq!(table.translate("mismatched types"), "型が不一致です");
+        assert_eq!(
+            table.translate_for("ko", "mismatched types"),
+            "타입이 일치하지 않습니다"
+        );
+        // 完全一致しない場合は正規表現で探す
+        assert_eq!(
+            table.translate("unused variable: `x`"),
+            "変数が使われていません: `x`"
+        );
+        assert_eq!(
+            table.translate("value moved here, in previous iteration of loop"),
+            "ここで値を移動しました, in previous iteration of loop"
+        );
+        // その言語の翻訳がなければ翻訳しない
+        assert_eq!(
+            table.translate_for("ko", "value moved here"),
+            "value moved here"
+        );
+    }
+
+    #[test]
+    fn test_literal_index() {
+        let entries: Vec<TranslateEntry> = serde_json::from_str(
+            r#"[
+                { "en": "unused import", "ja": "使われていないインポート", "exact": false },
+                { "en": "unused", "ja": "未使用", "exact": false },
+                { "en": "unused {$what}", "ja": "未使用の{$what}" },
+                { "en": "Unused Import", "ja": "使われていないインポート（大文字）", "ignore_case": true, "exact": false },
+                { "en": "import", "ja": "インポート", "exact": false }
+            ]"#,
+        )
+        .unwrap();
+        let table = TranslationTable::new(entries);
+        let index = table.literal.as_ref().unwrap();
+        assert_eq!(index.indexed, [true, true, false, false, true]);
+        // 先頭に一致するエントリのみ候補にする（途中に現れる "import" は除く）
+        assert_eq!(index.candidates("unused import: `std::io`"), [0, 1]);
+        assert!(index.candidates("an unused import").is_empty());
+
+        // 索引を使わない場合と同じ翻訳になる
+        let mut without_index = TranslationTable::new(table.entries().to_vec());
+        without_index.literal = None;
+        for message in [
+            "unused import: `std::io`",
+            "unused variable",
+            "UNUSED IMPORT: `a`",
+            "import `a`",
+            "an unused import",
+        ] {
+            assert_eq!(
+                table.translate(message),
+                without_index.translate_uncached(DEFAULT_LANG, message),
+                "{message}"
+            );
+        }
+        assert_eq!(
+            table.translate("unused import: `std::io`"),
+            "使われていないインポート: `std::io`"
+        );
+        assert_eq!(
+            table.translate("UNUSED IMPORT: `a`"),
+            "使われていないインポート（大文字）: `a`"
+        );
+    }
+
+    #[test]
+    fn test_translation_table_cache() {
+        let (entries, messages) = table_fixture(16);
+        let table = TranslationTable::new(entries);
+        for _ in 0..2 {
+            for message in &messages {
+                assert_eq!(
+                    table.translate(message),
+                    table.translate_uncached(DEFAULT_LANG, message)
+                );
+            }
+        }
+        let cached = |message: &str| {
+            let cache = table.cache.lock().unwrap();
+            let cache = &cache[DEFAULT_LANG];
+            cache.current.contains_key(message) || cache.previous.contains_key(message)
+        };
+        assert_eq!(
+            table.cache.lock().unwrap()[DEFAULT_LANG].current.len(),
+            messages.len()
+        );
+        assert!(table.cache.lock().unwrap().get("ko").is_none());
+        assert_eq!(table.translate_for("ko", &messages[0]), messages[0]);
+        assert_eq!(table.cache.lock().unwrap()["ko"].current.len(), 1);
+
+        // 覚えておく件数には上限があるが、繰り返し翻訳するメッセージは忘れない
+        for i in 0..CACHE_CAPACITY * 3 {
+            table.translate(&format!("an unknown message {i}"));
+            if i % 100 == 0 {
+                table.translate(&messages[0]);
+            }
+        }
+        {
+            let cache = table.cache.lock().unwrap();
+            let cache = &cache[DEFAULT_LANG];
+            assert!(cache.current.len() <= CACHE_CAPACITY);
+            assert!(cache.previous.len() <= CACHE_CAPACITY);
+        }
+        assert!(cached(&messages[0]));
+        assert!(!cached(&messages[1]));
+        assert_eq!(
+            table.translate(&messages[0]),
+            table.translate_uncached(DEFAULT_LANG, &messages[0])
+        );
+    }
+
+    #[test]
+    fn test_translator_with_transform() {
+        let entries = vec![
+            TranslateEntry {
+                en: "mismatched types".to_string(),
+                ja: "型が不一致です".to_string(),
+                ..Default::default()
+            },
+            TranslateEntry {
+                en: "value moved here".to_string(),
+                ja: "ここで値を移動しました".to_string(),
+                ..Default::default()
+            },
+        ];
+        let translator = Translator::new(entries).with_transform(Box::new(|m| {
+            m.starts_with("mismatched").then(|| m.to_uppercase())
+        }));
+
+        // フックが Some を返せば、翻訳データより優先する
+        assert_eq!(translator.translate("mismatched types"), "MISMATCHED TYPES");
+        // フックが None を返せば、翻訳データで翻訳する
+        assert_eq!(
+            translator.translate("value moved here"),
+            "ここで値を移動しました"
+        );
+
+        let json = serde_json::json!({
+            "message": "mismatched types",
+            "spans": [{ "label": "value moved here" }],
+            "children": [],
+            "rendered": "mismatched types\nvalue moved here",
+        });
+        let translated = translator.translate_json(&json);
+        assert_eq!(translated["message"], "MISMATCHED TYPES");
+        assert_eq!(translated["spans"][0]["label"], "ここで値を移動しました");
+        assert_eq!(
+            translated["rendered"],
+            "MISMATCHED TYPES\nここで値を移動しました"
+        );
+    }
+
+    #[test]
+    fn test_slice_at_char_boundaries() {
+        let s = "型が不一致です";
+        // "型" は 3 バイトなので、1 や 2 は文字境界ではない
+        assert_eq!(slice_at_char_boundaries(s, 0, 3), "型");
+        assert_eq!(slice_at_char_boundaries(s, 1, 5), "型");
+        assert_eq!(slice_at_char_boundaries(s, 4, 100), "が不一致です");
+        assert_eq!(slice_at_char_boundaries(s, 5, 4), "");
+        assert_eq!(
+            [
+                slice_at_char_boundaries(s, 0, 4),
+                slice_at_char_boundaries(s, 4, 8),
+                slice_at_char_boundaries(s, 8, s.len()),
+            ]
+            .concat(),
+            s
+        );
+    }
+
+    #[test]
+    fn test_replace_rendered_overlapping() {
+        let rendered = "error: value moved\n  |     -- value moved here\n";
+        // 短い方が先に見つかった場合
+        let replaced = vec![
+            ("value moved".to_string(), "値が移動されました".to_string()),
+            (
+                "value moved here".to_string(),
+                "ここで値を移動しました".to_string(),
+            ),
+        ];
+        let expected = "error: 値が移動されました\n  |     -- ここで値を移動しました\n";
+        let index = replaced_index(&replaced);
+        for strategy in [
+            ReplaceStrategy::Linear,
+            ReplaceStrategy::AhoCorasick(&index),
+        ] {
+            assert_eq!(replace_rendered(rendered, &replaced, strategy), expected);
+        }
+    }
+
+    #[test]
+    fn test_replace_rendered_single_pass() {
+        let rendered = "expected type\nfound type\n";
+        // 翻訳後の文字列が、別の翻訳前の文字列を含む場合
+        let replaced = vec![
+            ("expected type".to_string(), "期待される type".to_string()),
+            ("type".to_string(), "型".to_string()),
+        ];
+        let expected = "期待される type\nfound 型\n";
+        let index = replaced_index(&replaced);
+        for strategy in [
+            ReplaceStrategy::Linear,
+            ReplaceStrategy::AhoCorasick(&index),
+        ] {
+            assert_eq!(replace_rendered(rendered, &replaced, strategy), expected);
+        }
+    }
+
+    #[test]
+    fn test_replace_rendered_word_boundary() {
+        // 英数字だけの短い文字列は、識別子の一部を置き換えない
+        let rendered = "error: use\n  |     let used = user_id; // use\n  |     \x1b[1muse\x1b[0m";
+        let replaced = vec![
+            ("use".to_string(), "使用".to_string()),
+            ("let used".to_string(), "使われた".to_string()),
+        ];
+        let expected =
+            "error: 使用\n  |     使われた = user_id; // 使用\n  |     \x1b[1m使用\x1b[0m";
+        let index = replaced_index(&replaced);
+        for strategy in [
+            ReplaceStrategy::Linear,
+            ReplaceStrategy::AhoCorasick(&index),
+        ] {
+            assert_eq!(replace_rendered(rendered, &replaced, strategy), expected);
+        }
+        // ANSI のエスケープシーケンスを含まない場合
+        let rendered = "use `reuse`, use_x use";
+        let index = replaced_index(&replaced);
+        for strategy in [
+            ReplaceStrategy::Linear,
+            ReplaceStrategy::AhoCorasick(&index),
+        ] {
+            assert_eq!(
+                replace_rendered(rendered, &replaced[..1], strategy),
+                "使用 `reuse`, use_x 使用"
+            );
+        }
+    }
+
+    #[test]
+    fn test_replace_rendered_multibyte() {
+        let rendered =
+            "error: 値 mismatched types 型\nnote: `値` は「ここ」で移動\nvalue moved here";
+        let replaced = vec![
+            ("mismatched types".to_string(), "型が不一致です".to_string()),
+            ("`値` は「ここ」で移動".to_string(), "moved".to_string()),
+            (
+                "value moved here".to_string(),
+                "ここで値を移動しました".to_string(),
+            ),
+        ];
+        let expected = "error: 値 型が不一致です 型\nnote: moved\nここで値を移動しました";
+        let index = replaced_index(&replaced);
+        for strategy in [
+            ReplaceStrategy::Linear,
+            ReplaceStrategy::AhoCorasick(&index),
+        ] {
+            assert_eq!(replace_rendered(rendered, &replaced, strategy), expected);
+        }
+    }
+
+    #[test]
+    fn test_translator_translate_for() {
+        let entries: Vec<TranslateEntry> = serde_json::from_str(
+            r#"[
+                { "en": "mismatched types", "ja": "型が不一致です", "ko": "타입이 일치하지 않습니다" },
+                { "en": "value moved here", "ja": "ここで値を移動しました" }
+            ]"#,
+        )
+        .unwrap();
+        let translator = Translator::new(entries);
+
+        assert_eq!(
+            translator.translate_for("ja", "mismatched types"),
+            "型が不一致です"
+        );
+        assert_eq!(
+            translator.translate_for("ko", "mismatched types"),
+            "타입이 일치하지 않습니다"
+        );
+        // その言語の翻訳がなければ翻訳しない
+        assert_eq!(
+            translator.translate_for("ko", "value moved here"),
+            "value moved here"
+        );
+
+        let translator = translator.with_lang("ko");
+        assert_eq!(
+            translator.translate("mismatched types"),
+            "타입이 일치하지 않습니다"
+        );
+    }
+
+    #[test]
+    fn test_translator_with_max_rendered() {
+        let entries = vec![TranslateEntry {
+            en: "mismatched types".to_string(),
+            ja: "型が不一致です".to_string(),
+            ..Default::default()
+        }];
+        let translator = Translator::new(entries).with_max_rendered(64);
+
+        let json = serde_json::json!({
+            "message": "mismatched types",
+            "rendered": "error[E0308]: mismatched types\n",
+        });
+        let translated = translator.translate_json(&json);
+        assert_eq!(translated["rendered"], "error[E0308]: 型が不一致です\n");
+
+        // 上限を超える rendered は英語のまま
+        let rendered = format!("error[E0308]: mismatched types\n{}", "=".repeat(64));
+        let json = serde_json::json!({
+            "message": "mismatched types",
+            "rendered": rendered,
+        });
+        let translated = translator.translate_json(&json);
+        assert_eq!(translated["message"], "型が不一致です");
+        assert_eq!(translated["rendered"], rendered.as_str());
+    }
+
+    #[test]
+    fn test_translator_with_bilingual() {
+        let entries: Vec<TranslateEntry> = serde_json::from_str(
+            r#"[
+                { "en": "mismatched types", "ja": "型が不一致です" },
+                { "en": "expected `{$ty1}`, found `{$ty2}`", "ja": "`{$ty1}`を期待したが、`{$ty2}`が見つかった" }
+            ]"#,
+        )
+        .unwrap();
+        let json = serde_json::json!({
+            "message": "mismatched types",
+            "spans": [{ "label": "expected `i32`, found `&str`" }, { "label": "expected due to this" }],
+            "rendered": "error[E0308]: mismatched types\n  |            ^^^ expected `i32`, found `&str`\n  |            expected due to this\n",
+        });
+
+        let translator = Translator::new(entries.clone()).with_bilingual(Bilingual::Inline);
+        let translated = translator.translate_json(&json);
+        assert_eq!(translated["message"], "型が不一致です (mismatched types)");
+        assert_eq!(
+            translated["spans"][0]["label"],
+            "`i32`を期待したが、`&str`が見つかった (expected `i32`, found `&str`)"
+        );
+        // 翻訳できなかったものはそのまま
+        assert_eq!(translated["spans"][1]["label"], "expected due to this");
+        assert_eq!(
+            translated["rendered"],
+            "error[E0308]: 型が不一致です (mismatched types)\n  |            ^^^ `i32`を期待したが、`&str`が見つかった (expected `i32`, found `&str`)\n  |            expected due to this\n"
+        );
+
+        let translator = Translator::new(entries).with_bilingual(Bilingual::Line);
+        let translated = translator.translate_json(&json);
+        assert_eq!(translated["message"], "mismatched types\n型が不一致です");
+        assert_eq!(
+            translated["rendered"],
+            "error[E0308]: mismatched types\nerror[E0308]: 型が不一致です\n  |            ^^^ expected `i32`, found `&str`\n  |            ^^^ `i32`を期待したが、`&str`が見つかった\n  |            expected due to this\n"
+        );
+
+        // 人が読む形式でも、英語の行の後に翻訳後の行を追加する
+        let text = "error[E0308]: mismatched types\n --> src/main.rs:2:18\n";
+        assert_eq!(
+            translator.translate_human(text),
+            "error[E0308]: mismatched types\nerror[E0308]: 型が不一致です\n --> src/main.rs:2:18\n"
+        );
+    }
+
+    #[test]
+    fn test_translate_json_message_without_rendered() {
+        let entries: Vec<TranslateEntry> = serde_json::from_str(
+            r#"[
+                { "en": "mismatched types", "ja": "型が不一致です" },
+                { "en": "expected due to this", "ja": "これにより期待される" }
+            ]"#,
+        )
+        .unwrap();
+
+        // rendered が null なら null のまま
+        let json = serde_json::json!({
+            "message": "mismatched types",
+            "spans": [{ "label": "expected due to this" }],
+            "rendered": null,
+        });
+        let translated = translate_json_message(&json, &entries);
+        assert_eq!(translated["message"], "型が不一致です");
+        assert_eq!(translated["spans"][0]["label"], "これにより期待される");
+        assert_eq!(translated.get("rendered"), Some(&serde_json::Value::Null));
+
+        // rendered がなければ追加しない
+        let json = serde_json::json!({ "message": "mismatched types" });
+        let translated = translate_json_message(&json, &entries);
+        assert_eq!(translated["message"], "型が不一致です");
+        assert_eq!(translated.get("rendered"), None);
+    }
+
+    #[test]
+    fn test_convert_json_error_format_trailing_newline() {
+        let entries: Vec<TranslateEntry> =
+            serde_json::from_str(r#"[{ "en": "mismatched types", "ja": "型が不一致です" }]"#)
+                .unwrap();
+        let translator = Translator::new(entries);
+        let line = r#"{"$message_type":"diagnostic","message":"mismatched types","spans":[],"children":[],"rendered":null}"#;
+        let translated = r#"{"$message_type":"diagnostic","message":"型が不一致です","spans":[],"children":[],"rendered":null}"#;
+        for (input, expected) in [
+            (line.to_string(), translated.to_string()),
+            (format!("{line}\n"), format!("{translated}\n")),
+            (
+                format!("{line}\n{line}\n"),
+                format!("{translated}\n{translated}\n"),
+            ),
+            // 末尾の空の行も残す
+            (format!("{line}\n\n"), format!("{translated}\n\n")),
+            (format!("\n{line}"), format!("\n{translated}")),
+        ] {
+            let output = convert_json_error_format(input.into_bytes(), &translator);
+            assert_eq!(String::from_utf8(output).unwrap(), expected);
+        }
+    }
+
+    // 診断、診断でない JSON、空の行、改行コードが "\r\n" の行を混ぜた count 行の JSONL
+    fn jsonl_fixture(count: usize) -> Vec<u8> {
+        let mut data = Vec::new();
+        for i in 0..count {
+            let line = match i % 4 {
+                0 => format!(
+                    r#"{{"$message_type":"diagnostic","message":"mismatched types","code":null,"level":"error","spans":[{{"label":"expected `i{i}`, found `&str`"}}],"children":[],"rendered":"error: mismatched types\n"}}"#
+                ),
+                1 => format!(
+                    r#"{{"$message_type":"artifact","artifact":"lib{i}.rlib","emit":"link"}}"#
+                ),
+                2 => String::new(),
+                _ => format!(
+                    "{{\"$message_type\":\"diagnostic\",\"message\":\"unknown {i}\",\"level\":\"warning\",\"spans\":[],\"children\":[],\"rendered\":null}}\r"
+                ),
+            };
+            data.extend_from_slice(line.as_bytes());
+            data.push(b'\n');
+        }
+        data
+    }
+
+    #[test]
+    fn test_convert_json_lines_parallel() {
+        let entries: Vec<TranslateEntry> = serde_json::from_str(
+            r#"[
+                { "en": "mismatched types", "ja": "型が不一致です" },
+                { "en": "expected `{$ty1}`, found `{$ty2}`", "ja": "`{$ty1}`を期待したが、`{$ty2}`が見つかった" }
+            ]"#,
+        )
+        .unwrap();
+        let translator = Translator::new(entries);
+        let data = jsonl_fixture(1000);
+        let sequential = convert_json_lines(data.clone(), &translator, false);
+        let parallel = convert_json_lines(data.clone(), &translator, true);
+        assert_eq!(parallel, sequential);
+        assert_ne!(sequential, data);
+        assert_eq!(
+            convert_json_error_format(data.clone(), &translator),
+            sequential
+        );
+
+        // JSON として読み込めない行があれば、並行して翻訳しても全体をそのまま返す
+        let mut data = data;
+        data.extend_from_slice(b"not a json line\n");
+        data.extend_from_slice(&jsonl_fixture(8));
+        assert_eq!(convert_json_lines(data.clone(), &translator, true), data);
+    }
+
+    #[test]
+    fn test_convert_json_error_format_crlf() {
+        let entries: Vec<TranslateEntry> =
+            serde_json::from_str(r#"[{ "en": "mismatched types", "ja": "型が不一致です" }]"#)
+                .unwrap();
+        let translator = Translator::new(entries);
+        let line = r#"{"$message_type":"diagnostic","message":"mismatched types","spans":[],"children":[],"rendered":null}"#;
+        let translated = r#"{"$message_type":"diagnostic","message":"型が不一致です","spans":[],"children":[],"rendered":null}"#;
+        let other = r#"{"$message_type":"artifact","artifact":"a"}"#;
+        // 改行コードは行ごとにそのまま残す
+        for (input, expected) in [
+            (
+                format!("{line}\r\n{other}\r\n\r\n{line}\r\n"),
+                format!("{translated}\r\n{other}\r\n\r\n{translated}\r\n"),
+            ),
+            (
+                format!("{line}\r\n{line}\n{line}"),
+                format!("{translated}\r\n{translated}\n{translated}"),
+            ),
+        ] {
+            let output = convert_json_error_format(input.into_bytes(), &translator);
+            assert_eq!(String::from_utf8(output).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_convert_json_error_format_invalid_utf8() {
+        let entries: Vec<TranslateEntry> =
+            serde_json::from_str(r#"[{ "en": "mismatched types", "ja": "型が不一致です" }]"#)
+                .unwrap();
+        let translator = Translator::new(entries);
+        let line = r#"{"$message_type":"diagnostic","message":"mismatched types","spans":[],"children":[],"rendered":null}"#;
+        let translated = r#"{"$message_type":"diagnostic","message":"型が不一致です","spans":[],"children":[],"rendered":null}"#;
+        // UTF-8 として読み込めない行だけをそのまま残し、他の行は翻訳する
+        let mut input = format!("{line}\n").into_bytes();
+        input.extend_from_slice(b"{\"message\":\"\xff\xfe\"}\n");
+        input.extend_from_slice(line.as_bytes());
+        let mut expected = format!("{translated}\n").into_bytes();
+        expected.extend_from_slice(b"{\"message\":\"\xff\xfe\"}\n");
+        expected.extend_from_slice(translated.as_bytes());
+        assert_eq!(convert_json_error_format(input, &translator), expected);
+    }
+
+    #[test]
+    fn test_translator_stats() {
+        let entries: Vec<TranslateEntry> = serde_json::from_str(
+            r#"[
+                { "en": "mismatched types", "ja": "型が不一致です" },
+                { "en": "expected due to this", "ja": "これにより期待される" }
+            ]"#,
+        )
+        .unwrap();
+        let jsonl = [
+            r#"{"$message_type":"diagnostic","message":"mismatched types","spans":[{"label":"expected due to this"},{"label":null}],"children":[{"message":"an unknown note","spans":[]}],"rendered":null}"#,
+            r#"{"$message_type":"artifact","artifact":"libfoo.rlib","emit":"link"}"#,
+            r#"{"$message_type":"diagnostic","message":"an unknown error","spans":[{"label":""}],"children":[],"rendered":null}"#,
+        ]
+        .join("\n");
+
+        // 指定しなければ数えない
+        let translator = Translator::new(entries.clone());
+        convert_json_error_format(jsonl.clone().into_bytes(), &translator);
+        assert_eq!(translator.stats(), None);
+
+        let translator = Translator::new(entries).with_stats();
+        convert_json_error_format(jsonl.into_bytes(), &translator);
+        let stats = translator.stats().unwrap();
+        assert_eq!(
+            stats,
+            TranslateStats {
+                translated: 2,
+                untranslated: 2,
+                lines: 2
+            }
+        );
+        assert_eq!(stats.to_string(), "translated=2 untranslated=2 lines=2");
+    }
+
+    #[test]
+    fn test_translator_with_levels() {
+        let entries: Vec<TranslateEntry> = serde_json::from_str(
+            r#"[
+                { "en": "mismatched types", "ja": "型が不一致です" },
+                { "en": "unused variable: `{$name}`", "ja": "変数が使われていません: `{$name}`" }
+            ]"#,
+        )
+        .unwrap();
+        let error = serde_json::json!({
+            "$message_type": "diagnostic",
+            "message": "mismatched types",
+            "level": "error",
+            "spans": [],
+            "children": [],
+            "rendered": "error[E0308]: mismatched types\n",
+        });
+        let warning = serde_json::json!({
+            "$message_type": "diagnostic",
+            "message": "unused variable: `b`",
+            "level": "warning",
+            "spans": [],
+            "children": [],
+            "rendered": "warning: unused variable: `b`\n",
+        });
+
+        // 指定しなければすべての level を翻訳する
+        let translator = Translator::new(entries.clone());
+        assert_eq!(
+            convert_json_error_line(warning.clone(), &translator)["message"],
+            "変数が使われていません: `b`"
+        );
+
+        // エラーのみ翻訳し、警告はそのまま残す
+        let translator = Translator::new(entries).with_levels(vec!["error".to_string()]);
+        assert_eq!(
+            convert_json_error_line(error, &translator)["rendered"],
+            "error[E0308]: 型が不一致です\n"
+        );
+        assert_eq!(
+            convert_json_error_line(warning.clone(), &translator),
+            warning
+        );
+    }
+
+    #[test]
+    fn test_translator_with_primary_spans_only() {
+        let entries: Vec<TranslateEntry> = serde_json::from_str(
+            r#"[
+                { "en": "mismatched types", "ja": "型が不一致です" },
+                { "en": "expected `{$expected}`, found `{$found}`", "ja": "`{$expected}`を期待したが、`{$found}`が見つかった" },
+                { "en": "expected due to this", "ja": "これにより期待される" }
+            ]"#,
+        )
+        .unwrap();
+        let json = serde_json::json!({
+            "$message_type": "diagnostic",
+            "message": "mismatched types",
+            "level": "error",
+            "spans": [
+                { "is_primary": true, "label": "expected `i32`, found `&str`" },
+                { "is_primary": false, "label": "expected due to this" }
+            ],
+            "children": [],
+            "rendered": "error[E0308]: mismatched types\n  |     --- ^^^ expected `i32`, found `&str`\n  |     |\n  |     expected due to this\n",
+        });
+
+        // 指定しなければすべてのラベルを翻訳する
+        let translated = convert_json_error_line(json.clone(), &Translator::new(entries.clone()));
+        assert_eq!(
+            translated["spans"][0]["label"],
+            "`i32`を期待したが、`&str`が見つかった"
+        );
+        assert_eq!(translated["spans"][1]["label"], "これにより期待される");
+        assert_eq!(
+            translated["rendered"],
+            "error[E0308]: 型が不一致です\n  |     --- ^^^ `i32`を期待したが、`&str`が見つかった\n  |     |\n  |     これにより期待される\n"
+        );
+
+        // 主なスパンのラベルのみ翻訳し、他のラベルは rendered でも英語のまま残す
+        let translator = Translator::new(entries).with_primary_spans_only();
+        let translated = convert_json_error_line(json, &translator);
+        assert_eq!(translated["message"], "型が不一致です");
+        assert_eq!(
+            translated["spans"][0]["label"],
+            "`i32`を期待したが、`&str`が見つかった"
+        );
+        assert_eq!(translated["spans"][1]["label"], "expected due to this");
+        assert_eq!(
+            translated["rendered"],
+            "error[E0308]: 型が不一致です\n  |     --- ^^^ `i32`を期待したが、`&str`が見つかった\n  |     |\n  |     expected due to this\n"
+        );
+    }
+
+    #[test]
+    fn test_convert_json_error_line_future_incompat() {
+        let entries: Vec<TranslateEntry> = serde_json::from_str(
+            r#"[{ "en": "unused variable: `{$name}`", "ja": "変数が使われていません: `{$name}`" }]"#,
+        )
+        .unwrap();
+        let translator = Translator::new(entries);
+        let line = r#"{"$message_type":"future_incompat","future_incompat_report":[{"diagnostic":{"$message_type":"diagnostic","message":"unused variable: `b`","code":null,"level":"warning","spans":[],"children":[],"rendered":"warning: unused variable: `b`\n"}},{"other":1}]}"#;
+        let expected = r#"{"$message_type":"future_incompat","future_incompat_report":[{"diagnostic":{"$message_type":"diagnostic","message":"変数が使われていません: `b`","code":null,"level":"warning","spans":[],"children":[],"rendered":"warning: 変数が使われていません: `b`\n"}},{"other":1}]}"#;
+        let json: serde_json::Value = serde_json::from_str(line).unwrap();
+        let converted = convert_json_error_line(json, &translator);
+        assert_eq!(serde_json::to_string(&converted).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_convert_json_error_line_without_message_type() {
+        let entries: Vec<TranslateEntry> =
+            serde_json::from_str(r#"[{ "en": "mismatched types", "ja": "型が不一致です" }]"#)
+                .unwrap();
+        let translator = Translator::new(entries);
+
+        // 古い rustc の出力（$message_type がない）
+        let line = r#"{"message":"mismatched types","code":null,"level":"error","spans":[],"children":[],"rendered":"error: mismatched types\n"}"#;
+        let expected = r#"{"message":"型が不一致です","code":null,"level":"error","spans":[],"children":[],"rendered":"error: 型が不一致です\n"}"#;
+        let json: serde_json::Value = serde_json::from_str(line).unwrap();
+        let converted = convert_json_error_line(json, &translator);
+        assert_eq!(serde_json::to_string(&converted).unwrap(), expected);
+
+        // 診断の形でない JSON や、診断でない $message_type はそのまま
+        for line in [
+            r#"{"message":"mismatched types","level":"error"}"#,
+            r#"{"message":"mismatched types","spans":[]}"#,
+            r#"{"message":"mismatched types","level":1,"spans":[]}"#,
+            r#"{"$message_type":"artifact","message":"mismatched types","level":"error","spans":[]}"#,
+        ] {
+            let json: serde_json::Value = serde_json::from_str(line).unwrap();
+            let converted = convert_json_error_line(json.clone(), &translator);
+            assert_eq!(converted, json, "{line}");
+        }
+    }
+
+    #[test]
+    fn test_json_messages() {
+        let json = serde_json::json!({
+            "message": "borrow of moved value: `s1`",
+            "spans": [{ "label": "value moved here" }, { "label": null }],
+            "children": [
+                { "message": "consider cloning", "spans": [{ "label": "here" }] }
+            ],
+            "rendered": "error[E0382]: borrow of moved value: `s1`\n",
+        });
+        assert_eq!(
+            json_messages(&json),
+            [
+                "borrow of moved value: `s1`",
+                "value moved here",
+                "consider cloning",
+                "here"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_translate_json_message_key_order() {
+        let entries = vec![TranslateEntry {
+            en: "mismatched types".to_string(),
+            ja: "型が不一致です".to_string(),
+            ..Default::default()
+        }];
+        // rustc が出力する順序（アルファベット順ではない）
+        let line = r#"{"$message_type":"diagnostic","message":"mismatched types","code":{"code":"E0308","explanation":null},"level":"error","spans":[{"file_name":"src/main.rs","label":null}],"children":[],"rendered":"error[E0308]: mismatched types\n"}"#;
+        let json: serde_json::Value = serde_json::from_str(line).unwrap();
+        let translated = serde_json::to_string(&translate_json_message(&json, &entries)).unwrap();
+        assert_eq!(
+            translated,
+            line.replace("mismatched types", "型が不一致です")
+        );
+    }
+
+    #[test]
+    fn test_translate_json_message_synthetic_span() {
+        let entries = vec![
+            TranslateEntry {
+                en: "cannot find value `{$name}` in this scope".to_string(),
+                ja: "このスコープに値`{$name}`が見つかりません".to_string(),
+                ..Default::default()
+            },
+            TranslateEntry {
+                en: "not found in this scope".to_string(),
+                ja: "スコープ内で見つかりません".to_string(),
+                ..Default::default()
+            },
+        ];
+        // playground などが組み立てた、"<anon>" のファイル名と長さ 0 のスパンを持つ診断
+        let span = serde_json::json!({
+            "file_name": "<anon>",
+            "byte_start": 0,
+            "byte_end": 0,
+            "line_start": 1,
+            "line_end": 1,
+            "column_start": 1,
+            "column_end": 1,
+            "is_primary": true,
+            "text": [],
+            "label": "not found in this scope",
+        });
+        let json = serde_json::json!({
+            "message": "cannot find value `x` in this scope",
+            "spans": [span, { "file_name": "<anon>", "label": null }],
+            "children": [],
+            "rendered": "error[E0425]: cannot find value `x` in this scope\n --> <anon>:1:1\n  |\n  = not found in this scope\n",
+        });
+        let translated = translate_json_message(&json, &entries);
+
+        assert_eq!(translated["message"], "このスコープに値`x`が見つかりません");
+        let mut expected_span = span.clone();
+        expected_span["label"] = "スコープ内で見つかりません".into();
+        assert_eq!(translated["spans"][0], expected_span);
+        assert_eq!(translated["spans"][1], json["spans"][1]);
+        assert_eq!(
+            translated["rendered"],
+            "error[E0425]: このスコープに値`x`が見つかりません\n --> <anon>:1:1\n  |\n  = スコープ内で見つかりません\n"
+        );
+    }
+
+    #[test]
+    fn test_replace_rendered_source_lines() {
+        // ソースコードの行と位置の行は、翻訳前の文字列を含んでいても置き換えない
+        let rendered = concat!(
+            "error[E0308]: mismatched types\n",
+            " --> src/mismatched types.rs:2:18\n",
+            "  |\n",
+            "2 |     let x: i32 = \"mismatched types\";\n",
+            "  |            ---   ^^^^^^^^^^^^^^^^^^ expected `i32`, found `&str`\n",
+            "  |            |\n",
+            "  |            expected due to this\n",
+            "  |\n",
+            "help: consider removing the quotes\n",
+            "  |\n",
+            "2 -     let x: i32 = \"expected due to this\";\n",
+            "2 +     let x: i32 = expected due to this;\n",
+            "  |\n",
+            "  = note: mismatched types\n",
+        );
+        let replaced = vec![
+            ("mismatched types".to_string(), "型が不一致です".to_string()),
+            (
+                "expected `i32`, found `&str`".to_string(),
+                "`i32`を期待したが、`&str`が見つかった".to_string(),
+            ),
+            (
+                "expected due to this".to_string(),
+                "このため期待した".to_string(),
+            ),
+        ];
+        let expected = concat!(
+            "error[E0308]: 型が不一致です\n",
+            " --> src/mismatched types.rs:2:18\n",
+            "  |\n",
+            "2 |     let x: i32 = \"mismatched types\";\n",
+            "  |            ---   ^^^^^^^^^^^^^^^^^^ `i32`を期待したが、`&str`が見つかった\n",
+            "  |            |\n",
+            "  |            このため期待した\n",
+            "  |\n",
+            "help: consider removing the quotes\n",
+            "  |\n",
+            "2 -     let x: i32 = \"expected due to this\";\n",
+            "2 +     let x: i32 = expected due to this;\n",
+            "  |\n",
+            "  = note: 型が不一致です\n",
+        );
+        let index = replaced_index(&replaced);
+        for strategy in [
+            ReplaceStrategy::Linear,
+            ReplaceStrategy::AhoCorasick(&index),
+        ] {
+            assert_eq!(replace_rendered(rendered, &replaced, strategy), expected);
+        }
+    }
+
+    #[test]
+    fn test_replace_rendered_ansi() {
+        let rendered = "\x1b[0m\x1b[1m\x1b[38;5;9merror[E0308]\x1b[0m\x1b[0m\x1b[1m: mismatched types\x1b[0m\n\x1b[0m  \x1b[0m\x1b[1m\x1b[38;5;12m|\x1b[0m \x1b[0m\x1b[1m\x1b[38;5;9m^^^\x1b[0m \x1b[0m\x1b[1m\x1b[38;5;9mvalue \x1b[4mmoved\x1b[24m here\x1b[0m\n";
+        let replaced = vec![
+            ("mismatched types".to_string(), "型が不一致です".to_string()),
+            (
+                "value moved here".to_string(),
+                "ここで値を移動しました".to_string(),
+            ),
+        ];
+        let expected = "\x1b[0m\x1b[1m\x1b[38;5;9merror[E0308]\x1b[0m\x1b[0m\x1b[1m: 型が不一致です\x1b[0m\n\x1b[0m  \x1b[0m\x1b[1m\x1b[38;5;12m|\x1b[0m \x1b[0m\x1b[1m\x1b[38;5;9m^^^\x1b[0m \x1b[0m\x1b[1m\x1b[38;5;9mここで値を移動しました\x1b[4m\x1b[24m\x1b[0m\n";
+        let index = replaced_index(&replaced);
+        for strategy in [
+            ReplaceStrategy::Linear,
+            ReplaceStrategy::AhoCorasick(&index),
+        ] {
+            assert_eq!(replace_rendered(rendered, &replaced, strategy), expected);
+        }
+    }
+}
@@ -1,402 +1,2968 @@
 use std::env;
-use std::io::{self, Read, Write};
+use std::io::{self, BufRead, Read, Write};
+use std::path::Path;
 use std::process::{Command, Stdio, exit};
 
-/// 翻訳データの型定義
-#[derive(Debug, Clone, serde::Deserialize)]
-pub struct TranslateEntry {
-    pub en: String,
-    pub ja: String,
+use rustc_ja_wrapper::config::{Config, find_config_file, is_truthy};
+use rustc_ja_wrapper::coverage::{format_coverage, measure_coverage, untranslated_stubs};
+use rustc_ja_wrapper::file::{
+    CheckIssue, check_translations, dedup_translations, filter_by_category, filter_by_version,
+    has_version_range, load_translations, merge_translations, reverse_entries,
+};
+use rustc_ja_wrapper::{Bilingual, DEFAULT_LANG, TranslateEntry, Translator};
+
+/// 組み込みの翻訳データ（日本語）
+/// feature "bundled-ja" が無効なら空で、翻訳データは設定の translate（環境変数 RUSTC_JA_TRANSLATE）で指定する
+#[cfg(feature = "bundled-ja")]
+const BUNDLED_TRANSLATIONS: &str = include_str!("../assets/translate.ja.json");
+#[cfg(not(feature = "bundled-ja"))]
+const BUNDLED_TRANSLATIONS: &str = "[]";
+
+/// 組み込みの翻訳データの言語と内容（assets/translate.<言語>.json）
+const BUNDLED_LOCALES: &[(&str, &str)] = &[(DEFAULT_LANG, BUNDLED_TRANSLATIONS)];
+
+/// ラッパーの設定
+/// 環境変数 RUSTC_JA_CONFIG のファイル、なければカレントディレクトリの rustc-ja-wrapper.toml を読み込み、
+/// 環境変数で上書きする
+static CONFIG: once_cell::sync::Lazy<Config> = once_cell::sync::Lazy::new(|| {
+    let path = find_config_file(env::var_os("RUSTC_JA_CONFIG"), Path::new("."));
+    let config = match path.map(|path| {
+        std::fs::read_to_string(&path)
+            .map_err(|e| format!("failed to read {}: {}", path.display(), e))
+            .and_then(|toml| Config::from_toml(&toml))
+            .map_err(|e| format!("{} (using the default settings)", e))
+    }) {
+        Some(Ok(config)) => config,
+        Some(Err(warning)) => {
+            eprintln!("rustc-ja-wrapper: {}", warning);
+            Config::default()
+        }
+        None => Config::default(),
+    };
+    config.with_env(|name| env::var_os(name))
+});
+
+/// 翻訳先の言語
+/// 設定の lang（環境変数 RUSTC_JA_LANG）、なければ LC_ALL か LANG（"ja_JP.UTF-8" なら "ja"）で選ぶ
+/// 組み込みの翻訳データがない言語であれば日本語を使う
+static LOCALE: once_cell::sync::Lazy<String> = once_cell::sync::Lazy::new(|| {
+    let available: Vec<&str> = BUNDLED_LOCALES.iter().map(|(lang, _)| *lang).collect();
+    let (locale, warning) = select_locale(
+        CONFIG.lang.as_deref(),
+        env::var("LC_ALL")
+            .ok()
+            .or_else(|| env::var("LANG").ok())
+            .as_deref(),
+        &available,
+    );
+    if let Some(warning) = warning {
+        eprintln!("rustc-ja-wrapper: {}", warning);
+    }
+    locale
+});
+
+// 翻訳先の言語を選ぶ（組み込みの翻訳データがない言語の場合は日本語にする）
+// explicit（RUSTC_JA_LANG）で指定した言語がない場合のみ警告のメッセージも返す
+// ロケール（LC_ALL や LANG）はこのラッパー向けの指定とは限らないので、なくても警告しない
+fn select_locale(
+    explicit: Option<&str>,
+    locale: Option<&str>,
+    available: &[&str],
+) -> (String, Option<String>) {
+    let lang_of = |locale: &str| {
+        let lang = locale.split(['_', '.', '@']).next().unwrap_or("");
+        lang.to_ascii_lowercase()
+    };
+    if let Some(explicit) = explicit.filter(|l| !l.is_empty()) {
+        let lang = lang_of(explicit);
+        if available.contains(&lang.as_str()) {
+            return (lang, None);
+        }
+        let warning = format!(
+            "no bundled translations for {:?} (using {:?})",
+            explicit, DEFAULT_LANG
+        );
+        return (DEFAULT_LANG.to_string(), Some(warning));
+    }
+    match locale.map(lang_of) {
+        Some(lang) if available.contains(&lang.as_str()) => (lang, None),
+        _ => (DEFAULT_LANG.to_string(), None),
+    }
+}
+
+// 言語の組み込みの翻訳データを返す（なければ日本語）
+fn bundled_translations(lang: &str) -> &'static str {
+    BUNDLED_LOCALES
+        .iter()
+        .find(|(l, _)| *l == lang)
+        .map_or(BUNDLED_TRANSLATIONS, |(_, json)| json)
 }
 
 /// JSONの翻訳データ（可変部分は "{$name}" や "{$ty}" などのプレースホルダを含む）
-static TRANSLATE_LIST: once_cell::sync::Lazy<Vec<TranslateEntry>> =
-    once_cell::sync::Lazy::new(|| {
-        let json_str = include_str!("../assets/translate.json");
-        // 英語文字列の長いものを先、短いものを後に並べ替える
-        let mut entries: Vec<TranslateEntry> = serde_json::from_str(json_str).unwrap_or_default();
-        entries.sort_by(|a, b| b.en.len().cmp(&a.en.len()));
+/// 設定の translate（環境変数 RUSTC_JA_TRANSLATE）があれば、そのファイルを組み込みの翻訳データに重ねて読み込む
+/// ディレクトリであれば、その中の "*.json" をファイル名の順に読み込む
+/// 環境変数 RUSTC_JA_CATEGORIES（カンマ区切り）が設定されていれば、そのカテゴリのみ読み込む
+static TRANSLATE_LIST: once_cell::sync::Lazy<Vec<TranslateEntry>> = once_cell::sync::Lazy::new(
+    || {
+        let path = CONFIG.translate.as_deref();
+        let categories = env::var("RUSTC_JA_CATEGORIES").ok().map(|v| split_list(&v));
+        if !cfg!(feature = "bundled-ja") && path.is_none() {
+            eprintln!(
+                "rustc-ja-wrapper: no translations: built without the bundled translations (feature \"bundled-ja\"); set RUSTC_JA_TRANSLATE to a translation file"
+            );
+        }
+        let (entries, warnings) =
+            load_translate_list(bundled_translations(&LOCALE), path, categories.as_deref());
+        for warning in warnings {
+            eprintln!("rustc-ja-wrapper: {}", warning);
+        }
         entries
-    });
+    },
+);
 
-fn main() {
-    let mut args = env::args_os().skip(1);
-    let cmd: std::ffi::OsString = match args.next() {
-        Some(c) => c,
-        None => {
-            eprintln!("Usage: rustc-ja-wrapper <command> [args...]");
-            exit(1);
-        }
-    };
+/// ラッパーが実行するコマンド（run で設定する）
+static WRAPPED_COMMAND: once_cell::sync::OnceCell<std::ffi::OsString> =
+    once_cell::sync::OnceCell::new();
 
-    let args_for_cmd: Vec<std::ffi::OsString> = args.collect();
+/// 翻訳データの min_version と max_version に使う rustc のバージョン
+/// 環境変数 RUSTC_JA_RUSTC_VERSION があればその値、なければ rustc_command() の "--version" の出力
+static RUSTC_VERSION: once_cell::sync::Lazy<Option<String>> = once_cell::sync::Lazy::new(|| {
+    if let Some(version) = env::var("RUSTC_JA_RUSTC_VERSION")
+        .ok()
+        .filter(|v| !v.is_empty())
+    {
+        return Some(version);
+    }
+    probe_rustc_version(&rustc_command())
+});
 
-    let child = Command::new(&cmd)
-        .args(&args_for_cmd)
-        // .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .spawn();
+// バージョンを調べる rustc
+// 実行するコマンドが rustc ならそれを、そうでなければ環境変数 RUSTC（なければ "rustc"）を使う
+fn rustc_command() -> std::ffi::OsString {
+    WRAPPED_COMMAND
+        .get()
+        .filter(|cmd| Path::new(cmd).file_stem().is_some_and(|s| s == "rustc"))
+        .cloned()
+        .or_else(|| env::var_os("RUSTC"))
+        .unwrap_or_else(|| "rustc".into())
+}
 
-    let mut child = match child {
-        Ok(c) => c,
-        Err(e) => {
-            eprintln!("Failed to spawn command: {}", e);
-            exit(1);
+/// 翻訳データを使う翻訳器
+/// 環境変数 RUSTC_JA_MAX_RENDERED（バイト数）が設定されていれば、それを超える rendered は翻訳しない
+/// 環境変数 RUSTC_JA_MISSING_LOG が設定されていれば、翻訳できなかったメッセージをそのファイルに追記する
+/// 環境変数 RUSTC_JA_EMBED_ORIGINAL が設定されていれば、翻訳前の診断を "original" キーに埋め込む
+/// 環境変数 RUSTC_JA_LEVELS（カンマ区切り）が設定されていれば、その level の JSON の診断のみ翻訳する
+/// 環境変数 RUSTC_JA_PRIMARY_SPANS_ONLY が有効な値なら、JSON の診断の主なスパンのラベルのみ翻訳する
+/// 環境変数 RUSTC_JA_BILINGUAL が有効な値なら、翻訳前の英語と翻訳後の文字列を併記する
+/// デバッグ用のログを書き込む場合は、翻訳の件数も数える
+static TRANSLATOR: once_cell::sync::Lazy<Translator> = once_cell::sync::Lazy::new(|| {
+    let mut translator = Translator::new(TRANSLATE_LIST.clone()).with_lang(&LOCALE);
+    if let Some(max_rendered) = env::var("RUSTC_JA_MAX_RENDERED")
+        .ok()
+        .and_then(|v| v.parse().ok())
+    {
+        translator = translator.with_max_rendered(max_rendered);
+    }
+    if env::var_os("RUSTC_JA_EMBED_ORIGINAL").is_some() {
+        translator = translator.with_embed_original();
+    }
+    if let Some(path) = env::var_os("RUSTC_JA_MISSING_LOG") {
+        let log = MissingLog::new(path.into());
+        translator = translator.with_missing(Box::new(move |m| log.record(m)));
+    }
+    if let Some(levels) = env::var("RUSTC_JA_LEVELS").ok().map(|v| split_list(&v)) {
+        translator = translator.with_levels(levels);
+    }
+    if env::var_os("RUSTC_JA_PRIMARY_SPANS_ONLY").is_some_and(|v| is_truthy(&v.to_string_lossy())) {
+        translator = translator.with_primary_spans_only();
+    }
+    if let Some(bilingual) = env::var("RUSTC_JA_BILINGUAL")
+        .ok()
+        .and_then(|v| bilingual_style(&v))
+    {
+        translator = translator.with_bilingual(bilingual);
+    }
+    if DEBUG_LOG.is_some() {
+        translator = translator.with_stats();
+    }
+    translator
+});
+
+// カンマ区切りの値を分ける（前後の空白と空の要素は除く）
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(|c| c.trim().to_string())
+        .filter(|c| !c.is_empty())
+        .collect()
+}
+
+// RUSTC_JA_BILINGUAL の値から併記の形式を決める（"line" なら行の追加、それ以外の有効な値なら括弧書き）
+fn bilingual_style(value: &str) -> Option<Bilingual> {
+    if value.trim().eq_ignore_ascii_case("line") {
+        Some(Bilingual::Line)
+    } else {
+        is_truthy(value).then_some(Bilingual::Inline)
+    }
+}
+
+/// 翻訳できなかったメッセージの記録先
+struct MissingLog {
+    path: std::path::PathBuf,
+    /// 記録済みのメッセージ（同じメッセージは一度だけ記録する）
+    seen: std::sync::Mutex<std::collections::HashSet<String>>,
+}
+
+impl MissingLog {
+    fn new(path: std::path::PathBuf) -> Self {
+        MissingLog {
+            path,
+            seen: Default::default(),
         }
-    };
+    }
 
-    let mut stderr_buf = Vec::new();
+    // まだ記録していないメッセージであれば、ファイルに追記する
+    fn record(&self, message: &str) {
+        let Ok(mut seen) = self.seen.lock() else {
+            return;
+        };
+        if seen.insert(message.to_string()) {
+            append_debug_log_to(Some(&self.path), message);
+        }
+    }
+}
 
-    if let Some(mut err) = child.stderr.take() {
-        if let Err(e) = err.read_to_end(&mut stderr_buf) {
-            eprintln!("Failed to read stderr: {}", e);
-            exit(1);
+/// 翻訳データを読み込む
+/// path が指定されていれば、そのファイル（JSON、`.po` か `.csv`）を組み込みの翻訳データ（bundled_json）に重ねる
+/// ファイルを読み込めない場合は組み込みの翻訳データだけを使い、警告のメッセージも返す
+/// CSV の正しくない行や英語文字列が空のエントリは読み飛ばし、それぞれ警告する
+fn load_translate_list(
+    bundled_json: &str,
+    path: Option<&Path>,
+    categories: Option<&[String]>,
+) -> (Vec<TranslateEntry>, Vec<String>) {
+    let (bundled_entries, mut warnings) = load_translations(bundled_json, categories);
+    for warning in &mut warnings {
+        *warning = format!("bundled translations: {}", warning);
+    }
+    let bundled_entries = dedup_logged(
+        filter_loaded(bundled_entries, categories),
+        "bundled translations",
+    );
+    let Some(path) = path else {
+        return (bundled_entries, warnings);
+    };
+    let entries = if path.is_dir() {
+        // ディレクトリの場合は、その中の "*.json" をファイル名の順に読み込んでつなげる
+        // 同じ英語文字列（と文脈）のエントリは、後のファイルのものを使う
+        let files = match json_files_in(path) {
+            Ok(files) => files,
+            Err(e) => {
+                let warning = format!(
+                    "failed to read {}: {} (using the bundled translations)",
+                    path.display(),
+                    e
+                );
+                warnings.push(warning);
+                return (bundled_entries, warnings);
+            }
+        };
+        let mut entries = Vec::new();
+        for file in files {
+            match read_translation_file(&file, categories) {
+                Ok((file_entries, file_warnings)) => {
+                    entries.extend(file_entries);
+                    warnings.extend(file_warnings);
+                }
+                Err(e) => warnings.push(format!(
+                    "failed to read {}: {} (skipped)",
+                    file.display(),
+                    e
+                )),
+            }
+        }
+        entries
+    } else {
+        match read_translation_file(path, categories) {
+            Ok((entries, file_warnings)) => {
+                warnings.extend(file_warnings);
+                entries
+            }
+            Err(e) => {
+                let warning = format!(
+                    "failed to read {}: {} (using the bundled translations)",
+                    path.display(),
+                    e
+                );
+                warnings.push(warning);
+                return (bundled_entries, warnings);
+            }
         }
+    };
+    let entries = dedup_logged(
+        filter_loaded(entries, categories),
+        &path.display().to_string(),
+    );
+    (merge_translations(bundled_entries, entries), warnings)
+}
+
+// 読み込んだエントリから、使わないものを除く
+// 環境変数 RUSTC_JA_CATEGORIES_STRICT が有効なら、categories の指定がある場合にカテゴリのないものも除く
+fn filter_loaded(
+    entries: Vec<TranslateEntry>,
+    categories: Option<&[String]>,
+) -> Vec<TranslateEntry> {
+    let strict =
+        env::var_os("RUSTC_JA_CATEGORIES_STRICT").is_some_and(|v| is_truthy(&v.to_string_lossy()));
+    filter_by_rustc_version(filter_by_category(entries, categories, strict))
+}
+
+// rustc のバージョンの範囲を指定したエントリがあれば、範囲に入らないものを除く
+// バージョンを調べるのは、範囲を指定したエントリがある場合のみ
+fn filter_by_rustc_version(entries: Vec<TranslateEntry>) -> Vec<TranslateEntry> {
+    if !has_version_range(&entries) {
+        return entries;
     }
+    filter_by_version(entries, RUSTC_VERSION.as_deref())
+}
 
-    if let Ok(s) = std::str::from_utf8(&stderr_buf) {
-        append_debug_log("RESPONSE");
-        append_debug_log(s);
+// rustc の "--version" の出力を返す（実行できなければ None）
+// コマンドごとに起動しないよう、結果を一時ディレクトリにキャッシュする
+fn probe_rustc_version(rustc: &std::ffi::OsStr) -> Option<String> {
+    let cache = version_cache_path(rustc);
+    if let Some(version) = cache
+        .as_ref()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .filter(|v| !v.is_empty())
+    {
+        return Some(version);
     }
+    let output = Command::new(rustc)
+        .arg("--version")
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    let version = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if let Some(path) = cache {
+        // キャッシュに書き込めなくても、次回また調べるだけなので無視する
+        let _ = std::fs::write(path, &version);
+    }
+    Some(version)
+}
 
-    // "--error-format=json" が含まれているか判定
-    let error_format_json = std::ffi::OsStr::new("--error-format=json");
-    let has_json_error_format = args_for_cmd.iter().any(|a| a == error_format_json);
+// rustc のバージョンのキャッシュファイル
+// 実行ファイルのパスと更新日時と大きさ、rustup のツールチェインが変われば別のファイルにする
+fn version_cache_path(rustc: &std::ffi::OsStr) -> Option<std::path::PathBuf> {
+    use std::hash::{Hash, Hasher};
+
+    let path = find_executable(rustc)?;
+    let metadata = std::fs::metadata(&path).ok()?;
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    path.hash(&mut hasher);
+    metadata.modified().ok()?.hash(&mut hasher);
+    metadata.len().hash(&mut hasher);
+    env::var_os("RUSTUP_TOOLCHAIN").hash(&mut hasher);
+    Some(env::temp_dir().join(format!(
+        "rustc-ja-wrapper-rustc-version-{:016x}",
+        hasher.finish()
+    )))
+}
 
-    // 標準エラー出力変換処理
-    if has_json_error_format {
-        stderr_buf = convert_json_error_format(stderr_buf);
+// コマンドの実行ファイルを探す（パスを含まない名前なら環境変数 PATH から探す）
+fn find_executable(cmd: &std::ffi::OsStr) -> Option<std::path::PathBuf> {
+    let path = Path::new(cmd);
+    if path.components().count() > 1 {
+        return path.is_file().then(|| path.to_path_buf());
     }
+    env::split_paths(&env::var_os("PATH")?)
+        .flat_map(|dir| {
+            let file = dir.join(cmd);
+            let mut exe = file.clone().into_os_string();
+            exe.push(env::consts::EXE_SUFFIX);
+            [std::path::PathBuf::from(exe), file]
+        })
+        .find(|file| file.is_file())
+}
 
-    // 標準エラー出力に書き出す
-    if let Err(e) = io::stderr().write_all(&stderr_buf) {
-        eprintln!("Failed to write to stderr: {}", e);
-        exit(1);
+// ディレクトリの中の "*.json" のファイルを、ファイル名の順に返す
+fn json_files_in(dir: &Path) -> io::Result<Vec<std::path::PathBuf>> {
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_file() && path.extension().is_some_and(|ext| ext == "json") {
+            files.push(path);
+        }
     }
-    io::stderr().flush().unwrap_or_else(|e| {
-        eprintln!("Failed to flush stderr: {}", e);
-        exit(1);
-    });
+    files.sort_by(|a, b| a.file_name().cmp(&b.file_name()));
+    Ok(files)
+}
 
-    let status = match child.wait() {
-        Ok(s) => s,
+// 翻訳ファイルを 1 つ読み込む（警告にはファイルのパスを付ける）
+fn read_translation_file(
+    path: &Path,
+    categories: Option<&[String]>,
+) -> io::Result<(Vec<TranslateEntry>, Vec<String>)> {
+    let text = std::fs::read_to_string(path)?;
+    let (entries, warnings) = if path.extension().is_some_and(|ext| ext == "po") {
+        // ".po" のファイルは gettext の形式として読み込む（プレースホルダは "{$name}" のまま）
+        (rustc_ja_wrapper::po::from_po(&text), Vec::new())
+    } else if path.extension().is_some_and(|ext| ext == "csv") {
+        // ".csv" のファイルは "en,ja" の 2 列の表として読み込む
+        rustc_ja_wrapper::csv::from_csv(&text)
+    } else {
+        load_translations(&text, categories)
+    };
+    let warnings = warnings
+        .into_iter()
+        .map(|w| format!("{}: {}", path.display(), w))
+        .collect();
+    Ok((entries, warnings))
+}
+
+// 同じ英語文字列（と文脈）のエントリは最後のものだけ残し、除いたものをデバッグ用のログに書き込む
+fn dedup_logged(entries: Vec<TranslateEntry>, source: &str) -> Vec<TranslateEntry> {
+    let (entries, dropped) = dedup_translations(entries);
+    for entry in dropped {
+        append_debug_log(&format!("DUPLICATE {}: {:?}", source, entry.en));
+    }
+    entries
+}
+
+fn main() {
+    use std::io::IsTerminal;
+    let stdin_is_terminal = io::stdin().is_terminal();
+    match run(env::args_os().skip(1).collect(), stdin_is_terminal) {
+        Ok(code) => exit(code),
         Err(e) => {
-            eprintln!("Failed to wait for child: {}", e);
+            eprintln!("{}", e);
             exit(1);
         }
-    };
+    }
+}
 
-    exit(status.code().unwrap_or(1));
+/// ラッパーの実行に失敗した理由
+#[derive(Debug)]
+enum WrapperError {
+    /// コマンドを起動できなかった
+    Spawn(io::Error),
+    /// コマンドの出力を中継できなかった
+    Relay(io::Error),
+    /// コマンドの終了を待てなかった
+    Wait(io::Error),
 }
 
-// 標準エラーの JSONL を変換する
-fn convert_json_error_format(data: Vec<u8>) -> Vec<u8> {
-    // UTF-8として解釈できなければそのまま返す
-    let s = match std::str::from_utf8(&data) {
-        Ok(s) => s,
-        Err(_) => return data,
+impl std::fmt::Display for WrapperError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WrapperError::Spawn(e) => write!(f, "Failed to spawn command: {}", e),
+            WrapperError::Relay(e) => write!(f, "Failed to relay output: {}", e),
+            WrapperError::Wait(e) => write!(f, "Failed to wait for child: {}", e),
+        }
+    }
+}
+
+// ラッパーを実行し、終了コードを返す（args はラッパー自身の名前を除いた引数）
+// コマンドが指定されなかった場合、stdin_is_terminal なら使い方を表示し、そうでなければ標準入力を翻訳する
+fn run(args: Vec<std::ffi::OsString>, stdin_is_terminal: bool) -> Result<i32, WrapperError> {
+    let mut args = args.into_iter();
+    let Some(cmd) = args.next() else {
+        let stdin = io::stdin();
+        return Ok(run_without_command(
+            stdin.lock(),
+            stdin_is_terminal,
+            &mut io::stdout().lock(),
+        ));
     };
 
-    let mut out_lines = Vec::new();
-    for line in s.lines() {
-        // 各行をJSONとしてパース
-        match serde_json::from_str::<serde_json::Value>(line) {
-            Ok(json) => {
-                // 変換処理関数を呼び出す
-                let converted = convert_json_error_line(json);
-
-                // 変換後をJSON文字列化
-                match serde_json::to_string(&converted) {
-                    Ok(s) => out_lines.push(s),
-                    Err(_) => return data, // 失敗したら何もしない
-                };
+    // ラッパー自身のバージョンと使い方（最初の引数の場合のみ）
+    if let Some(info) = cmd.to_str().and_then(wrapper_info) {
+        print!("{}", info);
+        return Ok(0);
+    }
+
+    // ラッパー自身のオプション
+    match cmd.to_str() {
+        Some("--wrapper-export-po") => return Ok(export_po(args.next())),
+        Some("--wrapper-import-po") => return Ok(import_po(args.next(), args.next())),
+        Some("--wrapper-selfcheck") => return Ok(selfcheck()),
+        Some("--check") => return Ok(check(args.next())),
+        Some("--doctor") => {
+            // "--doctor /path/to/rustc" なら、そのコマンドを実行する場合と同じ rustc を調べる
+            if let Some(rustc) = args.next() {
+                let _ = WRAPPED_COMMAND.set(rustc);
             }
-            Err(_) => return data, // パース失敗時は何もしない
+            return Ok(doctor());
+        }
+        Some("--dump-translations") => {
+            return Ok(dump_translations(
+                args.next().is_some_and(|a| a == "--json"),
+            ));
         }
+        Some("--reverse") => return Ok(reverse()),
+        Some("--filter") => return Ok(filter(args.next().is_some_and(|a| a == "--dry-run"))),
+        Some("--coverage") => return Ok(coverage(args.next())),
+        Some("--emit-untranslated-json") => {
+            return Ok(emit_untranslated_json(args.next(), args.next()));
+        }
+        Some(FAKE_COMPILER_ARG) => return Ok(fake_compiler()),
+        _ => {}
+    }
+
+    let args_for_cmd: Vec<std::ffi::OsString> = args.collect();
+    let _ = WRAPPED_COMMAND.set(cmd.clone());
+
+    // RUSTC_WRAPPER として "rustc-ja-wrapper /path/to/rustc -vV" のように呼ばれる問い合わせは、
+    // 診断を出さないので、標準出力も標準エラー出力もそのまま引き継いで実行する
+    if is_probe(&args_for_cmd) {
+        return run_passthrough(&cmd, &args_for_cmd);
+    }
+
+    // 設定の disable（環境変数 RUSTC_JA_DISABLE）が有効なら、標準出力も標準エラー出力も変換しない
+    let disabled = CONFIG.disable;
+
+    // 環境変数 RUSTC_JA_DRY_RUN が有効なら、翻訳はするが出力は変換せずに書き出し、
+    // 翻訳の前後の組をデバッグ用のログ（未設定なら標準出力）に書き出す
+    let dry_run = !disabled
+        && env::var_os("RUSTC_JA_DRY_RUN").is_some_and(|v| is_truthy(&v.to_string_lossy()));
+
+    // 環境変数 RUSTC_JA_TRANSLATE_STDOUT が設定されていれば、標準出力も変換する（dry run の場合は変換しない）
+    // "--print" の出力は診断ではないが、問い合わせとしてそのまま実行済みなのでここには来ない
+    let translate_stdout =
+        !disabled && !dry_run && env::var_os("RUSTC_JA_TRANSLATE_STDOUT").is_some();
+    // 設定の human（環境変数 RUSTC_JA_HUMAN）が無効なら、JSON 以外の形式はそのまま書き出す
+    let stderr_format = (!disabled)
+        .then(|| detect_error_format(&args_for_cmd))
+        .filter(|_| CONFIG.human || detects_json_format(&args_for_cmd));
+
+    // 環境変数 RUSTC_JA_TIMEOUT（秒）が設定されていれば、その時間を過ぎたコマンドを止める
+    let timeout =
+        env::var_os("RUSTC_JA_TIMEOUT").and_then(|v| timeout_duration(&v.to_string_lossy()));
+
+    // どちらの出力も変換せず、制限時間もない場合は、中継せずにそのまま実行する
+    if stderr_format.is_none() && !translate_stdout && timeout.is_none() {
+        return run_passthrough(&cmd, &args_for_cmd);
+    }
+
+    let mut child = Command::new(&cmd)
+        .args(&args_for_cmd)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(WrapperError::Spawn)?;
+
+    let timed_out = relay_output(
+        &mut child,
+        translate_stdout,
+        stderr_format,
+        dry_run.then(|| dry_run_report(io::stdout())),
+        timeout,
+        io::stdout(),
+        io::stderr(),
+    )
+    .map_err(WrapperError::Relay)?;
+
+    let status = child.wait().map_err(WrapperError::Wait)?;
+
+    append_stats_log();
+    if timed_out {
+        eprintln!(
+            "rustc-ja-wrapper: killed the command after RUSTC_JA_TIMEOUT ({:?})",
+            timeout.unwrap_or_default()
+        );
+        return Ok(TIMEOUT_EXIT_CODE);
     }
-    // 改行区切りで連結してバイト列に戻す
-    out_lines.join("\n").into_bytes()
+    Ok(exit_code(&status))
+}
+
+/// RUSTC_JA_TIMEOUT で止めた場合の終了コード（GNU の timeout コマンドと同じ）
+const TIMEOUT_EXIT_CODE: i32 = 124;
+
+/// 制限時間を過ぎたかを確かめる間隔
+const TIMEOUT_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(20);
+
+/// 制限時間を過ぎてから、パイプに残った出力を読み込むのを待つ時間
+const TIMEOUT_DRAIN_GRACE: std::time::Duration = std::time::Duration::from_millis(100);
+
+// 読み込んだ塊（空なら終わり）
+type PipeChunk = io::Result<Vec<u8>>;
+
+// 子プロセスのパイプを別のスレッドで読み込み、読み込んだ分を順に返す Read
+// 孫プロセスがパイプを開いたままでも、close_pipe で終わりを送れば、それまでに読み込んだ分で打ち切れる
+struct PipeReader {
+    receiver: std::sync::mpsc::Receiver<PipeChunk>,
+    chunk: Vec<u8>,
+    pos: usize,
+    done: bool,
 }
 
-// コンパイルエラーのJSONであれば、各種フィールドを日本語に翻訳する
-fn convert_json_error_line(json: serde_json::Value) -> serde_json::Value {
-    if let serde_json::Value::Object(ref obj) = json {
-        if let Some(mt) = obj.get("$message_type") {
-            if mt == "diagnostic" {
-                return translate_json_message(&json, &TRANSLATE_LIST);
+// パイプを読み込むスレッドを起動し、PipeReader と、終わりを送るための Sender を返す
+// スレッドは待ち合わせないので、孫プロセスがパイプを閉じるまで残る
+fn pipe_reader(
+    mut pipe: impl Read + Send + 'static,
+) -> (PipeReader, std::sync::mpsc::Sender<PipeChunk>) {
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let pipe_sender = sender.clone();
+    std::thread::spawn(move || {
+        let mut buf = vec![0; 8192];
+        loop {
+            let chunk = match pipe.read(&mut buf) {
+                Ok(n) => Ok(buf[..n].to_vec()),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => Err(e),
+            };
+            let last = !matches!(&chunk, Ok(c) if !c.is_empty());
+            if pipe_sender.send(chunk).is_err() || last {
+                return;
             }
         }
-    }
-    json
-}
-
-// JSON内のメッセージを日本語に翻訳する
-// 翻訳対象は以下のフィールド（値が null の場合には何もしない）
-// - "message"
-// - "spans[].label"
-// - "children[].message"
-// - "children[].spans[].label"
-// また "rendered" フィールドの中身について、各メッセージの翻訳前と同じ文字列が含まれている場合には、翻訳後文字列に置き換える
-// JSONフォーマットの形式は以下を参照
-// - <https://doc.rust-lang.org/rustc/json.html>
-pub fn translate_json_message(
-    json: &serde_json::Value,
-    translations: &[TranslateEntry],
-) -> serde_json::Value {
-    let mut new_json = json.clone();
-    let mut replaced = Vec::new();
-
-    // message
-    if let Some(message) = json.get("message").and_then(|m| m.as_str()) {
-        let translated = translate_message(message, translations);
-        if translated != message {
-            new_json["message"] = serde_json::Value::String(translated.clone());
-            replaced.push((message.to_string(), translated));
-        }
-    }
-
-    // spans[].label
-    if let Some(spans) = json.get("spans").and_then(|s| s.as_array()) {
-        let mut new_spans = spans.clone();
-        for (i, span) in spans.iter().enumerate() {
-            if let Some(label) = span.get("label").and_then(|l| l.as_str()) {
-                let translated = translate_message(label, translations);
-                if translated != label {
-                    let mut new_span = span.clone();
-                    new_span["label"] = serde_json::Value::String(translated.clone());
-                    new_spans[i] = new_span;
-                    replaced.push((label.to_string(), translated));
+    });
+    let reader = PipeReader {
+        receiver,
+        chunk: Vec::new(),
+        pos: 0,
+        done: false,
+    };
+    (reader, sender)
+}
+
+// PipeReader の読み込みを終わらせる（それまでに読み込んだ分は返す）
+fn close_pipe(sender: &std::sync::mpsc::Sender<PipeChunk>) {
+    let _ = sender.send(Ok(Vec::new()));
+}
+
+impl Read for PipeReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.chunk.len() {
+            if self.done {
+                return Ok(0);
+            }
+            match self.receiver.recv() {
+                Ok(Ok(chunk)) if chunk.is_empty() => self.done = true,
+                Ok(Ok(chunk)) => {
+                    self.chunk = chunk;
+                    self.pos = 0;
+                }
+                Ok(Err(e)) => {
+                    self.done = true;
+                    return Err(e);
                 }
+                Err(_) => self.done = true,
             }
         }
-        new_json["spans"] = serde_json::Value::Array(new_spans);
+        let n = buf.len().min(self.chunk.len() - self.pos);
+        buf[..n].copy_from_slice(&self.chunk[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
     }
+}
 
-    // children[].message, children[].spans[].label
-    if let Some(children) = json.get("children").and_then(|c| c.as_array()) {
-        let mut new_children = children.clone();
-        for (i, child) in children.iter().enumerate() {
-            let mut new_child = child.clone();
-            // children[].message
-            if let Some(child_msg) = child.get("message").and_then(|m| m.as_str()) {
-                let translated = translate_message(child_msg, translations);
-                if translated != child_msg {
-                    new_child["message"] = serde_json::Value::String(translated.clone());
-                    replaced.push((child_msg.to_string(), translated));
-                }
-            }
-            // children[].spans[].label
-            if let Some(child_spans) = child.get("spans").and_then(|s| s.as_array()) {
-                let mut new_child_spans = child_spans.clone();
-                for (j, span) in child_spans.iter().enumerate() {
-                    if let Some(label) = span.get("label").and_then(|l| l.as_str()) {
-                        let translated = translate_message(label, translations);
-                        if translated != label {
-                            let mut new_span = span.clone();
-                            new_span["label"] = serde_json::Value::String(translated.clone());
-                            new_child_spans[j] = new_span;
-                            replaced.push((label.to_string(), translated));
-                        }
-                    }
-                }
-                new_child["spans"] = serde_json::Value::Array(new_child_spans);
-            }
-            new_children[i] = new_child;
+// RUSTC_JA_TIMEOUT の値（秒、小数も可）から制限時間を決める（正の数でなければ制限しない）
+fn timeout_duration(value: &str) -> Option<std::time::Duration> {
+    let secs: f64 = value.trim().parse().ok()?;
+    (secs > 0.0)
+        .then(|| std::time::Duration::try_from_secs_f64(secs).ok())
+        .flatten()
+}
+
+// 子プロセスの終了を待ち、制限時間を過ぎたら止める（止めた場合は true を返す）
+fn wait_or_kill(child: &mut std::process::Child, limit: std::time::Duration) -> io::Result<bool> {
+    let start = std::time::Instant::now();
+    loop {
+        if child.try_wait()?.is_some() {
+            return Ok(false);
         }
-        new_json["children"] = serde_json::Value::Array(new_children);
+        let elapsed = start.elapsed();
+        if elapsed >= limit {
+            child.kill()?;
+            return Ok(true);
+        }
+        std::thread::sleep(TIMEOUT_POLL_INTERVAL.min(limit - elapsed));
     }
+}
 
-    // rendered の置換
-    if let Some(rendered) = new_json.get("rendered").and_then(|r| r.as_str()) {
-        let mut new_rendered = rendered.to_string();
-        for (orig, trans) in &replaced {
-            if !orig.is_empty() && orig != trans {
-                new_rendered = new_rendered.replace(orig, trans);
-            }
+// 標準出力も標準エラー出力もそのまま引き継いでコマンドを実行し、その終了コードを返す
+// Unix では exec でこのプロセスをコマンドに置き換えるので、Ctrl-C などのシグナルや
+// プロセスグループは、コマンドを直接実行した場合と同じになる（起動できた場合は戻らない）
+// 他のプラットフォームでは子プロセスとして実行し、終了を待つ
+fn run_passthrough(
+    cmd: &std::ffi::OsStr,
+    args: &[std::ffi::OsString],
+) -> Result<i32, WrapperError> {
+    let mut command = Command::new(cmd);
+    command.args(args);
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        // exec は失敗した場合のみ戻る
+        Err(WrapperError::Spawn(command.exec()))
+    }
+    #[cfg(not(unix))]
+    passthrough_status(&mut command)
+}
+
+// exec を使えない場合: コマンドを子プロセスとして実行し、終了を待って終了コードを返す
+#[cfg(any(not(unix), test))]
+fn passthrough_status(command: &mut Command) -> Result<i32, WrapperError> {
+    let status = command.status().map_err(WrapperError::Spawn)?;
+    Ok(exit_code(&status))
+}
+
+// デバッグ用のログに、翻訳の件数を追記する（翻訳器を使わなかった場合は 0 件）
+fn append_stats_log() {
+    if DEBUG_LOG.is_none() {
+        return;
+    }
+    let stats = once_cell::sync::Lazy::get(&TRANSLATOR)
+        .and_then(Translator::stats)
+        .unwrap_or_default();
+    append_debug_log(&format!("STATS {}", stats));
+}
+
+// 子プロセスの終了状態から、このプロセスの終了コードを決める
+// Unix でシグナルにより終了した場合は、シェルと同じく 128 + シグナル番号にする
+fn exit_code(status: &std::process::ExitStatus) -> i32 {
+    if let Some(code) = status.code() {
+        return code;
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return 128 + signal;
         }
-        new_json["rendered"] = serde_json::Value::String(new_rendered);
     }
+    1
+}
+
+/// 使い方
+const USAGE: &str = "Usage: rustc-ja-wrapper <command> [args...]";
+
+/// "--help" で表示するオプションと環境変数の説明
+const ENV_HELP: &str = "\
+Wrapper options (only as the first argument):
+  --check [file]             check a translation file (default: the bundled translations)
+  --doctor [command]         show the translations, language, debug log and rustc in use
+  --dump-translations [--json]
+                             list the loaded translations in matching order
+  --reverse                  translate JSON diagnostics on stdin back into English
+  --filter [--dry-run]       translate JSON diagnostics on stdin to stdout without running a command
+  --coverage <jsonl-file>    report how many diagnostic messages in the file are translated
+  --emit-untranslated-json <jsonl-file> [json-file]
+                             write the untranslated messages in the file as entries with an empty \"ja\"
+  --help                     show this help
+  --version                  show the version
+
+Environment variables:
+  RUSTC_JA_CONFIG            config file (default: ./rustc-ja-wrapper.toml; env vars override it)
+  RUSTC_JA_TRANSLATE         translation file (JSON, .po or .csv) or directory of *.json merged over the bundled translations
+  RUSTC_JA_LANG              language of the bundled translations (default: LC_ALL/LANG, then ja)
+  RUSTC_JA_CATEGORIES        comma-separated categories to translate (e.g. borrow,types)
+  RUSTC_JA_CATEGORIES_STRICT also skip entries without a category when RUSTC_JA_CATEGORIES is set
+  RUSTC_JA_LEVELS            comma-separated levels of JSON diagnostics to translate (e.g. error,warning)
+  RUSTC_JA_EMBED_ORIGINAL    embed the original diagnostic as \"original\" in JSON output
+  RUSTC_JA_MAX_RENDERED      do not translate \"rendered\" longer than this many bytes
+  RUSTC_JA_MISSING_LOG       append untranslated messages to this file
+  RUSTC_JA_DEBUG_LOG         append the compiler's stderr to this file (1: temp directory)
+  RUSTC_JA_TRANSLATE_STDOUT  also translate JSON diagnostics on stdout
+  RUSTC_JA_HUMAN             translate human/short output (unless 0/false/no/off)
+  RUSTC_JA_PRIMARY_SPANS_ONLY translate only the labels of primary spans in JSON diagnostics
+  RUSTC_JA_BILINGUAL         show English with the translation (inline: \"ja (en)\", line: English line then translated line)
+  RUSTC_JA_DISABLE           pass all output through untranslated (unless 0/false/no/off)
+  RUSTC_JA_RUSTC_VERSION     rustc version for min_version/max_version (default: probe rustc --version)
+  RUSTC_JA_TIMEOUT           kill the command after this many seconds and exit with 124
+  RUSTC_JA_DRY_RUN           pass output through untranslated and report the translations to the debug log or stdout
+";
 
-    // 1行JSONLとして返す
-    match serde_json::to_string(&new_json) {
-        Ok(s) => serde_json::from_str(&s).unwrap_or(new_json),
-        Err(_) => new_json,
+// 最初の引数が "--version" か "--help" であれば、表示する内容を返す
+fn wrapper_info(arg: &str) -> Option<String> {
+    match arg {
+        "--version" => Some(format!("rustc-ja-wrapper {}\n", env!("CARGO_PKG_VERSION"))),
+        "--help" => Some(format!("{}\n\n{}", USAGE, ENV_HELP)),
+        _ => None,
     }
 }
 
-/// メッセージを日本語に翻訳する
-pub fn translate_message(message: &str, translations: &[TranslateEntry]) -> String {
-    // プレースホルダ用の正規表現
-    static PLACEHOLDER_RE: once_cell::sync::Lazy<regex::Regex> =
-        once_cell::sync::Lazy::new(|| regex::Regex::new(r"\{\$(\w+)\}").unwrap());
-    for trans in translations.iter() {
-        let en_str = &trans.en;
-        let ja_str = &trans.ja;
+/// 自己診断で使う疑似コンパイラとして起動するための引数
+const FAKE_COMPILER_ARG: &str = "--wrapper-fake-compiler";
+
+/// 疑似コンパイラが標準エラー出力に書き出す診断
+const FAKE_DIAGNOSTIC: &str = r#"{"$message_type":"diagnostic","message":"mismatched types","code":{"code":"E0308","explanation":null},"level":"error","spans":[],"children":[],"rendered":"error[E0308]: mismatched types\n"}"#;
+
+/// 疑似コンパイラの終了コード
+const FAKE_EXIT_CODE: i32 = 1;
+
+/// 自己診断で使う翻訳データ（組み込みの翻訳データがなくても確認できるように、RUSTC_JA_TRANSLATE に指定する）
+const SELFCHECK_TRANSLATIONS: &str = r#"[{ "en": "mismatched types", "ja": "型が不一致です" }]"#;
+
+// 疑似コンパイラとして、決まった診断を標準エラー出力に書き出して終了する
+fn fake_compiler() -> i32 {
+    eprintln!("{}", FAKE_DIAGNOSTIC);
+    FAKE_EXIT_CODE
+}
+
+// 自己診断: 自分自身をラッパーとして起動し、疑似コンパイラの診断が翻訳されるか確認する
+// "ラッパー → 疑似コンパイラ" の起動・取り込み・翻訳・書き出し・終了コードを一通り確認する
+fn selfcheck() -> i32 {
+    let exe = match env::current_exe() {
+        Ok(exe) => exe,
+        Err(e) => {
+            eprintln!("selfcheck: failed to locate the wrapper: {}", e);
+            return 1;
+        }
+    };
+    // カレントディレクトリの rustc-ja-wrapper.toml を読み込まないように、一時ディレクトリで実行する
+    let dir = env::temp_dir().join(format!("rustc-ja-wrapper-selfcheck-{}", std::process::id()));
+    let output = run_selfcheck_wrapper(&exe, &dir);
+    let _ = std::fs::remove_dir_all(&dir);
+    let output = match output {
+        Ok(output) => output,
+        Err(e) => {
+            eprintln!("selfcheck: failed to run the wrapper: {}", e);
+            return 1;
+        }
+    };
+    match check_selfcheck_output(&output.stderr, output.status.code()) {
+        Ok(()) => {
+            println!("selfcheck: ok");
+            0
+        }
+        Err(e) => {
+            eprintln!("selfcheck: {}", e);
+            1
+        }
+    }
+}
 
-        // プレースホルダ以外の部分をエスケープしつつ、プレースホルダは名前付きグループに変換
-        let mut re_str = String::new();
-        let mut last = 0;
-        for caps in PLACEHOLDER_RE.captures_iter(en_str) {
-            let m = caps.get(0).unwrap();
-            // プレースホルダ前の部分をエスケープ
-            re_str.push_str(&regex::escape(&en_str[last..m.start()]));
-            // プレースホルダ部分を名前付きグループに
-            let name = &caps[1];
-            re_str.push_str(&format!("(?P<{}>.+?)", name));
-            last = m.end();
+// 自己診断のラッパーを dir で起動する
+// dir には空の設定ファイルと自己診断の翻訳データを書き込み、RUSTC_JA_CONFIG と RUSTC_JA_TRANSLATE に指定する
+fn run_selfcheck_wrapper(exe: &Path, dir: &Path) -> io::Result<std::process::Output> {
+    std::fs::create_dir_all(dir)?;
+    let config = dir.join(rustc_ja_wrapper::config::CONFIG_FILE_NAME);
+    std::fs::write(&config, "")?;
+    let translate = dir.join("translate.json");
+    std::fs::write(&translate, SELFCHECK_TRANSLATIONS)?;
+    let mut command = Command::new(exe);
+    command.current_dir(dir).args([
+        exe.as_os_str(),
+        FAKE_COMPILER_ARG.as_ref(),
+        "--error-format=json".as_ref(),
+    ]);
+    // 利用者の設定に影響されないように、RUSTC_JA_* の環境変数は引き継がない
+    for (key, _) in env::vars_os() {
+        if key.to_string_lossy().starts_with("RUSTC_JA_") {
+            command.env_remove(key);
         }
-        // 残りの部分をエスケープ
-        re_str.push_str(&regex::escape(&en_str[last..]));
+    }
+    command
+        .env("RUSTC_JA_CONFIG", &config)
+        .env("RUSTC_JA_TRANSLATE", &translate)
+        .env("RUSTC_JA_LANG", DEFAULT_LANG)
+        .output()
+}
 
-        // 末尾に「.*」を追加して先頭一致＋残り文字列取得
-        let re = match regex::Regex::new(&format!("^{}(.*)$", re_str)) {
-            Ok(r) => r,
-            Err(_) => continue,
-        };
-        if let Some(caps) = re.captures(message) {
-            // ja側のプレースホルダをキャプチャ値で置換
-            let mut result = ja_str.to_string();
-            for name in re.capture_names().flatten() {
-                if name.is_empty() || name == "0" || name == "1" {
-                    continue;
-                }
-                if let Some(val) = caps.name(name) {
-                    result = result.replace(&format!("{{${}}}", name), val.as_str());
-                }
-            }
-            // 追加: パターン外の残り文字列を末尾に追加
-            if let Some(extra) = caps.get(caps.len() - 1) {
-                let extra_str = extra.as_str();
-                if !extra_str.is_empty() {
-                    result.push_str(extra_str);
-                }
-            }
-            return result;
+// 設定を診断して表示する（翻訳データが空なら、または rustc が見つからなければ 1 を返す）
+// rustc は min_version と max_version に使うものと同じ rustc_command() を調べる
+fn doctor() -> i32 {
+    let categories = env::var("RUSTC_JA_CATEGORIES").ok().map(|v| split_list(&v));
+    let rustc = Command::new(rustc_command())
+        .arg("--version")
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string());
+    let report = diagnose(CONFIG.translate.as_deref(), categories.as_deref(), rustc);
+    print!("{}", report);
+    if report.is_ok() { 0 } else { 1 }
+}
+
+// 読み込んだ翻訳データのエントリを、照合する順に表示する（コマンドは起動しない）
+// json なら標準出力にエントリの JSON を書き出し、件数と警告は標準エラー出力に書き出す
+fn dump_translations(json: bool) -> i32 {
+    let categories = env::var("RUSTC_JA_CATEGORIES").ok().map(|v| split_list(&v));
+    let (entries, warnings) = load_translate_list(
+        bundled_translations(&LOCALE),
+        CONFIG.translate.as_deref(),
+        categories.as_deref(),
+    );
+    if json {
+        eprintln!("{} entries", entries.len());
+        for warning in &warnings {
+            eprintln!("warning: {}", warning);
+        }
+        if let Err(e) = io::stdout().write_all(&entries_to_json(&entries)) {
+            eprintln!("Failed to write translations: {}", e);
+            return 1;
         }
+    } else {
+        print!("{}", format_dump(&entries, &warnings, &LOCALE));
     }
-    message.to_string()
+    0
 }
 
-/// デバッグ用: /tmp/rustc-ja-wrapper-debug.log に追記書き込みする
-pub fn append_debug_log(msg: &str) {
+// "--dump-translations" の表示（件数、警告、照合する順の番号と英語と翻訳）
+fn format_dump(entries: &[TranslateEntry], warnings: &[String], lang: &str) -> String {
+    let mut out = format!("{} entries\n", entries.len());
+    for warning in warnings {
+        out.push_str(&format!("warning: {}\n", warning));
+    }
+    for (i, entry) in entries.iter().enumerate() {
+        let translated = entry
+            .text_for(lang)
+            .map_or("(no translation)".to_string(), |t| format!("{:?}", t));
+        out.push_str(&format!("{}. {:?} → {}\n", i + 1, entry.en, translated));
+    }
+    out
+}
+
+/// "--doctor" で表示する診断の結果
+struct Doctor {
+    /// 組み込みの翻訳データがあるか（feature "bundled-ja"）
+    bundled: bool,
+    /// 組み込みの翻訳データに重ねて読み込む翻訳ファイル
+    translate: Option<std::path::PathBuf>,
+    /// 読み込んだエントリの件数
+    entries: usize,
+    /// 読み込み時の警告
+    warnings: Vec<String>,
+    /// 翻訳先の言語
+    lang: String,
+    /// デバッグ用のログファイル
+    debug_log: Option<std::path::PathBuf>,
+    /// `rustc --version` の出力（見つからなければ None）
+    rustc: Option<String>,
+}
+
+impl Doctor {
+    // 翻訳データがあり、rustc も見つかったか
+    fn is_ok(&self) -> bool {
+        self.entries > 0 && self.rustc.is_some()
+    }
+}
+
+impl std::fmt::Display for Doctor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.bundled, &self.translate) {
+            (true, Some(path)) => writeln!(f, "translations: bundled + {}", path.display())?,
+            (true, None) => writeln!(f, "translations: bundled")?,
+            (false, Some(path)) => writeln!(f, "translations: {}", path.display())?,
+            (false, None) => writeln!(f, "translations: none")?,
+        }
+        writeln!(f, "entries: {}", self.entries)?;
+        for warning in &self.warnings {
+            writeln!(f, "warning: {}", warning)?;
+        }
+        writeln!(f, "language: {}", self.lang)?;
+        match &self.debug_log {
+            Some(path) => writeln!(f, "debug log: {}", path.display())?,
+            None => writeln!(f, "debug log: disabled")?,
+        }
+        match &self.rustc {
+            Some(version) => writeln!(f, "rustc: {}", version),
+            None => writeln!(f, "rustc: not found"),
+        }
+    }
+}
+
+// 翻訳データを読み込み直して、診断の結果をまとめる（警告は表示せずに結果に含める）
+fn diagnose(path: Option<&Path>, categories: Option<&[String]>, rustc: Option<String>) -> Doctor {
+    let (entries, warnings) = load_translate_list(bundled_translations(&LOCALE), path, categories);
+    Doctor {
+        bundled: cfg!(feature = "bundled-ja"),
+        translate: path.map(Path::to_path_buf),
+        entries: entries.len(),
+        warnings,
+        lang: LOCALE.clone(),
+        debug_log: DEBUG_LOG.clone(),
+        rustc,
+    }
+}
+
+// 自己診断の結果を確認する
+fn check_selfcheck_output(stderr: &[u8], code: Option<i32>) -> Result<(), String> {
+    if code != Some(FAKE_EXIT_CODE) {
+        return Err(format!(
+            "unexpected exit code: {:?} (expected {})",
+            code, FAKE_EXIT_CODE
+        ));
+    }
+    let json: serde_json::Value = serde_json::from_slice(stderr)
+        .map_err(|e| format!("stderr is not a JSON diagnostic: {}", e))?;
+    let expected = ("型が不一致です", "error[E0308]: 型が不一致です\n");
+    if (json["message"].as_str(), json["rendered"].as_str()) != (Some(expected.0), Some(expected.1))
+    {
+        return Err(format!("diagnostic is not translated: {}", json));
+    }
+    Ok(())
+}
+
+// 翻訳データを `.po` 形式でファイルに書き出す
+fn export_po(path: Option<std::ffi::OsString>) -> i32 {
+    let Some(path) = path else {
+        eprintln!("Usage: rustc-ja-wrapper --wrapper-export-po <file>");
+        return 1;
+    };
+    for warning in rustc_ja_wrapper::po::unexported(&TRANSLATE_LIST) {
+        eprintln!("rustc-ja-wrapper: {}", warning);
+    }
+    if let Err(e) = std::fs::write(&path, rustc_ja_wrapper::po::to_po(&TRANSLATE_LIST)) {
+        eprintln!("Failed to write {}: {}", path.to_string_lossy(), e);
+        return 1;
+    }
+    0
+}
+
+// `.po` 形式のファイルを読み込んで、翻訳データの JSON として書き出す
+fn import_po(po_path: Option<std::ffi::OsString>, json_path: Option<std::ffi::OsString>) -> i32 {
+    let (Some(po_path), Some(json_path)) = (po_path, json_path) else {
+        eprintln!("Usage: rustc-ja-wrapper --wrapper-import-po <po-file> <json-file>");
+        return 1;
+    };
+    let po = match std::fs::read_to_string(&po_path) {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("Failed to read {}: {}", po_path.to_string_lossy(), e);
+            return 1;
+        }
+    };
+    let entries = rustc_ja_wrapper::po::from_po(&po);
+    if let Err(e) = std::fs::write(&json_path, entries_to_json(&entries)) {
+        eprintln!("Failed to write {}: {}", json_path.to_string_lossy(), e);
+        return 1;
+    }
+    0
+}
+
+// 標準入力の JSONL の日本語の診断を、英語に戻して標準出力に書き出す
+fn reverse() -> i32 {
+    let translator = Translator::new(reverse_entries(&TRANSLATE_LIST));
+    let stdin = io::stdin();
+    if let Err(e) = reverse_json_lines(stdin.lock(), &mut io::stdout(), &translator) {
+        eprintln!("Failed to reverse: {}", e);
+        return 1;
+    }
+    0
+}
+
+// 標準入力の JSONL の診断を翻訳して、標準出力に書き出す（コマンドは起動しない）
+// dry run の場合は変換せずに書き出し、翻訳の前後の組をデバッグ用のログ（未設定なら標準エラー出力）に書き出す
+fn filter(dry_run: bool) -> i32 {
+    let stdin = io::stdin();
+    let mut report = dry_run.then(|| dry_run_report(io::stderr()));
+    filter_to(
+        stdin.lock(),
+        &mut io::stdout().lock(),
+        report.as_deref_mut().map(|r| r as &mut dyn Write),
+    )
+}
+
+// dry run の報告の書き出し先（デバッグ用のログがあればそのファイル、なければ `fallback`）
+fn dry_run_report(fallback: impl Write + Send + 'static) -> Box<dyn Write + Send> {
     use std::fs::OpenOptions;
-    use std::io::Write;
-    let path = "/tmp/rustc-ja-wrapper-debug.log";
-    if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(path) {
-        let _ = writeln!(file, "{}", msg);
+    DEBUG_LOG
+        .as_deref()
+        .and_then(|path| OpenOptions::new().create(true).append(true).open(path).ok())
+        .map_or_else(
+            || Box::new(fallback) as Box<dyn Write + Send>,
+            |file| Box::new(file),
+        )
+}
+
+// コマンドが指定されなかった場合、入力が端末なら使い方を表示し、
+// パイプなどで渡された場合は "--filter" と同じく JSONL の診断を翻訳する
+fn run_without_command(input: impl BufRead, is_terminal: bool, out: &mut impl Write) -> i32 {
+    if is_terminal {
+        eprintln!("{}", USAGE);
+        return 1;
     }
+    filter_to(input, out, None)
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+// JSONL の診断を翻訳して書き出し、終了コードを返す（report があれば dry run）
+fn filter_to(input: impl BufRead, out: &mut impl Write, report: Option<&mut dyn Write>) -> i32 {
+    if let Err(e) = filter_json_lines(input, out, report) {
+        eprintln!("Failed to filter: {}", e);
+        return 1;
+    }
+    append_stats_log();
+    0
+}
 
-    #[test]
-    fn test_translate_message_simple() {
-        // テスト用の翻訳データ
-        let test_translate_entries: &[TranslateEntry] = &[
-            TranslateEntry { en: "hello".to_string(), ja: "こんにちは".to_string() },
-            TranslateEntry { en: "error: {$name}".to_string(), ja: "エラー: {$name}".to_string() },
-            TranslateEntry { en: "borrow of moved value".to_string(), ja: "移動された値の借用".to_string() },
-            TranslateEntry {
-                en: "move occurs because `{$name}` has type `{$ty}`, which does not implement the `Copy` trait".to_string(),
-                ja: "`{$ty}` 型の `{$name}` は `Copy` トレイトを実装していないので、移動します".to_string()
-            },
-        ];
+// JSONL の診断を 1 行ずつ翻訳して書き出す（診断でない行はそのまま書き出す）
+// 標準エラー出力の JSON の診断と同じく、行が届くたびに変換する（report があれば dry run）
+fn filter_json_lines(
+    input: impl BufRead,
+    out: &mut impl Write,
+    report: Option<&mut dyn Write>,
+) -> io::Result<()> {
+    stream_stderr(input, out, ErrorFormat::Json, report)
+}
 
-        assert_eq!(
-            translate_message("hello", test_translate_entries),
-            "こんにちは"
-        );
-        assert_eq!(
-            translate_message("error: foo", test_translate_entries),
-            "エラー: foo"
-        );
-        assert_eq!(
-            translate_message("not found", test_translate_entries),
-            "not found"
-        );
-        assert_eq!(
-            translate_message("borrow of moved value", test_translate_entries),
-            "移動された値の借用"
-        );
-        assert_eq!(
-            translate_message(
-                "move occurs because `s1` has type `String`, which does not implement the `Copy` trait",
-                test_translate_entries
-            ),
-            "`String` 型の `s1` は `Copy` トレイトを実装していないので、移動します"
-        );
-    }
-
-    #[test]
-    fn test_translate_json_message_message_field() {
-        let test_translate_entries: &[TranslateEntry] = &[
-            TranslateEntry {
-                en: "borrow of moved value".to_string(),
-                ja: "移動された値の借用".to_string(),
-            },
-            TranslateEntry {
-                en: "value moved here".to_string(),
-                ja: "ここで値を移動".to_string(),
-            },
-            TranslateEntry {
-                en: "value borrowed here after move".to_string(),
-                ja: "移動後の値をここで借用".to_string(),
-            },
-            TranslateEntry {
-                en: "consider cloning the value if the performance cost is acceptable".to_string(),
-                ja: "複製コストが許容できるなら、クローンすることを検討してください".to_string(),
-            },
-        ];
-        let json = serde_json::json!({
-            "message": "borrow of moved value: `s1`",
-            "spans": [
-                {
-                    "label": "value moved here",
-                },
-                {
-                    "label": "value borrowed here after move",
-                },
-            ],
-            "children": [
-                {
-                    "message": "consider cloning the value if the performance cost is acceptable",
-                    "spans": [
-                        {
-                            "label": "hello",
-                        }
-                    ],
-                },
-            ],
-            "rendered": "borrow of moved value: `s1`\nvalue moved here\nvalue borrowed here after move\nconsider cloning the value if the performance cost is acceptable",
+// JSONL の診断を 1 行ずつ指定した翻訳器で変換する（診断でない行はそのまま書き出す）
+fn reverse_json_lines(
+    input: impl BufRead,
+    out: &mut impl Write,
+    translator: &Translator,
+) -> io::Result<()> {
+    for line in input.lines() {
+        let line = line?;
+        match serde_json::from_str::<serde_json::Value>(&line) {
+            Ok(json) if rustc_ja_wrapper::is_diagnostic(&json) => {
+                let reversed = translator.translate_json(&json);
+                writeln!(out, "{}", serde_json::to_string(&reversed)?)?;
+            }
+            _ => writeln!(out, "{}", line)?,
+        }
+    }
+    out.flush()
+}
+
+/// 網羅率の表示で示す、翻訳できなかったメッセージの件数
+const COVERAGE_TOP: usize = 20;
+
+// JSONL のファイルの診断について、翻訳の網羅率を表示する
+fn coverage(path: Option<std::ffi::OsString>) -> i32 {
+    let Some(path) = path else {
+        eprintln!("Usage: rustc-ja-wrapper --coverage <jsonl-file>");
+        return 1;
+    };
+    let jsonl = match std::fs::read_to_string(&path) {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("Failed to read {}: {}", path.to_string_lossy(), e);
+            return 1;
+        }
+    };
+    let coverage = measure_coverage(&jsonl, &|m| TRANSLATOR.translate(m));
+    print!("{}", format_coverage(&coverage, COVERAGE_TOP));
+    0
+}
+
+// JSONL のファイルの診断のうち翻訳できなかったメッセージを、ja を空にした翻訳データのひな形にする
+// 出力先を省略した場合は標準出力に書き出す
+fn emit_untranslated_json(
+    path: Option<std::ffi::OsString>,
+    json_path: Option<std::ffi::OsString>,
+) -> i32 {
+    let Some(path) = path else {
+        eprintln!("Usage: rustc-ja-wrapper --emit-untranslated-json <jsonl-file> [json-file]");
+        return 1;
+    };
+    let jsonl = match std::fs::read_to_string(&path) {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("Failed to read {}: {}", path.to_string_lossy(), e);
+            return 1;
+        }
+    };
+    let coverage = measure_coverage(&jsonl, &|m| TRANSLATOR.translate(m));
+    let json = entries_to_json(&untranslated_stubs(&coverage));
+    let result = match &json_path {
+        Some(json_path) => std::fs::write(json_path, json),
+        None => io::stdout().write_all(&json),
+    };
+    if let Err(e) = result {
+        let target = json_path.map_or("stdout".into(), |p| p.to_string_lossy().into_owned());
+        eprintln!("Failed to write {}: {}", target, e);
+        return 1;
+    }
+    0
+}
+
+// 翻訳ファイルを検査して結果を表示する（省略時は組み込みの翻訳データ）
+// エラーが 1 件でもあれば 1 を返す
+fn check(path: Option<std::ffi::OsString>) -> i32 {
+    let json_str = match &path {
+        Some(path) => match std::fs::read_to_string(path) {
+            Ok(s) => s,
+            Err(e) => {
+                eprintln!("Failed to read {}: {}", path.to_string_lossy(), e);
+                return 1;
+            }
+        },
+        None => bundled_translations(&LOCALE).to_string(),
+    };
+    let issues = check_translations(&json_str, &LOCALE);
+    let mut errors = 0;
+    for issue in &issues {
+        match issue {
+            CheckIssue::Error(msg) => {
+                errors += 1;
+                eprintln!("error: {}", msg);
+            }
+            CheckIssue::Warning(msg) => eprintln!("warning: {}", msg),
+        }
+    }
+    eprintln!(
+        "check: {} error(s), {} warning(s)",
+        errors,
+        issues.len() - errors
+    );
+    if errors > 0 { 1 } else { 0 }
+}
+
+// 翻訳データを assets/translate.ja.json と同じ書式（インデント 4）の JSON にする
+fn entries_to_json(entries: &[TranslateEntry]) -> Vec<u8> {
+    use serde::Serialize;
+    let mut buf = Vec::new();
+    let formatter = serde_json::ser::PrettyFormatter::with_indent(b"    ");
+    let mut serializer = serde_json::Serializer::with_formatter(&mut buf, formatter);
+    // Vec<u8> への書き込みは失敗しない
+    entries.serialize(&mut serializer).unwrap();
+    buf.push(b'\n');
+    buf
+}
+
+/// コンパイラのエラー出力の形式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ErrorFormat {
+    Human,
+    Json,
+    Short,
+}
+
+// "--error-format" と "--json" の指定から、エラー出力の形式を判定する（引数ファイルの中も調べる）
+// "--error-format=json" と "--error-format json" のどちらの書き方にも対応し、
+// "--error-format=json,artifacts" のようなカンマ区切りの値は、"json" を含めば JSON とみなす
+// "--json=diagnostic-rendered-ansi" などの "--json" は JSON の出力でのみ使えるので、指定があれば JSON とみなす
+// "--error-format" が複数指定された場合は、rustc と同様に最後の指定を使う
+fn detect_error_format(args: &[std::ffi::OsString]) -> ErrorFormat {
+    let flags = compiler_flags(args);
+    let mut format = ErrorFormat::Human;
+    let mut json_option = false;
+    let mut iter = flags.iter().map(|a| a.to_str());
+    while let Some(arg) = iter.next() {
+        let value = match arg {
+            Some("--error-format") => iter.next().flatten(),
+            Some("--json") => {
+                iter.next();
+                json_option = true;
+                continue;
+            }
+            Some(a) if a.starts_with("--json=") => {
+                json_option = true;
+                continue;
+            }
+            Some(a) => a.strip_prefix("--error-format="),
+            None => None,
+        };
+        format = match value {
+            Some(value) if value.split(',').any(|v| v.trim() == "json") => ErrorFormat::Json,
+            Some("short") => ErrorFormat::Short,
+            Some(_) => ErrorFormat::Human,
+            None => continue,
+        };
+    }
+    if json_option {
+        ErrorFormat::Json
+    } else {
+        format
+    }
+}
+
+// エラー出力が JSON の形式か（判定は detect_error_format と同じ）
+fn detects_json_format(args: &[std::ffi::OsString]) -> bool {
+    detect_error_format(args) == ErrorFormat::Json
+}
+
+// コンパイラへのオプションとして扱う引数を返す
+// "--" 以降はオプションではないので含めず、引数ファイルは展開する
+fn compiler_flags(args: &[std::ffi::OsString]) -> Vec<std::ffi::OsString> {
+    let before_separator = |a: &std::ffi::OsString| a != "--";
+    let flags: Vec<std::ffi::OsString> =
+        args.iter().cloned().take_while(before_separator).collect();
+    expand_argfiles(&flags)
+        .into_iter()
+        .take_while(before_separator)
+        .collect()
+}
+
+// "@path" 形式の引数ファイルを展開する
+// rustc と同様に、ファイルの各行を 1 つの引数として扱う（クォートは解釈しない）
+// "@shell:path" の場合は、シェルと同様にクォートを解釈して空白で区切る
+// 読み込めない場合は、元の引数をそのまま残す
+fn expand_argfiles(args: &[std::ffi::OsString]) -> Vec<std::ffi::OsString> {
+    let mut expanded = Vec::new();
+    for arg in args {
+        let Some(path) = arg.to_str().and_then(|a| a.strip_prefix('@')) else {
+            expanded.push(arg.clone());
+            continue;
+        };
+        let (path, shell) = match path.strip_prefix("shell:") {
+            Some(p) => (p, true),
+            None => (path, false),
+        };
+        match std::fs::read_to_string(path) {
+            Ok(content) if shell => expanded.extend(split_shell_words(&content).map(Into::into)),
+            Ok(content) => expanded.extend(content.lines().map(Into::into)),
+            Err(_) => expanded.push(arg.clone()),
+        }
+    }
+    expanded
+}
+
+// シェルと同様に、クォートとバックスラッシュを解釈して空白で区切る
+fn split_shell_words(s: &str) -> impl Iterator<Item = String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                in_word = true;
+                word.extend(chars.by_ref().take_while(|&c| c != '\''));
+            }
+            '"' => {
+                in_word = true;
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => break,
+                        '\\' => word.extend(chars.next()),
+                        _ => word.push(c),
+                    }
+                }
+            }
+            '\\' => {
+                in_word = true;
+                word.extend(chars.next());
+            }
+            c if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            _ => {
+                in_word = true;
+                word.push(c);
+            }
+        }
+    }
+    if in_word {
+        words.push(word);
+    }
+    words.into_iter()
+}
+
+// コンパイルではなく、バージョンや設定の問い合わせ（"-vV" や "--print" など）か判定する
+// cargo は RUSTC_WRAPPER 経由でもこれらを呼び出して、出力を解析する
+fn is_probe(args: &[std::ffi::OsString]) -> bool {
+    let version_query = !args.is_empty()
+        && args.iter().all(|a| {
+            matches!(
+                a.to_str(),
+                Some("-V" | "-vV" | "-v" | "--version" | "--verbose")
+            )
+        })
+        && args.iter().any(|a| a != "-v" && a != "--verbose");
+    version_query || has_print_option(args)
+}
+
+// "--print" オプションが含まれているか判定する
+fn has_print_option(args: &[std::ffi::OsString]) -> bool {
+    args.iter().any(|a| {
+        a.to_str()
+            .is_some_and(|s| s == "--print" || s.starts_with("--print="))
+    })
+}
+
+// 子プロセスの標準出力と標準エラー出力を中継する
+// 標準出力は translate_stdout が有効なら JSON の診断のみ変換し、無効ならそのまま書き出す（成果物の情報などの JSON は翻訳しない）
+// 標準エラー出力は stderr_format の形式として 1 行ずつ変換して書き出す（None ならそのまま書き出す）
+// それぞれ別のスレッドで同時に読み込み、両方を読み終えてから戻るので、
+// どちらかの出力が多くてもパイプが詰まってデッドロックしない
+// timeout を過ぎても子プロセスが終了しなければ止め、それまでの出力を書き出して true を返す
+// 孫プロセスがパイプを開いたままの場合も、timeout を過ぎたら（少し待ってから）読み込みを打ち切る
+fn relay_output(
+    child: &mut std::process::Child,
+    translate_stdout: bool,
+    stderr_format: Option<ErrorFormat>,
+    mut report: Option<Box<dyn Write + Send>>,
+    timeout: Option<std::time::Duration>,
+    mut out: impl Write + Send,
+    mut err: impl Write + Send,
+) -> io::Result<bool> {
+    // 制限時間があれば、パイプは別のスレッドで読み込み、打ち切れるようにする
+    let mut closers = Vec::new();
+    let mut pipe = |pipe: Box<dyn Read + Send>| -> Box<dyn Read + Send> {
+        if timeout.is_none() {
+            return pipe;
+        }
+        let (reader, closer) = pipe_reader(pipe);
+        closers.push(closer);
+        Box::new(reader)
+    };
+    let child_stdout = child.stdout.take().map(|p| pipe(Box::new(p)));
+    let child_stderr = child.stderr.take().map(|p| pipe(Box::new(p)));
+    let deadline = timeout.map(|limit| std::time::Instant::now() + limit + TIMEOUT_DRAIN_GRACE);
+    std::thread::scope(|scope| {
+        let stdout_relay = child_stdout.map(|mut child_out| {
+            scope.spawn(move || -> io::Result<()> {
+                if translate_stdout {
+                    let mut buf = Vec::new();
+                    child_out.read_to_end(&mut buf)?;
+                    out.write_all(&convert_json_error_format(buf))?;
+                } else {
+                    io::copy(&mut child_out, &mut out)?;
+                }
+                out.flush()
+            })
+        });
+
+        // 標準エラー出力を 1 行ずつ変換して書き出す（"--error-format" の指定に合わせる）
+        let stderr_relay = child_stderr.map(|mut child_err| {
+            scope.spawn(move || -> io::Result<()> {
+                match stderr_format {
+                    Some(format) => {
+                        append_debug_log("RESPONSE");
+                        stream_stderr(
+                            io::BufReader::new(child_err),
+                            &mut err,
+                            format,
+                            report.as_deref_mut().map(|r| r as &mut dyn Write),
+                        )
+                    }
+                    None => {
+                        io::copy(&mut child_err, &mut err)?;
+                        err.flush()
+                    }
+                }
+            })
         });
-        let translated = translate_json_message(&json, test_translate_entries);
-        let expected_json = serde_json::json!({
-            "message": "移動された値の借用: `s1`",
-            "spans": [
+
+        // 制限時間があれば、ここで終了を待つ（止めるとパイプが閉じるので、読み込みのスレッドも終わる）
+        let timed_out = timeout.map_or(Ok(false), |limit| wait_or_kill(child, limit));
+
+        // 孫プロセスがパイプを開いたままなら、制限時間を過ぎたところで読み込みを打ち切る
+        let (finished, finished_receiver) = std::sync::mpsc::channel::<()>();
+        if let Some(deadline) = deadline {
+            let closers = std::mem::take(&mut closers);
+            scope.spawn(move || {
+                let wait = deadline.saturating_duration_since(std::time::Instant::now());
+                if let Err(std::sync::mpsc::RecvTimeoutError::Timeout) =
+                    finished_receiver.recv_timeout(wait)
                 {
-                    "label": "ここで値を移動",
-                },
+                    closers.iter().for_each(close_pipe);
+                }
+            });
+        }
+
+        // 両方のスレッドの終了を待つ（どちらかが失敗しても、もう一方も最後まで読み込む）
+        let join = |relay: Option<std::thread::ScopedJoinHandle<'_, io::Result<()>>>, name| {
+            relay.map_or(Ok(()), |handle| {
+                handle
+                    .join()
+                    .unwrap_or_else(|_| Err(io::Error::other(format!("failed to relay {}", name))))
+            })
+        };
+        let stderr_result = join(stderr_relay, "stderr");
+        let stdout_result = join(stdout_relay, "stdout");
+        drop(finished);
+        stderr_result.and(stdout_result).and(timed_out)
+    })
+}
+
+// 標準エラー出力を読み込みながら、完全な 1 行ごとに変換して書き出す
+// 途中までしか届いていない行は、改行が届くまで溜めておく（最後の行は改行がなくても書き出す）
+// report があれば dry run として、変換前の行を書き出し、翻訳の前後の組を report に書き出す
+fn stream_stderr(
+    mut reader: impl BufRead,
+    writer: &mut impl Write,
+    format: ErrorFormat,
+    mut report: Option<&mut dyn Write>,
+) -> io::Result<()> {
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            return Ok(());
+        }
+        if let Ok(s) = std::str::from_utf8(&line) {
+            append_debug_log(s.trim_end_matches(['\n', '\r']));
+        }
+        match report.as_deref_mut() {
+            Some(report) => {
+                let converted = convert_stderr_line(format, line.clone());
+                for (original, translated) in substitutions(format, &line, &converted) {
+                    writeln!(report, "{:?} → {:?}", original, translated)?;
+                }
+                report.flush()?;
+                writer.write_all(&line)?;
+            }
+            None => writer.write_all(&convert_stderr_line(format, std::mem::take(&mut line)))?,
+        }
+        writer.flush()?;
+    }
+}
+
+// 変換前後の 1 行から、翻訳の前後の組を取り出す
+// JSON の診断は、同じ位置にある文字列のうち変わったもの（"rendered" を除く）の組にする
+// それ以外は行全体（改行コードを除く）の組にする
+fn substitutions(format: ErrorFormat, original: &[u8], converted: &[u8]) -> Vec<(String, String)> {
+    if original == converted {
+        return Vec::new();
+    }
+    if format == ErrorFormat::Json
+        && let (Ok(a), Ok(b)) = (
+            serde_json::from_slice::<serde_json::Value>(original),
+            serde_json::from_slice::<serde_json::Value>(converted),
+        )
+    {
+        let mut pairs = Vec::new();
+        changed_strings(&a, &b, &mut pairs);
+        return pairs;
+    }
+    let line = |data: &[u8]| {
+        String::from_utf8_lossy(data)
+            .trim_end_matches(['\n', '\r'])
+            .to_string()
+    };
+    vec![(line(original), line(converted))]
+}
+
+// 2 つの JSON の同じ位置にある文字列のうち、変わったものの組を集める（"rendered" は除く）
+fn changed_strings(
+    a: &serde_json::Value,
+    b: &serde_json::Value,
+    pairs: &mut Vec<(String, String)>,
+) {
+    use serde_json::Value;
+    match (a, b) {
+        (Value::String(a), Value::String(b)) if a != b => pairs.push((a.clone(), b.clone())),
+        (Value::Array(a), Value::Array(b)) => {
+            for (a, b) in a.iter().zip(b) {
+                changed_strings(a, b, pairs);
+            }
+        }
+        (Value::Object(a), Value::Object(b)) => {
+            for (key, a) in a.iter().filter(|(key, _)| *key != "rendered") {
+                if let Some(b) = b.get(key) {
+                    changed_strings(a, b, pairs);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+// 標準エラー出力の 1 行を、改行コードを除いて変換する
+fn convert_stderr_line(format: ErrorFormat, mut line: Vec<u8>) -> Vec<u8> {
+    let body_len = line.len()
+        - line
+            .iter()
+            .rev()
+            .take_while(|&&b| b == b'\n' || b == b'\r')
+            .count();
+    let ending = line.split_off(body_len);
+    let mut converted = match format {
+        ErrorFormat::Json if line.is_empty() => line,
+        ErrorFormat::Json => convert_json_error_format(line),
+        ErrorFormat::Short => convert_short_error_format(line),
+        ErrorFormat::Human => convert_human_error_format(line),
+    };
+    converted.extend_from_slice(&ending);
+    converted
+}
+
+// 標準エラーの人が読む形式の出力を変換する
+fn convert_human_error_format(data: Vec<u8>) -> Vec<u8> {
+    // UTF-8として解釈できなければそのまま返す
+    match std::str::from_utf8(&data) {
+        Ok(s) => TRANSLATOR.translate_human(s).into_bytes(),
+        Err(_) => data,
+    }
+}
+
+// 標準エラーの短い形式の出力を変換する
+fn convert_short_error_format(data: Vec<u8>) -> Vec<u8> {
+    // UTF-8として解釈できなければそのまま返す
+    match std::str::from_utf8(&data) {
+        Ok(s) => TRANSLATOR.translate_short(s).into_bytes(),
+        Err(_) => data,
+    }
+}
+
+// 標準エラーの JSONL を変換する
+fn convert_json_error_format(data: Vec<u8>) -> Vec<u8> {
+    rustc_ja_wrapper::convert_json_error_format(data, &TRANSLATOR)
+}
+
+/// デバッグ用のログファイル
+/// 設定の debug_log（環境変数 RUSTC_JA_DEBUG_LOG）がある場合のみ書き込む
+static DEBUG_LOG: once_cell::sync::Lazy<Option<std::path::PathBuf>> =
+    once_cell::sync::Lazy::new(|| {
+        debug_log_path(
+            CONFIG
+                .debug_log
+                .clone()
+                .map(std::path::PathBuf::into_os_string),
+        )
+    });
+
+// 設定の debug_log（RUSTC_JA_DEBUG_LOG）の値からログファイルのパスを決める
+// 空または "1" の場合はパスの指定ではないとみなし、一時ディレクトリのファイルを使う
+fn debug_log_path(value: Option<std::ffi::OsString>) -> Option<std::path::PathBuf> {
+    let value = value?;
+    if value.is_empty() || value == "1" {
+        Some(default_debug_log_path())
+    } else {
+        Some(value.into())
+    }
+}
+
+// 既定のログファイルのパス
+fn default_debug_log_path() -> std::path::PathBuf {
+    env::temp_dir().join("rustc-ja-wrapper-debug.log")
+}
+
+/// デバッグ用: ログファイルに追記書き込みする（RUSTC_JA_DEBUG_LOG が未設定なら何もしない）
+pub fn append_debug_log(msg: &str) {
+    append_debug_log_to(DEBUG_LOG.as_deref(), msg);
+}
+
+// 指定したログファイルに追記書き込みする
+fn append_debug_log_to(path: Option<&Path>, msg: &str) {
+    use std::fs::OpenOptions;
+    let Some(path) = path else {
+        return;
+    };
+    if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(path) {
+        let _ = writeln!(file, "{}", msg);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustc_ja_wrapper::translate_message;
+
+    #[cfg(feature = "bundled-ja")]
+    #[test]
+    fn test_translate_message_found_keyword() {
+        assert_eq!(
+            translate_message("expected identifier, found keyword `fn`", &TRANSLATE_LIST),
+            "識別子を期待したが、キーワード`fn`が見つかった"
+        );
+        assert_eq!(
+            translate_message(
+                "expected identifier, found keyword `struct`",
+                &TRANSLATE_LIST
+            ),
+            "識別子を期待したが、キーワード`struct`が見つかった"
+        );
+        assert_eq!(
+            translate_message("expected identifier, found keyword", &TRANSLATE_LIST),
+            "識別子を期待したが、キーワードが見つかった"
+        );
+        assert_eq!(
+            translate_message("escape `type` to use it as an identifier", &TRANSLATE_LIST),
+            "識別子として使うなら`type`をエスケープしてください"
+        );
+        assert_eq!(
+            translate_message(
+                "expected one of `:`, `;`, or `=`, found keyword `in`",
+                &TRANSLATE_LIST
+            ),
+            "`:`, `;`, or `=`のいずれかを期待したが、キーワード`in`が見つかった"
+        );
+        assert_eq!(
+            translate_message(
+                "expected one of `:`, `;`, or `=`, found `x`",
+                &TRANSLATE_LIST
+            ),
+            "`:`, `;`, or `=`のいずれかを期待したが、`x`が見つかった"
+        );
+    }
+
+    #[cfg(feature = "bundled-ja")]
+    #[test]
+    fn test_translate_aborting_summary() {
+        let translate = |m| translate_message(m, &TRANSLATE_LIST);
+        assert_eq!(
+            translate("aborting due to 1 previous error"),
+            "先行するエラーのため中止します"
+        );
+        assert_eq!(
+            translate("aborting due to 3 previous errors"),
+            "先行する3個のエラーのため中止します"
+        );
+        assert_eq!(
+            translate("aborting due to 1 previous error; 1 warning emitted"),
+            "先行するエラーのため中止します（1件の警告が出ました）"
+        );
+        assert_eq!(
+            translate("aborting due to 12 previous errors; 2 warnings emitted"),
+            "先行する12個のエラーのため中止します（2件の警告が出ました）"
+        );
+        assert_eq!(translate("2 warnings emitted"), "2件の警告が出ました");
+
+        // 人が読む形式の最後の行
+        let translator = Translator::new(TRANSLATE_LIST.clone());
+        assert_eq!(
+            translator.translate_human("error: aborting due to 2 previous errors\n"),
+            "error: 先行する2個のエラーのため中止します\n"
+        );
+    }
+
+    #[cfg(feature = "bundled-ja")]
+    #[test]
+    fn test_translate_cargo_lines() {
+        // cargo 自身が出力する行も、rustc の診断と同じ見出しの形式なので同じ翻訳データで翻訳する
+        let translator = Translator::new(TRANSLATE_LIST.clone());
+        let text = concat!(
+            "warning: unused manifest key: package.autor\n",
+            "   Compiling foo v0.1.0 (/tmp/foo)\n",
+            "warning: `foo` (bin \"foo\") generated 2 warnings\n",
+            "error: could not compile `foo` (bin \"foo\") due to 1 previous error; 2 warnings emitted\n",
+            "warning: build failed, waiting for other jobs to finish...\n",
+        );
+        let expected = concat!(
+            "warning: マニフェストの使われていないキー: package.autor\n",
+            "   Compiling foo v0.1.0 (/tmp/foo)\n",
+            "warning: `foo`（bin \"foo\"）で2件の警告が出ました\n",
+            "error: 先行するエラーのため`foo`（bin \"foo\"）をコンパイルできませんでした（2件の警告が出ました）\n",
+            "warning: ビルドに失敗しました。他のジョブの終了を待っています...\n",
+        );
+        assert_eq!(translator.translate_human(text), expected);
+        assert_eq!(
+            translate_message(
+                "could not compile `foo` (lib) due to 3 previous errors",
+                &TRANSLATE_LIST
+            ),
+            "先行する3個のエラーのため`foo`（lib）をコンパイルできませんでした"
+        );
+    }
+
+    #[test]
+    fn test_is_probe() {
+        let args = |list: &[&str]| -> Vec<std::ffi::OsString> {
+            list.iter().map(std::ffi::OsString::from).collect()
+        };
+        assert!(is_probe(&args(&["-vV"])));
+        assert!(is_probe(&args(&["-V"])));
+        assert!(is_probe(&args(&["--version", "--verbose"])));
+        assert!(is_probe(&args(&[
+            "-",
+            "--crate-name",
+            "___",
+            "--print=file-names",
+            "--crate-type",
+            "bin",
+        ])));
+        assert!(!is_probe(&args(&[])));
+        assert!(!is_probe(&args(&["-v"])));
+        assert!(!is_probe(&args(&[
+            "--crate-name",
+            "foo",
+            "--error-format=json",
+            "src/main.rs",
+        ])));
+    }
+
+    #[test]
+    fn test_detect_error_format_argfile() {
+        let dir = env::temp_dir();
+        let argfile = dir.join(format!("rustc-ja-wrapper-test-{}.args", std::process::id()));
+        std::fs::write(&argfile, "--crate-name\nfoo\n--error-format=json\n").unwrap();
+        let shell_argfile = dir.join(format!(
+            "rustc-ja-wrapper-test-{}.shell-args",
+            std::process::id()
+        ));
+        std::fs::write(
+            &shell_argfile,
+            "--cfg 'feature=\"a b\"' \"--error-format=json\"\n",
+        )
+        .unwrap();
+
+        let args: Vec<std::ffi::OsString> = vec![
+            "src/main.rs".into(),
+            format!("@{}", argfile.display()).into(),
+        ];
+        assert_eq!(detect_error_format(&args), ErrorFormat::Json);
+        assert_eq!(
+            expand_argfiles(&args),
+            ["src/main.rs", "--crate-name", "foo", "--error-format=json"]
+        );
+
+        let args: Vec<std::ffi::OsString> =
+            vec![format!("@shell:{}", shell_argfile.display()).into()];
+        assert_eq!(detect_error_format(&args), ErrorFormat::Json);
+        assert_eq!(
+            expand_argfiles(&args),
+            ["--cfg", "feature=\"a b\"", "--error-format=json"]
+        );
+
+        // 存在しない引数ファイルはそのまま
+        let args: Vec<std::ffi::OsString> = vec!["@/nonexistent/rustc-ja-wrapper.args".into()];
+        assert_eq!(detect_error_format(&args), ErrorFormat::Human);
+        assert_eq!(expand_argfiles(&args), args);
+
+        std::fs::remove_file(argfile).unwrap();
+        std::fs::remove_file(shell_argfile).unwrap();
+    }
+
+    #[cfg(feature = "bundled-ja")]
+    #[test]
+    fn test_translate_message_recursion_limit() {
+        assert_eq!(
+            translate_message(
+                "recursion limit reached while expanding `$crate::format_args!`",
+                &TRANSLATE_LIST
+            ),
+            "`$crate::format_args!`の展開中に再帰制限に達しました"
+        );
+        // 属性の提案は翻訳せずにそのまま残す
+        assert_eq!(
+            translate_message(
+                "consider increasing the recursion limit by adding a `#![recursion_limit = \"256\"]` attribute to your crate (`foo`)",
+                &TRANSLATE_LIST
+            ),
+            "クレート（`foo`）に`#![recursion_limit = \"256\"]`属性を追加して、再帰制限を増やすことを検討してください"
+        );
+        assert_eq!(
+            translate_message(
+                "consider adding a `#![type_length_limit=\"1094719\"]` attribute to your crate",
+                &TRANSLATE_LIST
+            ),
+            "クレートに`#![type_length_limit=\"1094719\"]`属性を追加することを検討してください"
+        );
+    }
+
+    // 少しずつしか読み込めないパイプの代わり
+    #[cfg(feature = "bundled-ja")]
+    struct ChunkedReader<'a> {
+        data: &'a [u8],
+        chunk: usize,
+    }
+
+    #[cfg(feature = "bundled-ja")]
+    impl Read for ChunkedReader<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = self.chunk.min(self.data.len()).min(buf.len());
+            buf[..n].copy_from_slice(&self.data[..n]);
+            self.data = &self.data[n..];
+            Ok(n)
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_exit_code() {
+        let status = |script: &str| Command::new("sh").arg("-c").arg(script).status().unwrap();
+        assert_eq!(exit_code(&status("exit 3")), 3);
+        assert_eq!(exit_code(&status("kill -KILL $$")), 128 + 9);
+        assert_eq!(exit_code(&status("kill -TERM $$")), 128 + 15);
+    }
+
+    #[cfg(unix)]
+    #[cfg(feature = "bundled-ja")]
+    #[test]
+    fn test_relay_output_large() {
+        // 標準出力を書き終えてから標準エラー出力を書くので、同時に読み込まないと詰まる
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(
+                "yes 'out line' | head -n 20000; yes 'error: mismatched types' | head -n 20000 >&2",
+            )
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut out = Vec::new();
+        let mut err = Vec::new();
+        relay_output(
+            &mut child,
+            false,
+            Some(ErrorFormat::Human),
+            None,
+            None,
+            &mut out,
+            &mut err,
+        )
+        .unwrap();
+        assert!(child.wait().unwrap().success());
+
+        assert_eq!(out, "out line\n".repeat(20000).into_bytes());
+        assert_eq!(err, "error: 型が不一致です\n".repeat(20000).into_bytes());
+    }
+
+    #[cfg(unix)]
+    #[cfg(feature = "bundled-ja")]
+    #[test]
+    fn test_relay_output_stress() {
+        // 標準エラー出力と標準出力に数 MB ずつ、交互に書き出す
+        let script = "for i in 1 2 3 4; do \
+            yes 'error: mismatched types' | head -n 50000 >&2; \
+            yes 'out line with some padding' | head -n 50000; \
+        done";
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(script)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut out = Vec::new();
+        let mut err = Vec::new();
+        relay_output(
+            &mut child,
+            true,
+            Some(ErrorFormat::Human),
+            None,
+            None,
+            &mut out,
+            &mut err,
+        )
+        .unwrap();
+        assert!(child.wait().unwrap().success());
+
+        assert_eq!(out.len(), "out line with some padding\n".len() * 200000);
+        assert_eq!(err, "error: 型が不一致です\n".repeat(200000).into_bytes());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_passthrough_status() {
+        // exec を使えないプラットフォームでの実行方法（子プロセスの終了コードを引き継ぐ）
+        let mut command = Command::new("sh");
+        command.args(["-c", "exit 3"]);
+        assert_eq!(passthrough_status(&mut command).unwrap(), 3);
+
+        let mut command = Command::new("rustc-ja-wrapper-test-no-such-command");
+        assert!(matches!(
+            passthrough_status(&mut command),
+            Err(WrapperError::Spawn(_))
+        ));
+    }
+
+    #[test]
+    fn test_run() {
+        // コマンドを起動できない場合（変換する場合も、そのまま実行する場合も）
+        for args in [
+            &[
+                "rustc-ja-wrapper-test-no-such-command",
+                "--error-format=json",
+            ][..],
+            &["rustc-ja-wrapper-test-no-such-command", "-vV"][..],
+        ] {
+            let args = args.iter().map(|a| a.into()).collect();
+            let err = run(args, true).unwrap_err();
+            assert!(matches!(err, WrapperError::Spawn(_)), "{err:?}");
+            assert!(
+                err.to_string().starts_with("Failed to spawn command: "),
+                "{err}"
+            );
+        }
+
+        // コマンドが指定されず、標準入力が端末の場合は、使い方を表示して 1 を返す
+        assert_eq!(run(Vec::new(), true).unwrap(), 1);
+
+        // ラッパー自身のオプション
+        assert_eq!(run(vec!["--version".into()], true).unwrap(), 0);
+    }
+
+    #[cfg(unix)]
+    #[cfg(feature = "bundled-ja")]
+    #[test]
+    fn test_relay_output_timeout() {
+        // 制限時間を過ぎたら止め、それまでの標準エラー出力を翻訳して書き出す
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg("echo 'error: mismatched types' >&2; exec sleep 10")
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let start = std::time::Instant::now();
+        let mut out = Vec::new();
+        let mut err = Vec::new();
+        let timed_out = relay_output(
+            &mut child,
+            false,
+            Some(ErrorFormat::Human),
+            None,
+            Some(std::time::Duration::from_millis(300)),
+            &mut out,
+            &mut err,
+        )
+        .unwrap();
+        assert!(timed_out);
+        assert!(start.elapsed() < std::time::Duration::from_secs(5));
+        assert!(!child.wait().unwrap().success());
+        assert_eq!(String::from_utf8(err).unwrap(), "error: 型が不一致です\n");
+
+        // 孫プロセスがパイプを開いたままでも、制限時間を過ぎたら待たずに戻る
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg("echo out; echo 'error: mismatched types' >&2; sleep 10 & sleep 10")
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let start = std::time::Instant::now();
+        let mut out = Vec::new();
+        let mut err = Vec::new();
+        let timed_out = relay_output(
+            &mut child,
+            false,
+            Some(ErrorFormat::Human),
+            None,
+            Some(std::time::Duration::from_millis(300)),
+            &mut out,
+            &mut err,
+        )
+        .unwrap();
+        assert!(timed_out);
+        assert!(
+            start.elapsed() < std::time::Duration::from_secs(2),
+            "{:?}",
+            start.elapsed()
+        );
+        assert_eq!(String::from_utf8(out).unwrap(), "out\n");
+        assert_eq!(String::from_utf8(err).unwrap(), "error: 型が不一致です\n");
+
+        // 制限時間内に終了すれば止めない
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg("exit 3")
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let timed_out = relay_output(
+            &mut child,
+            false,
+            None,
+            None,
+            Some(std::time::Duration::from_secs(10)),
+            io::sink(),
+            io::sink(),
+        )
+        .unwrap();
+        assert!(!timed_out);
+        assert_eq!(exit_code(&child.wait().unwrap()), 3);
+    }
+
+    #[test]
+    fn test_timeout_duration() {
+        assert_eq!(
+            timeout_duration("2"),
+            Some(std::time::Duration::from_secs(2))
+        );
+        assert_eq!(
+            timeout_duration(" 0.5 "),
+            Some(std::time::Duration::from_millis(500))
+        );
+        for value in ["", "0", "-1", "abc", "inf", "NaN"] {
+            assert_eq!(timeout_duration(value), None, "{value}");
+        }
+    }
+
+    #[test]
+    fn test_relay_output_disabled() {
+        // 翻訳しない場合は、改行コードや不正な UTF-8 も含めてそのまま書き出す
+        let expected =
+            b"error: mismatched types\r\n{\"message\":\"mismatched types\"}\n\xff\xfe no newline";
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(r#"printf 'error: mismatched types\r\n{"message":"mismatched types"}\n\377\376 no newline' >&2; exit 3"#)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut out = Vec::new();
+        let mut err = Vec::new();
+        relay_output(&mut child, false, None, None, None, &mut out, &mut err).unwrap();
+        assert_eq!(exit_code(&child.wait().unwrap()), 3);
+        assert!(out.is_empty());
+        assert_eq!(err, expected);
+    }
+
+    #[cfg(feature = "bundled-ja")]
+    #[test]
+    fn test_stream_stderr_dry_run() {
+        let diagnostic = r#"{"$message_type":"diagnostic","message":"mismatched types","code":null,"level":"error","spans":[{"label":"expected `i32`, found `&str`"}],"children":[],"rendered":"error: mismatched types\n"}"#;
+        let input = format!("{diagnostic}\nnot a json line\n");
+
+        // 出力は変換せず、翻訳の前後の組を報告する
+        let mut out = Vec::new();
+        let mut report = Vec::new();
+        stream_stderr(
+            input.as_bytes(),
+            &mut out,
+            ErrorFormat::Json,
+            Some(&mut report),
+        )
+        .unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), input);
+        assert_eq!(
+            String::from_utf8(report).unwrap(),
+            concat!(
+                "\"mismatched types\" → \"型が不一致です\"\n",
+                "\"expected `i32`, found `&str`\" → \"`i32`を期待したが、`&str`が見つかった\"\n",
+            )
+        );
+
+        let input = "error[E0308]: mismatched types\r\n --> src/main.rs:2:18\r\n";
+        let mut out = Vec::new();
+        let mut report = Vec::new();
+        stream_stderr(
+            input.as_bytes(),
+            &mut out,
+            ErrorFormat::Human,
+            Some(&mut report),
+        )
+        .unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), input);
+        assert_eq!(
+            String::from_utf8(report).unwrap(),
+            "\"error[E0308]: mismatched types\" → \"error[E0308]: 型が不一致です\"\n"
+        );
+    }
+
+    #[cfg(feature = "bundled-ja")]
+    #[test]
+    fn test_stream_stderr() {
+        let diagnostic = r#"{"$message_type":"diagnostic","message":"mismatched types","code":null,"level":"error","spans":[],"children":[],"rendered":"error: mismatched types\n"}"#;
+        let input = format!("{diagnostic}\nnot a json line\n{diagnostic}");
+
+        // 3 バイトずつ届いても、行の途中で変換しない
+        let reader = ChunkedReader {
+            data: input.as_bytes(),
+            chunk: 3,
+        };
+        let mut out = Vec::new();
+        stream_stderr(
+            io::BufReader::with_capacity(4, reader),
+            &mut out,
+            ErrorFormat::Json,
+            None,
+        )
+        .unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.split('\n').collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].contains("\"message\":\"型が不一致です\""));
+        assert_eq!(lines[1], "not a json line");
+        assert!(lines[2].contains("\"message\":\"型が不一致です\""));
+
+        let input = "error[E0308]: mismatched types\r\n --> src/main.rs:2:18\r\n";
+        let reader = ChunkedReader {
+            data: input.as_bytes(),
+            chunk: 5,
+        };
+        let mut out = Vec::new();
+        stream_stderr(
+            io::BufReader::new(reader),
+            &mut out,
+            ErrorFormat::Human,
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "error[E0308]: 型が不一致です\r\n --> src/main.rs:2:18\r\n"
+        );
+    }
+
+    #[test]
+    fn test_convert_json_error_format_unchanged_passthrough() {
+        // 診断でない行や、翻訳するものがない診断は、キーの順序や空白もそのまま残す
+        let data = concat!(
+            r#"{"$message_type":"artifact", "artifact":"/tmp/libfoo.rmeta","emit":"metadata"}"#,
+            "\n",
+            r#"{ "rendered": "error: zzz\n", "message": "zzz", "$message_type": "diagnostic", "spans": [], "children": [] }"#,
+        );
+        assert_eq!(
+            convert_json_error_format(data.as_bytes().to_vec()),
+            data.as_bytes()
+        );
+    }
+
+    #[cfg(feature = "bundled-ja")]
+    #[test]
+    fn test_convert_json_error_format_doctest() {
+        // doctest の診断は "src/lib.rs - foo (line 12)" のような合成ファイル名を持つ
+        let line = serde_json::json!({
+            "$message_type": "diagnostic",
+            "message": "mismatched types",
+            "code": { "code": "E0308", "explanation": null },
+            "level": "error",
+            "spans": [{
+                "file_name": "src/lib.rs - foo (line 12)",
+                "line_start": 3,
+                "line_end": 3,
+                "is_primary": true,
+                "label": "expected `i32`, found `&str`",
+            }],
+            "children": [],
+            "rendered": "error[E0308]: mismatched types\n --> src/lib.rs - foo (line 12):3:14\n  |\n3 | let x: i32 = \"a\";\n  |        ---   ^^^ expected `i32`, found `&str`\n",
+        })
+        .to_string();
+
+        let converted = convert_json_error_format(line.into_bytes());
+        let json: serde_json::Value = serde_json::from_slice(&converted).unwrap();
+        assert_eq!(json["message"], "型が不一致です");
+        assert_eq!(json["spans"][0]["file_name"], "src/lib.rs - foo (line 12)");
+        assert_eq!(
+            json["spans"][0]["label"],
+            "`i32`を期待したが、`&str`が見つかった"
+        );
+        assert_eq!(
+            json["rendered"],
+            "error[E0308]: 型が不一致です\n --> src/lib.rs - foo (line 12):3:14\n  |\n3 | let x: i32 = \"a\";\n  |        ---   ^^^ `i32`を期待したが、`&str`が見つかった\n"
+        );
+    }
+
+    #[cfg(feature = "bundled-ja")]
+    #[test]
+    fn test_embed_original() {
+        let original = serde_json::json!({
+            "$message_type": "diagnostic",
+            "message": "mismatched types",
+            "spans": [],
+            "children": [],
+            "rendered": "error[E0308]: mismatched types\n",
+        });
+        let translator = Translator::new(TRANSLATE_LIST.clone()).with_embed_original();
+        let embedded = rustc_ja_wrapper::convert_json_error_line(original.clone(), &translator);
+
+        assert_eq!(embedded["message"], "型が不一致です");
+        assert_eq!(embedded["rendered"], "error[E0308]: 型が不一致です\n");
+        assert_eq!(embedded["original"], original);
+    }
+
+    #[cfg(feature = "bundled-ja")]
+    #[test]
+    fn test_convert_json_error_format_main_not_found() {
+        let line = serde_json::json!({
+            "$message_type": "diagnostic",
+            "message": "`main` function not found in crate `foo`",
+            "code": { "code": "E0601", "explanation": null },
+            "level": "error",
+            "spans": [{
+                "file_name": "src/main.rs",
+                "is_primary": true,
+                "label": "consider adding a `main` function to `src/main.rs`",
+            }],
+            "children": [],
+            "rendered": "error[E0601]: `main` function not found in crate `foo`\n --> src/main.rs:3:2\n  |\n3 | }\n  |  ^ consider adding a `main` function to `src/main.rs`\n",
+        })
+        .to_string();
+
+        let converted = convert_json_error_format(line.into_bytes());
+        let json: serde_json::Value = serde_json::from_slice(&converted).unwrap();
+        assert_eq!(json["message"], "クレート`foo`に`main`関数が見つかりません");
+        assert_eq!(
+            json["spans"][0]["label"],
+            "`src/main.rs`に`main`関数を追加することを検討してください"
+        );
+        assert_eq!(
+            json["rendered"],
+            "error[E0601]: クレート`foo`に`main`関数が見つかりません\n --> src/main.rs:3:2\n  |\n3 | }\n  |  ^ `src/main.rs`に`main`関数を追加することを検討してください\n"
+        );
+    }
+
+    #[cfg(feature = "bundled-ja")]
+    #[test]
+    fn test_convert_json_error_format_derive_macro() {
+        let line = serde_json::json!({
+            "$message_type": "diagnostic",
+            "message": "cannot find derive macro `Serialize` in this scope",
+            "code": null,
+            "level": "error",
+            "spans": [{
+                "file_name": "src/main.rs",
+                "is_primary": true,
+                "label": null,
+            }],
+            "children": [],
+            "rendered": "error: cannot find derive macro `Serialize` in this scope\n --> src/main.rs:1:10\n  |\n1 | #[derive(Serialize)]\n  |          ^^^^^^^^^\n",
+        })
+        .to_string();
+
+        let converted = convert_json_error_format(line.into_bytes());
+        let json: serde_json::Value = serde_json::from_slice(&converted).unwrap();
+        assert_eq!(
+            json["message"],
+            "このスコープにderiveマクロ`Serialize`が見つかりません"
+        );
+        assert_eq!(json["spans"][0]["label"], serde_json::Value::Null);
+        // 属性のソースはそのまま残す
+        assert_eq!(
+            json["rendered"],
+            "error: このスコープにderiveマクロ`Serialize`が見つかりません\n --> src/main.rs:1:10\n  |\n1 | #[derive(Serialize)]\n  |          ^^^^^^^^^\n"
+        );
+
+        assert_eq!(
+            translate_message(
+                "unexpected `cfg` condition name: `tokio_unstable`",
+                &TRANSLATE_LIST
+            ),
+            "予期しない`cfg`の条件名です: `tokio_unstable`"
+        );
+        assert_eq!(
+            translate_message(
+                "the item is gated behind the `derive` feature",
+                &TRANSLATE_LIST
+            ),
+            "この要素は`derive`フィーチャーが有効なときのみ使えます"
+        );
+    }
+
+    #[test]
+    fn test_detect_error_format_separator() {
+        let args: Vec<std::ffi::OsString> =
+            vec!["src/main.rs".into(), "--error-format=json".into()];
+        assert_eq!(detect_error_format(&args), ErrorFormat::Json);
+
+        // "--" 以降はコンパイラのオプションではない
+        let args: Vec<std::ffi::OsString> = vec![
+            "src/main.rs".into(),
+            "--".into(),
+            "--error-format=json".into(),
+        ];
+        assert_eq!(detect_error_format(&args), ErrorFormat::Human);
+    }
+
+    #[cfg(feature = "bundled-ja")]
+    #[test]
+    fn test_convert_json_error_format_overflow_evaluating() {
+        let line = serde_json::json!({
+            "$message_type": "diagnostic",
+            "message": "overflow evaluating the requirement `Wrapper<Wrapper<u8>>: Trait`",
+            "code": { "code": "E0275", "explanation": null },
+            "level": "error",
+            "spans": [],
+            "children": [
                 {
-                    "label": "移動後の値をここで借用",
+                    "message": "consider increasing the recursion limit by adding a `#![recursion_limit = \"256\"]` attribute to your crate (`foo`)",
+                    "level": "help",
+                    "spans": [],
+                    "children": [],
+                    "rendered": null,
                 },
-            ],
-            "children": [
                 {
-                    "message": "複製コストが許容できるなら、クローンすることを検討してください",
-                    "spans": [
-                        {
-                            "label": "hello",
-                        }
-                    ],
+                    "message": "required for `Wrapper<u8>` to implement `Trait`",
+                    "level": "note",
+                    "spans": [],
+                    "children": [],
+                    "rendered": null,
                 },
             ],
-            "rendered": "移動された値の借用: `s1`\nここで値を移動\n移動後の値をここで借用\n複製コストが許容できるなら、クローンすることを検討してください",
-        });
-        assert_eq!(translated.get("message"), expected_json.get("message"));
-        assert_eq!(translated.get("spans"), expected_json.get("spans"));
-        assert_eq!(translated.get("children"), expected_json.get("children"));
-        assert_eq!(translated.get("rendered"), expected_json.get("rendered"));
+            "rendered": "error[E0275]: overflow evaluating the requirement `Wrapper<Wrapper<u8>>: Trait`\n  |\n  = help: consider increasing the recursion limit by adding a `#![recursion_limit = \"256\"]` attribute to your crate (`foo`)\nnote: required for `Wrapper<u8>` to implement `Trait`\n",
+        })
+        .to_string();
+
+        let converted = convert_json_error_format(line.into_bytes());
+        let json: serde_json::Value = serde_json::from_slice(&converted).unwrap();
+        assert_eq!(
+            json["message"],
+            "要件`Wrapper<Wrapper<u8>>: Trait`の評価中にオーバーフローしました"
+        );
+        assert_eq!(
+            json["children"][0]["message"],
+            "クレート（`foo`）に`#![recursion_limit = \"256\"]`属性を追加して、再帰制限を増やすことを検討してください"
+        );
+        assert_eq!(
+            json["children"][1]["message"],
+            "`Wrapper<u8>`が`Trait`を実装するために必要です"
+        );
+        assert_eq!(
+            json["rendered"],
+            "error[E0275]: 要件`Wrapper<Wrapper<u8>>: Trait`の評価中にオーバーフローしました\n  |\n  = help: クレート（`foo`）に`#![recursion_limit = \"256\"]`属性を追加して、再帰制限を増やすことを検討してください\nnote: `Wrapper<u8>`が`Trait`を実装するために必要です\n"
+        );
+    }
+
+    #[test]
+    fn test_po_json_round_trip() {
+        let po = rustc_ja_wrapper::po::to_po(&TRANSLATE_LIST);
+        let json = entries_to_json(&rustc_ja_wrapper::po::from_po(&po));
+        let round_trip =
+            rustc_ja_wrapper::file::parse_translations(std::str::from_utf8(&json).unwrap(), None)
+                .unwrap();
+
+        // "exact" や "plural" なども含めて、元のエントリと同じになる
+        assert_eq!(round_trip.len(), TRANSLATE_LIST.len());
+        assert_eq!(
+            entries_to_json(&round_trip),
+            entries_to_json(&TRANSLATE_LIST)
+        );
+    }
+
+    #[cfg(feature = "bundled-ja")]
+    #[test]
+    fn test_translate_message_associated_type() {
+        assert_eq!(
+            translate_message(
+                "the value of the associated type `Item` in `Iterator` must be specified",
+                &TRANSLATE_LIST
+            ),
+            "`Iterator`の関連型`Item`の値を指定する必要があります"
+        );
+        assert_eq!(
+            translate_message("associated type `Item` must be specified", &TRANSLATE_LIST),
+            "関連型`Item`を指定する必要があります"
+        );
+        // バッククォート内のジェネリック引数はそのまま残す
+        assert_eq!(
+            translate_message(
+                "specify the associated type: `Iterator<Item = Type>`",
+                &TRANSLATE_LIST
+            ),
+            "関連型を指定してください: `Iterator<Item = Type>`"
+        );
+        assert_eq!(
+            translate_message(
+                "missing generics for associated type `LendingIterator::Item`",
+                &TRANSLATE_LIST
+            ),
+            "関連型`LendingIterator::Item`のジェネリクスがありません"
+        );
+    }
+
+    #[test]
+    fn test_check_selfcheck_output() {
+        let translator = Translator::new(load_translations(SELFCHECK_TRANSLATIONS, None).0);
+        let translated = rustc_ja_wrapper::convert_json_error_format(
+            format!("{}\n", FAKE_DIAGNOSTIC).into_bytes(),
+            &translator,
+        );
+        assert_eq!(
+            check_selfcheck_output(&translated, Some(FAKE_EXIT_CODE)),
+            Ok(())
+        );
+
+        // 翻訳されていない、または終了コードが違う場合は失敗
+        assert!(check_selfcheck_output(FAKE_DIAGNOSTIC.as_bytes(), Some(FAKE_EXIT_CODE)).is_err());
+        assert!(check_selfcheck_output(&translated, Some(0)).is_err());
+        assert!(check_selfcheck_output(&translated, None).is_err());
+    }
+
+    #[test]
+    fn test_detect_error_format() {
+        let cases: &[(&[&str], ErrorFormat)] = &[
+            (&["src/main.rs"], ErrorFormat::Human),
+            (&["--error-format=json"], ErrorFormat::Json),
+            (&["--error-format", "json"], ErrorFormat::Json),
+            (&["--error-format=short"], ErrorFormat::Short),
+            (
+                &["--error-format", "short", "src/main.rs"],
+                ErrorFormat::Short,
+            ),
+            (
+                &["--error-format=json", "--error-format=human"],
+                ErrorFormat::Human,
+            ),
+            (&["--error-format=json,artifacts"], ErrorFormat::Json),
+            (&["--error-format", "artifacts,json"], ErrorFormat::Json),
+            (&["--json=diagnostic-rendered-ansi"], ErrorFormat::Json),
+            (&["--json", "artifacts", "src/main.rs"], ErrorFormat::Json),
+            (
+                &["--error-format=json", "--json=diagnostic-short,artifacts"],
+                ErrorFormat::Json,
+            ),
+            (&["--error-format=jsonl"], ErrorFormat::Human),
+        ];
+        for (args, expected) in cases {
+            let args: Vec<std::ffi::OsString> = args.iter().map(|a| a.into()).collect();
+            assert_eq!(detect_error_format(&args), *expected, "{:?}", args);
+            assert_eq!(
+                detects_json_format(&args),
+                *expected == ErrorFormat::Json,
+                "{:?}",
+                args
+            );
+        }
+    }
+
+    #[cfg(feature = "bundled-ja")]
+    #[test]
+    fn test_load_translate_list() {
+        let bundled =
+            rustc_ja_wrapper::file::parse_translations(BUNDLED_TRANSLATIONS, None).unwrap();
+        assert_eq!(
+            load_translate_list(BUNDLED_TRANSLATIONS, None, None)
+                .0
+                .len(),
+            bundled.len()
+        );
+
+        let dir = env::temp_dir();
+        let path = dir.join(format!("rustc-ja-wrapper-test-{}.json", std::process::id()));
+        std::fs::write(
+            &path,
+            r#"[
+                { "en": "mismatched types", "ja": "型が合いません" },
+                { "en": "an unknown message for the test", "ja": "テスト用の未知のメッセージ" }
+            ]"#,
+        )
+        .unwrap();
+        // 組み込みの翻訳データに重ねる
+        let (entries, warnings) = load_translate_list(BUNDLED_TRANSLATIONS, Some(&path), None);
+        assert!(warnings.is_empty());
+        assert_eq!(entries.len(), bundled.len() + 1);
+        assert_eq!(
+            translate_message("mismatched types", &entries),
+            "型が合いません"
+        );
+        assert_eq!(
+            translate_message("an unknown message for the test", &entries),
+            "テスト用の未知のメッセージ"
+        );
+        assert_eq!(
+            translate_message("value moved here", &entries),
+            translate_message("value moved here", &bundled)
+        );
+
+        // 不正なファイルは警告して組み込みの翻訳データを使う
+        std::fs::write(&path, "[{ \"en\": ").unwrap();
+        let (entries, warnings) = load_translate_list(BUNDLED_TRANSLATIONS, Some(&path), None);
+        assert_eq!(entries.len(), bundled.len());
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].starts_with(&format!(
+            "{}: failed to parse translations: ",
+            path.display()
+        )));
+
+        std::fs::remove_file(&path).unwrap();
+        let (entries, warnings) = load_translate_list(BUNDLED_TRANSLATIONS, Some(&path), None);
+        assert_eq!(entries.len(), bundled.len());
+        assert!(warnings[0].starts_with("failed to read "));
+    }
+
+    #[test]
+    fn test_load_translate_list_empty_en() {
+        let path = env::temp_dir().join(format!(
+            "rustc-ja-wrapper-test-{}-empty-en.json",
+            std::process::id()
+        ));
+        std::fs::write(&path, r#"[{ "en": "", "ja": "空" }]"#).unwrap();
+        let (entries, warnings) = load_translate_list(BUNDLED_TRANSLATIONS, Some(&path), None);
+        std::fs::remove_file(&path).unwrap();
+        assert!(entries.iter().all(|e| !e.en.is_empty()));
+        assert_eq!(
+            warnings,
+            [format!(
+                "{}: skipped an entry with an empty `en` (ja: \"空\")",
+                path.display()
+            )]
+        );
+
+        // 組み込みの翻訳データが不正な場合も警告する
+        let (entries, warnings) = load_translate_list("[", None, None);
+        assert!(entries.is_empty());
+        assert!(warnings[0].starts_with("bundled translations: failed to parse translations: "));
+    }
+
+    #[test]
+    fn test_load_translate_list_po() {
+        let path = env::temp_dir().join(format!("rustc-ja-wrapper-test-{}.po", std::process::id()));
+        std::fs::write(
+            &path,
+            r#"msgid ""
+msgstr ""
+"Content-Type: text/plain; charset=UTF-8\n"
+
+msgid "mismatched types"
+msgstr "型が合いません"
+
+#. 複数行のエントリ
+msgid ""
+"unused variable: "
+"`{$name}`"
+msgstr ""
+"使われていない変数: "
+"`{$name}`"
+"#,
+        )
+        .unwrap();
+        let (entries, warnings) = load_translate_list(BUNDLED_TRANSLATIONS, Some(&path), None);
+        std::fs::remove_file(&path).unwrap();
+        assert!(warnings.is_empty());
+        assert_eq!(
+            translate_message("mismatched types", &entries),
+            "型が合いません"
+        );
+        assert_eq!(
+            translate_message("unused variable: `x`", &entries),
+            "使われていない変数: `x`"
+        );
+    }
+
+    #[test]
+    fn test_load_translate_list_csv() {
+        let path =
+            env::temp_dir().join(format!("rustc-ja-wrapper-test-{}.csv", std::process::id()));
+        std::fs::write(
+            &path,
+            "en,ja\nmismatched types,型が合いません\n\"expected `{$ty1}`, found `{$ty2}`\",`{$ty2}`ではなく`{$ty1}`\nbroken\n",
+        )
+        .unwrap();
+        let (entries, warnings) = load_translate_list(BUNDLED_TRANSLATIONS, Some(&path), None);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            warnings,
+            [format!(
+                "{}: line 4: expected 2 fields, found 1 (skipped)",
+                path.display()
+            )]
+        );
+        assert_eq!(
+            translate_message("mismatched types", &entries),
+            "型が合いません"
+        );
+        assert_eq!(
+            translate_message("expected `i32`, found `&str`", &entries),
+            "`&str`ではなく`i32`"
+        );
+    }
+
+    #[test]
+    fn test_load_translate_list_dir() {
+        let dir = env::temp_dir().join(format!("rustc-ja-wrapper-test-{}-dir", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        // ファイル名の順に読み込むので、typeck.json の方が後になる
+        std::fs::write(
+            dir.join("typeck.json"),
+            r#"[
+                { "en": "mismatched types", "ja": "型が合いません" },
+                { "en": "expected `{$ty1}`, found `{$ty2}`", "ja": "`{$ty2}`ではなく`{$ty1}`" }
+            ]"#,
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("borrowck.json"),
+            r#"[
+                { "en": "mismatched types", "ja": "上書きされる翻訳" },
+                { "en": "value moved here", "ja": "値はここで移動しました" }
+            ]"#,
+        )
+        .unwrap();
+        std::fs::write(dir.join("notes.txt"), "not a translation file").unwrap();
+        let (entries, warnings) = load_translate_list(BUNDLED_TRANSLATIONS, Some(&dir), None);
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(warnings.is_empty(), "{warnings:?}");
+        assert_eq!(
+            translate_message("mismatched types", &entries),
+            "型が合いません"
+        );
+        assert_eq!(
+            translate_message("value moved here", &entries),
+            "値はここで移動しました"
+        );
+        assert_eq!(
+            translate_message("expected `i32`, found `&str`", &entries),
+            "`&str`ではなく`i32`"
+        );
+        // 英語文字列の長い順に並んでいる
+        assert!(entries.windows(2).all(|w| w[0].en.len() >= w[1].en.len()));
+        assert_eq!(
+            entries
+                .iter()
+                .filter(|e| e.en == "mismatched types")
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_format_dump() {
+        let bundled = r#"[
+            { "en": "mismatched types", "ja": "型が不一致です" },
+            { "en": "value moved here", "ja": "値はここで移動しました" }
+        ]"#;
+        let path = env::temp_dir().join(format!(
+            "rustc-ja-wrapper-test-{}-dump.json",
+            std::process::id()
+        ));
+        std::fs::write(
+            &path,
+            r#"[
+                { "en": "mismatched types", "ja": "型が合いません" },
+                { "en": "expected `{$ty1}`, found `{$ty2}`", "ja": "`{$ty1}`を期待したが、`{$ty2}`が見つかった" },
+                { "en": "", "ja": "空" }
+            ]"#,
+        )
+        .unwrap();
+        let (entries, warnings) = load_translate_list(bundled, Some(&path), None);
+        std::fs::remove_file(&path).unwrap();
+        let dump = format_dump(&entries, &warnings, "ja");
+        let lines: Vec<&str> = dump.lines().collect();
+        assert_eq!(lines[0], "3 entries");
+        assert!(
+            lines[1].starts_with("warning: ") && lines[1].contains("empty `en`"),
+            "{dump}"
+        );
+        // 重ねた翻訳で置き換え、英語文字列の長い順に並べる
+        assert_eq!(
+            &lines[2..],
+            [
+                "1. \"expected `{$ty1}`, found `{$ty2}`\" → \"`{$ty1}`を期待したが、`{$ty2}`が見つかった\"",
+                "2. \"mismatched types\" → \"型が合いません\"",
+                "3. \"value moved here\" → \"値はここで移動しました\"",
+            ]
+        );
+        assert!(
+            format_dump(&entries, &[], "en")
+                .contains("1. \"expected `{$ty1}`, found `{$ty2}`\" → (no translation)")
+        );
+    }
+
+    #[test]
+    fn test_bilingual_style() {
+        assert_eq!(bilingual_style("1"), Some(Bilingual::Inline));
+        assert_eq!(bilingual_style("inline"), Some(Bilingual::Inline));
+        assert_eq!(bilingual_style("Line"), Some(Bilingual::Line));
+        assert_eq!(bilingual_style("off"), None);
+    }
+
+    #[test]
+    fn test_select_locale() {
+        let available = ["ja", "ko"];
+        let select = |explicit, locale| select_locale(explicit, locale, &available);
+        assert_eq!(select(None, None), ("ja".to_string(), None));
+        assert_eq!(select(None, Some("ko_KR.UTF-8")), ("ko".to_string(), None));
+        assert_eq!(select(None, Some("C")), ("ja".to_string(), None));
+        assert_eq!(select(None, Some("en_US.UTF-8")), ("ja".to_string(), None));
+        // RUSTC_JA_LANG はロケールより優先する
+        assert_eq!(
+            select(Some("ko"), Some("ja_JP.UTF-8")),
+            ("ko".to_string(), None)
+        );
+        assert_eq!(select(Some(""), Some("ko_KR")), ("ko".to_string(), None));
+        // 指定した言語の組み込みの翻訳データがなければ警告する
+        let (locale, warning) = select(Some("fr"), Some("ko_KR"));
+        assert_eq!(locale, "ja");
+        assert_eq!(
+            warning.unwrap(),
+            "no bundled translations for \"fr\" (using \"ja\")"
+        );
+
+        assert_eq!(bundled_translations("ja"), BUNDLED_TRANSLATIONS);
+        assert_eq!(bundled_translations("fr"), BUNDLED_TRANSLATIONS);
+    }
+
+    #[test]
+    fn test_wrapper_info() {
+        assert_eq!(
+            wrapper_info("--version").unwrap(),
+            format!("rustc-ja-wrapper {}\n", env!("CARGO_PKG_VERSION"))
+        );
+        let help = wrapper_info("--help").unwrap();
+        assert!(help.starts_with(USAGE));
+        assert!(help.contains("RUSTC_JA_TRANSLATE "));
+        assert!(help.contains("RUSTC_JA_DEBUG_LOG"));
+
+        // コマンド名やコマンドへの引数はそのまま渡す
+        assert_eq!(wrapper_info("rustc"), None);
+        assert_eq!(wrapper_info("-vV"), None);
+    }
+
+    #[cfg(feature = "bundled-ja")]
+    #[test]
+    fn test_missing_log() {
+        let path = env::temp_dir().join(format!(
+            "rustc-ja-wrapper-test-{}-missing.log",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        let log = MissingLog::new(path.clone());
+        let translator =
+            Translator::new(TRANSLATE_LIST.clone()).with_missing(Box::new(move |m| log.record(m)));
+
+        let diagnostic = r#"{"$message_type":"diagnostic","message":"an unknown message","code":null,"level":"error","spans":[{"label":"value moved here"},{"label":"an unknown label"}],"children":[{"message":"an unknown message","spans":[]}],"rendered":null}"#;
+        let json: serde_json::Value = serde_json::from_str(diagnostic).unwrap();
+        let translated = translator.translate_json(&json);
+        assert_eq!(translated["spans"][0]["label"], "ここで値を移動しました");
+
+        // 翻訳できたものは記録せず、同じメッセージは一度だけ記録する
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "an unknown message\nan unknown label\n"
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(feature = "bundled-ja")]
+    #[test]
+    fn test_filter_json_lines() {
+        // cargo build --message-format=json の出力と、rustc の --error-format=json の出力
+        let input = concat!(
+            r#"{"reason":"compiler-message","package_id":"a 0.1.0","message":{"$message_type":"diagnostic","message":"mismatched types","code":{"code":"E0308","explanation":null},"level":"error","spans":[],"children":[],"rendered":"error[E0308]: mismatched types\n"}}"#,
+            "\n",
+            r#"{"$message_type":"diagnostic","message":"mismatched types","code":null,"level":"error","spans":[],"children":[],"rendered":"error[E0308]: mismatched types\n"}"#,
+            "\n",
+            r#"{"reason":"build-finished","success":false}"#,
+            "\n",
+        );
+        let expected = concat!(
+            r#"{"reason":"compiler-message","package_id":"a 0.1.0","message":{"$message_type":"diagnostic","message":"型が不一致です","code":{"code":"E0308","explanation":null},"level":"error","spans":[],"children":[],"rendered":"error[E0308]: 型が不一致です\n"}}"#,
+            "\n",
+            r#"{"$message_type":"diagnostic","message":"型が不一致です","code":null,"level":"error","spans":[],"children":[],"rendered":"error[E0308]: 型が不一致です\n"}"#,
+            "\n",
+            r#"{"reason":"build-finished","success":false}"#,
+            "\n",
+        );
+        let mut out = Vec::new();
+        filter_json_lines(input.as_bytes(), &mut out, None).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), expected);
+    }
+
+    #[cfg(feature = "bundled-ja")]
+    #[test]
+    fn test_run_without_command() {
+        let input = r#"{"$message_type":"diagnostic","message":"mismatched types","code":null,"level":"error","spans":[],"children":[],"rendered":"error: mismatched types\n"}"#;
+
+        // パイプで渡された場合は翻訳する
+        let mut out = Vec::new();
+        assert_eq!(run_without_command(input.as_bytes(), false, &mut out), 0);
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("\"message\":\"型が不一致です\""), "{out}");
+
+        // 端末の場合は使い方を表示して終了する（入力は読み込まない）
+        let mut out = Vec::new();
+        assert_eq!(run_without_command(input.as_bytes(), true, &mut out), 1);
+        assert!(out.is_empty());
+    }
+
+    #[cfg(feature = "bundled-ja")]
+    #[test]
+    fn test_diagnose() {
+        let bundled = diagnose(None, None, Some("rustc 1.0.0".to_string()));
+        assert!(bundled.entries > 0);
+        assert!(bundled.warnings.is_empty());
+        assert!(bundled.is_ok());
+        let report = bundled.to_string();
+        assert!(report.starts_with("translations: bundled\n"), "{report}");
+        assert!(report.contains(&format!("\nentries: {}\n", bundled.entries)));
+        assert!(report.ends_with("rustc: rustc 1.0.0\n"), "{report}");
+
+        let path = env::temp_dir().join(format!(
+            "rustc-ja-wrapper-test-{}-doctor.json",
+            std::process::id()
+        ));
+        std::fs::write(
+            &path,
+            r#"[{ "en": "rustc-ja-wrapper doctor test", "ja": "テスト" }, { "en": "", "ja": "空" }]"#,
+        )
+        .unwrap();
+        let report = diagnose(Some(&path), None, None);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(report.entries, bundled.entries + 1);
+        assert_eq!(
+            report.warnings,
+            [format!(
+                "{}: skipped an entry with an empty `en` (ja: \"空\")",
+                path.display()
+            )]
+        );
+        // rustc が見つからなければ失敗
+        assert!(!report.is_ok());
+        let text = report.to_string();
+        assert!(text.starts_with(&format!("translations: bundled + {}\n", path.display())));
+        assert!(text.contains(&format!("\nwarning: {}\n", report.warnings[0])));
+        assert!(text.ends_with("rustc: not found\n"), "{text}");
+    }
+
+    #[test]
+    fn test_doctor_without_bundled() {
+        // 組み込みの翻訳データがなければ、翻訳ファイルだけを表示する
+        let mut report = Doctor {
+            bundled: false,
+            translate: None,
+            entries: 0,
+            warnings: Vec::new(),
+            lang: DEFAULT_LANG.to_string(),
+            debug_log: None,
+            rustc: None,
+        };
+        assert!(report.to_string().starts_with("translations: none\n"));
+        report.translate = Some("translate.json".into());
+        assert!(
+            report
+                .to_string()
+                .starts_with("translations: translate.json\n")
+        );
+    }
+
+    #[cfg(feature = "bundled-ja")]
+    #[test]
+    fn test_reverse_json_lines() {
+        let translator = Translator::new(reverse_entries(&TRANSLATE_LIST));
+        let input = concat!(
+            r#"{"$message_type":"diagnostic","message":"型が不一致です","spans":[{"label":"`i32`を期待したが、`&str`が見つかった"}],"children":[],"rendered":"error[E0308]: 型が不一致です\n"}"#,
+            "\n",
+            r#"{"$message_type":"artifact","artifact":"a"}"#,
+            "\n",
+        );
+        let mut out = Vec::new();
+        reverse_json_lines(input.as_bytes(), &mut out, &translator).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+
+        let json: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(json["message"], "mismatched types");
+        assert_eq!(json["spans"][0]["label"], "expected `i32`, found `&str`");
+        assert_eq!(json["rendered"], "error[E0308]: mismatched types\n");
+        assert_eq!(lines[1], r#"{"$message_type":"artifact","artifact":"a"}"#);
+
+        // プレースホルダ 1 つ
+        assert_eq!(
+            translator.translate("変数が使われていません: `b`"),
+            "unused variable: `b`"
+        );
+
+        // "exact": false のエントリは、後に続く文字列があっても英語に戻す
+        assert_eq!(
+            translator.translate("移動された値の借用しました: `s1`"),
+            "borrow of moved value: `s1`"
+        );
+        let input = r#"{"$message_type":"diagnostic","message":"移動された値の借用しました: `s1`","spans":[],"children":[],"rendered":null}"#;
+        let mut out = Vec::new();
+        reverse_json_lines(input.as_bytes(), &mut out, &translator).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(json["message"], "borrow of moved value: `s1`");
+    }
+
+    #[test]
+    fn test_debug_log_path() {
+        assert_eq!(debug_log_path(None), None);
+        assert!(default_debug_log_path().starts_with(env::temp_dir()));
+        assert_eq!(
+            debug_log_path(Some("".into())),
+            Some(default_debug_log_path())
+        );
+        assert_eq!(
+            debug_log_path(Some("1".into())),
+            Some(default_debug_log_path())
+        );
+        assert_eq!(
+            debug_log_path(Some("debug.log".into())),
+            Some(std::path::PathBuf::from("debug.log"))
+        );
+    }
+
+    #[test]
+    fn test_append_debug_log_to() {
+        let path = env::temp_dir().join(format!(
+            "rustc-ja-wrapper-test-{}-debug.log",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+
+        // ログファイルが指定されていなければ書き込まない
+        append_debug_log_to(None, "ignored");
+        assert!(!path.exists());
+
+        append_debug_log_to(Some(&path), "RESPONSE");
+        append_debug_log_to(Some(&path), "error: mismatched types");
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "RESPONSE\nerror: mismatched types\n"
+        );
+        std::fs::remove_file(&path).unwrap();
     }
 }
@@ -0,0 +1,424 @@
+//! 翻訳データと gettext の `.po` 形式の相互変換
+//!
+//! `en` を msgid、`ja` を msgstr として出力する。
+//! プレースホルダ `{$name}` は python-brace-format 形式の `{name}` に変換し、
+//! 文字としての `{` と `}` は `{{` と `}}` にする（そのエントリには `#, python-brace-format` を付ける）。
+//! 読み込み時は `#, python-brace-format` が付いたエントリのみ、逆の変換をする。
+//!
+//! 照合に使う他の項目も、読み込み直せるように書き出す。
+//! - `context` は msgctxt
+//! - `exact`、`regex`、`ignore_case`、`match_in_backticks` は `#, exact`（`false` なら `#, no-exact`）などのフラグ
+//! - `category`、`priority`、`min_version`、`max_version` は `#. priority: 1` のようなコメント
+//! - `plural` は msgid_plural と `msgstr[1]`
+//!
+//! 日本語以外の翻訳は書き出さない（`unexported` で警告を作る）。
+
+use crate::{PluralForm, TranslateEntry};
+
+/// python-brace-format を表すフラグ
+const BRACE_FORMAT_FLAG: &str = "python-brace-format";
+
+/// 翻訳データを `.po` 形式にする
+pub fn to_po(entries: &[TranslateEntry]) -> String {
+    let mut po = String::new();
+    po.push_str("msgid \"\"\n");
+    po.push_str("msgstr \"\"\n");
+    po.push_str("\"Content-Type: text/plain; charset=UTF-8\\n\"\n");
+    po.push_str("\"Plural-Forms: nplurals=2; plural=(n != 1);\\n\"\n");
+    for entry in entries {
+        po.push('\n');
+        for (key, value) in [
+            ("category", entry.category.clone()),
+            (
+                "priority",
+                (entry.priority != 0).then(|| entry.priority.to_string()),
+            ),
+            ("min_version", entry.min_version.clone()),
+            ("max_version", entry.max_version.clone()),
+        ] {
+            if let Some(value) = value {
+                po.push_str(&format!("#. {}: {}\n", key, value));
+            }
+        }
+        let mut flags = vec![BRACE_FORMAT_FLAG];
+        match entry.exact {
+            Some(true) => flags.push("exact"),
+            Some(false) => flags.push("no-exact"),
+            None => {}
+        }
+        for (flag, on) in [
+            ("regex", entry.regex),
+            ("ignore-case", entry.ignore_case),
+            ("match-in-backticks", entry.match_in_backticks),
+        ] {
+            if on {
+                flags.push(flag);
+            }
+        }
+        po.push_str(&format!("#, {}\n", flags.join(", ")));
+        if let Some(context) = &entry.context {
+            po.push_str(&format!("msgctxt {}\n", quote(context)));
+        }
+        po.push_str(&format!("msgid {}\n", quote(&to_brace_format(&entry.en))));
+        match &entry.plural {
+            Some(plural) => {
+                let en = plural.en.as_deref().unwrap_or(&entry.en);
+                let ja = plural.texts.get("ja").unwrap_or(&entry.ja);
+                po.push_str(&format!("msgid_plural {}\n", quote(&to_brace_format(en))));
+                po.push_str(&format!(
+                    "msgstr[0] {}\n",
+                    quote(&to_brace_format(&entry.ja))
+                ));
+                po.push_str(&format!("msgstr[1] {}\n", quote(&to_brace_format(ja))));
+            }
+            None => po.push_str(&format!("msgstr {}\n", quote(&to_brace_format(&entry.ja)))),
+        }
+    }
+    po
+}
+
+/// `to_po` で書き出さない翻訳（日本語以外の言語）があるエントリについて、警告を返す
+pub fn unexported(entries: &[TranslateEntry]) -> Vec<String> {
+    entries
+        .iter()
+        .filter_map(|e| {
+            let mut langs: Vec<&str> = e.langs.keys().map(|l| l.as_str()).collect();
+            if let Some(plural) = &e.plural {
+                langs.extend(
+                    plural
+                        .texts
+                        .keys()
+                        .map(|l| l.as_str())
+                        .filter(|l| *l != "ja"),
+                );
+            }
+            langs.sort();
+            langs.dedup();
+            (!langs.is_empty()).then(|| {
+                format!(
+                    "{:?}: translations for {} are not exported",
+                    e.en,
+                    langs.join(", ")
+                )
+            })
+        })
+        .collect()
+}
+
+// 読み込み中のエントリ
+#[derive(Default)]
+struct PoEntry {
+    flags: Vec<String>,
+    comments: Vec<(String, String)>,
+    msgctxt: Option<String>,
+    msgid: String,
+    msgid_plural: Option<String>,
+    msgstr: Vec<String>,
+}
+
+impl PoEntry {
+    // 翻訳データのエントリにする（ヘッダと未翻訳のエントリは None）
+    fn into_entry(self) -> Option<TranslateEntry> {
+        let ja = self.msgstr.first().filter(|s| !s.is_empty())?;
+        if self.msgid.is_empty() {
+            return None;
+        }
+        let has_flag = |flag: &str| self.flags.iter().any(|f| f == flag);
+        let text = |s: &str| {
+            if has_flag(BRACE_FORMAT_FLAG) {
+                from_brace_format(s)
+            } else {
+                s.to_string()
+            }
+        };
+        let comment = |key: &str| {
+            self.comments
+                .iter()
+                .rev()
+                .find(|(k, _)| k == key)
+                .map(|(_, v)| v.clone())
+        };
+        let en = text(&self.msgid);
+        let ja = text(ja);
+        let plural = self.msgid_plural.as_ref().map(|msgid_plural| {
+            let plural_en = text(msgid_plural);
+            let plural_ja = self
+                .msgstr
+                .get(1)
+                .filter(|s| !s.is_empty())
+                .map(|s| text(s));
+            PluralForm {
+                en: (plural_en != en).then_some(plural_en),
+                texts: plural_ja
+                    .filter(|p| *p != ja)
+                    .map(|p| ("ja".to_string(), p))
+                    .into_iter()
+                    .collect(),
+            }
+        });
+        Some(TranslateEntry {
+            exact: if has_flag("exact") {
+                Some(true)
+            } else if has_flag("no-exact") {
+                Some(false)
+            } else {
+                None
+            },
+            regex: has_flag("regex"),
+            ignore_case: has_flag("ignore-case"),
+            match_in_backticks: has_flag("match-in-backticks"),
+            context: self.msgctxt.clone(),
+            category: comment("category"),
+            priority: comment("priority")
+                .and_then(|p| p.parse().ok())
+                .unwrap_or(0),
+            min_version: comment("min_version"),
+            max_version: comment("max_version"),
+            plural,
+            en,
+            ja,
+            ..Default::default()
+        })
+    }
+}
+
+/// `.po` 形式を読み込んで翻訳データにする
+/// ヘッダ（msgid が空のエントリ）と、msgstr が空（未翻訳）のエントリは読み飛ばす
+pub fn from_po(po: &str) -> Vec<TranslateEntry> {
+    // 読み込み中の項目
+    #[derive(PartialEq)]
+    enum Field {
+        None,
+        Msgctxt,
+        Msgid,
+        MsgidPlural,
+        Msgstr(usize),
+    }
+
+    let mut entries = Vec::new();
+    let mut entry = PoEntry::default();
+    let mut field = Field::None;
+
+    // msgstr まで読み込んだエントリがあれば、次のエントリを読み始める前に追加する
+    let mut next_entry = |entry: &mut PoEntry, field: &mut Field| {
+        if matches!(field, Field::Msgstr(_)) {
+            entries.extend(std::mem::take(entry).into_entry());
+            *field = Field::None;
+        }
+    };
+
+    for line in po.lines() {
+        let line = line.trim();
+        if let Some(flags) = line.strip_prefix("#,") {
+            next_entry(&mut entry, &mut field);
+            entry
+                .flags
+                .extend(flags.split(',').map(|f| f.trim().to_string()));
+        } else if let Some(comment) = line.strip_prefix("#.") {
+            next_entry(&mut entry, &mut field);
+            if let Some((key, value)) = comment.split_once(':') {
+                entry
+                    .comments
+                    .push((key.trim().to_string(), value.trim().to_string()));
+            }
+        } else if line.starts_with('#') || line.is_empty() {
+            continue;
+        } else if let Some(rest) = line.strip_prefix("msgctxt ") {
+            next_entry(&mut entry, &mut field);
+            field = Field::Msgctxt;
+            entry.msgctxt = Some(unquote(rest));
+        } else if let Some(rest) = line.strip_prefix("msgid_plural ") {
+            field = Field::MsgidPlural;
+            entry.msgid_plural = Some(unquote(rest));
+        } else if let Some(rest) = line.strip_prefix("msgid ") {
+            next_entry(&mut entry, &mut field);
+            field = Field::Msgid;
+            entry.msgid.push_str(&unquote(rest));
+        } else if let Some(rest) = line.strip_prefix("msgstr") {
+            // "msgstr ..." か "msgstr[n] ..."
+            let (index, rest) = match rest.strip_prefix('[').and_then(|r| r.split_once(']')) {
+                Some((n, rest)) => (n.trim().parse().unwrap_or(0), rest),
+                None => (0, rest),
+            };
+            if entry.msgstr.len() <= index {
+                entry.msgstr.resize(index + 1, String::new());
+            }
+            entry.msgstr[index].push_str(&unquote(rest));
+            field = Field::Msgstr(index);
+        } else if line.starts_with('"') {
+            // 複数行にわたる文字列の続き
+            let text = unquote(line);
+            match field {
+                Field::Msgctxt => entry.msgctxt.get_or_insert_default().push_str(&text),
+                Field::Msgid => entry.msgid.push_str(&text),
+                Field::MsgidPlural => entry.msgid_plural.get_or_insert_default().push_str(&text),
+                Field::Msgstr(index) => entry.msgstr[index].push_str(&text),
+                Field::None => {}
+            }
+        }
+    }
+    entries.extend(entry.into_entry());
+    entries
+}
+
+// "{$name}" を "{name}" に、文字としての "{" と "}" を "{{" と "}}" にする
+fn to_brace_format(s: &str) -> String {
+    let mut out = String::new();
+    let mut rest = s;
+    while let Some(c) = rest.chars().next() {
+        if let Some(name) = placeholder_at(rest) {
+            out.push_str(&format!("{{{}}}", name));
+            rest = &rest[name.len() + 3..];
+            continue;
+        }
+        match c {
+            '{' => out.push_str("{{"),
+            '}' => out.push_str("}}"),
+            _ => out.push(c),
+        }
+        rest = &rest[c.len_utf8()..];
+    }
+    out
+}
+
+// to_brace_format の逆変換
+fn from_brace_format(s: &str) -> String {
+    let mut out = String::new();
+    let mut rest = s;
+    while let Some(c) = rest.chars().next() {
+        if let Some(r) = rest.strip_prefix("{{") {
+            out.push('{');
+            rest = r;
+        } else if let Some(r) = rest.strip_prefix("}}") {
+            out.push('}');
+            rest = r;
+        } else if let Some(name) = brace_placeholder_at(rest) {
+            out.push_str(&format!("{{${}}}", name));
+            rest = &rest[name.len() + 2..];
+        } else {
+            out.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    out
+}
+
+// 先頭が "{$name}" であれば name を返す
+fn placeholder_at(s: &str) -> Option<&str> {
+    let name = s.strip_prefix("{$")?.split('}').next()?;
+    is_placeholder_name(name, s.len() - 3).then_some(name)
+}
+
+// 先頭が "{name}" であれば name を返す
+fn brace_placeholder_at(s: &str) -> Option<&str> {
+    let name = s.strip_prefix('{')?.split('}').next()?;
+    is_placeholder_name(name, s.len() - 2).then_some(name)
+}
+
+// プレースホルダ名として正しく、閉じ括弧まで収まっているか
+fn is_placeholder_name(name: &str, max_len: usize) -> bool {
+    !name.is_empty()
+        && name.len() <= max_len
+        && name.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
+
+// `.po` の文字列リテラルにする
+fn quote(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            _ => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+// `.po` の文字列リテラルを読み込む
+fn unquote(s: &str) -> String {
+    let s = s.trim();
+    let s = s.strip_prefix('"').unwrap_or(s);
+    let s = s.strip_suffix('"').unwrap_or(s);
+    let mut out = String::new();
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some(c) => out.push(c),
+            None => {}
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_po_round_trip() {
+        let json_str = r#"[
+            { "en": "mismatched types", "ja": "型が不一致です" },
+            { "en": "expected `{$ty1}`, found `{$ty2}`", "ja": "`{$ty1}`を期待したが、`{$ty2}`が見つかった" },
+            { "en": "expected one of `{`, found \"x\"", "ja": "`{`を期待したが、\"x\"が見つかった" },
+            { "en": "first line\nsecond\tline \\ end", "ja": "1行目\n2行目" }
+        ]"#;
+        let entries: Vec<TranslateEntry> = serde_json::from_str(json_str).unwrap();
+
+        let po = to_po(&entries);
+        assert!(po.contains("msgid \"expected `{ty1}`, found `{ty2}`\"\n"));
+        assert!(po.contains("msgid \"expected one of `{{`, found \\\"x\\\"\"\n"));
+
+        let round_trip = from_po(&po);
+        assert_eq!(round_trip.len(), entries.len());
+        for (a, b) in entries.iter().zip(&round_trip) {
+            assert_eq!(a.en, b.en);
+            assert_eq!(a.ja, b.ja);
+        }
+    }
+
+    #[test]
+    fn test_po_round_trip_fields() {
+        let json_str = r#"[
+            { "en": "borrow of moved value", "ja": "移動された値の借用しました", "exact": false, "category": "borrow" },
+            { "en": "value moved here", "ja": "ここで値を移動しました", "exact": true, "priority": -2 },
+            { "en": "expected", "ja": "期待される", "context": "mismatched types" },
+            { "en": "unused variable: `(\\w+)`", "ja": "変数が使われていません: `{$1}`", "regex": true },
+            { "en": "Mismatched Types", "ja": "型が不一致です", "ignore_case": true, "match_in_backticks": true },
+            { "en": "new message", "ja": "新しいメッセージ", "min_version": "1.80", "max_version": "1.85.1" },
+            { "en": "{$count} warning emitted", "ja": "{$count}件の警告が出ました", "plural": { "en": "{$count} warnings emitted" } },
+            { "en": "{$count} error", "ja": "{$count}個のエラー", "plural": { "ja": "{$count}個のエラーたち" } }
+        ]"#;
+        let entries: Vec<TranslateEntry> = serde_json::from_str(json_str).unwrap();
+        let po = to_po(&entries);
+        assert!(po.contains("#, python-brace-format, no-exact\nmsgid \"borrow of moved value\"\n"));
+        assert!(po.contains("msgctxt \"mismatched types\"\nmsgid \"expected\"\n"));
+        assert!(po.contains("#. priority: -2\n"));
+        assert!(po.contains("msgid_plural \"{count} warnings emitted\"\n"));
+
+        // JSON として書き出すと元と同じになる
+        let round_trip = from_po(&po);
+        let json = |entries: &[TranslateEntry]| serde_json::to_value(entries).unwrap();
+        assert_eq!(json(&round_trip), json(&entries));
+        assert!(unexported(&entries).is_empty());
+
+        // 日本語以外の翻訳は書き出さないので警告する
+        let entries: Vec<TranslateEntry> = serde_json::from_str(
+            r#"[{ "en": "mismatched types", "ja": "型が不一致です", "ko": "타입이 일치하지 않습니다" }]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            unexported(&entries),
+            ["\"mismatched types\": translations for ko are not exported"]
+        );
+    }
+}
@@ -0,0 +1,67 @@
+//! 問い合わせ（"--print" など）をラッパー経由で実行する確認（printf を使うので unix のみ）
+#![cfg(unix)]
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+/// 標準出力に書き出す JSON の診断
+const DIAGNOSTIC: &str = r#"{"$message_type":"diagnostic","message":"mismatched types","code":null,"level":"error","spans":[],"children":[],"rendered":"error: mismatched types\n"}"#;
+
+// テスト用の一時ディレクトリに、空の設定ファイルと翻訳ファイルを書き込む
+fn write_files(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "rustc-ja-wrapper-test-{}-probe-{}",
+        std::process::id(),
+        name
+    ));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("config.toml"), "").unwrap();
+    std::fs::write(
+        dir.join("translate.json"),
+        r#"[{ "en": "mismatched types", "ja": "型が不一致です" }]"#,
+    )
+    .unwrap();
+    dir
+}
+
+// 標準出力も変換する設定で、ラッパー経由で printf を実行する（診断だけを書き出し、option は書き出さない）
+fn run_printf(dir: &Path, option: &str) -> Output {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_rustc-ja-wrapper"));
+    command
+        .current_dir(dir)
+        .args(["printf", "%s\\n%.0s", DIAGNOSTIC, option]);
+    for (name, _) in std::env::vars_os() {
+        if name.to_string_lossy().starts_with("RUSTC_JA_") {
+            command.env_remove(name);
+        }
+    }
+    command
+        .env("RUSTC_JA_CONFIG", dir.join("config.toml"))
+        .env("RUSTC_JA_TRANSLATE", dir.join("translate.json"))
+        .env("RUSTC_JA_TRANSLATE_STDOUT", "1")
+        .env("RUSTC_JA_LANG", "ja")
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_print_passthrough() {
+    let dir = write_files("print");
+
+    // "--print" の出力は診断ではないので、JSON の診断の形でも変換しない
+    for option in ["--print", "--print=cfg"] {
+        let output = run_printf(&dir, option);
+        assert_eq!(output.status.code(), Some(0));
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap(),
+            format!("{DIAGNOSTIC}\n")
+        );
+    }
+
+    // 問い合わせでなければ、標準出力の JSON の診断を変換する
+    let output = run_printf(&dir, "--error-format=json");
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(json["message"], "型が不一致です");
+}
@@ -0,0 +1,50 @@
+//! 翻訳が当たらない診断は、変換しても内容（キーの順も含む）が変わらないことの確認
+
+use rustc_ja_wrapper::{TranslateEntry, Translator, convert_json_error_format};
+
+/// rustc 1.95.0 の `--error-format=json --json=diagnostic-rendered-ansi` の出力
+const UNTRANSLATED_JSONL: &str = include_str!("data/untranslated.jsonl");
+
+// 各行を JSON として読み込む（空の行は除く）
+fn values(data: &[u8]) -> Vec<serde_json::Value> {
+    std::str::from_utf8(data)
+        .unwrap()
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+fn assert_round_trip(entries: Vec<TranslateEntry>) {
+    let translator = Translator::new(entries);
+    let input = UNTRANSLATED_JSONL.as_bytes();
+    let output = convert_json_error_format(input.to_vec(), &translator);
+
+    let (expected, actual) = (values(input), values(&output));
+    assert_eq!(expected.len(), 10);
+    assert_eq!(actual, expected);
+    // キーの順も変えない（serde_json の preserve_order により、書き出すと順の違いが分かる）
+    for (a, b) in actual.iter().zip(&expected) {
+        assert_eq!(a.to_string(), b.to_string());
+    }
+    // 変わらなかった行は元の行をそのまま使うので、末尾の改行も含めてバイト列として等しい
+    assert_eq!(String::from_utf8(output).unwrap(), UNTRANSLATED_JSONL);
+}
+
+#[test]
+fn test_round_trip_empty_table() {
+    assert_round_trip(Vec::new());
+}
+
+#[test]
+fn test_round_trip_irrelevant_table() {
+    let entries = serde_json::from_str(
+        r#"[
+            { "en": "this message is never emitted", "ja": "このメッセージは出力されない" },
+            { "en": "unused import: `{$name}`", "ja": "使われていないインポート: `{$name}`" },
+            { "en": "mismatched", "ja": "不一致", "context": "no such context" }
+        ]"#,
+    )
+    .unwrap();
+    assert_round_trip(entries);
+}
@@ -0,0 +1,205 @@
+//! 実際の rustc をラッパー経由で実行する確認（rustc が見つからなければ何もしない）
+
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+/// E0382（移動後の値の借用）になるプログラム
+const BORROW_OF_MOVED_VALUE: &str = r#"fn main() {
+    let s1 = String::from("hello");
+    let s2 = s1;
+    println!("{}, {}", s1, s2);
+}
+"#;
+
+// rustc を実行できるか
+fn has_rustc() -> bool {
+    Command::new("rustc")
+        .arg("--version")
+        .output()
+        .is_ok_and(|output| output.status.success())
+}
+
+// テスト用の一時ディレクトリにソースファイルを書き込む
+fn write_source(name: &str, source: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "rustc-ja-wrapper-test-{}-rustc-{}",
+        std::process::id(),
+        name
+    ));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("main.rs");
+    std::fs::write(&path, source).unwrap();
+    path
+}
+
+// ラッパー経由で rustc を実行する（設定ファイルや環境変数の影響を受けないようにする）
+// translate を指定すれば、RUSTC_JA_TRANSLATE にそのファイルを指定する
+fn run_wrapper(
+    source: &std::path::Path,
+    args: &[&str],
+    translate: Option<&std::path::Path>,
+) -> Output {
+    let dir = source.parent().unwrap();
+    let mut command = Command::new(env!("CARGO_BIN_EXE_rustc-ja-wrapper"));
+    command
+        .current_dir(dir)
+        .arg("rustc")
+        .args(["--edition", "2021", "--crate-name", "main", "-o"])
+        .arg(dir.join("main"))
+        .args(args)
+        .arg(source);
+    for (name, _) in std::env::vars_os() {
+        if name.to_string_lossy().starts_with("RUSTC_JA_") {
+            command.env_remove(name);
+        }
+    }
+    if let Some(translate) = translate {
+        command.env("RUSTC_JA_TRANSLATE", translate);
+    }
+    command.env("RUSTC_JA_LANG", "ja").output().unwrap()
+}
+
+// stderr の JSONL から、指定したエラーコードの診断を探す
+fn find_diagnostic(stderr: &str, code: &str) -> serde_json::Value {
+    stderr
+        .lines()
+        .filter_map(|line| serde_json::from_str::<serde_json::Value>(line).ok())
+        .find(|json| json["code"]["code"] == code)
+        .unwrap_or_else(|| panic!("no {code} in stderr:\n{stderr}"))
+}
+
+#[cfg(feature = "bundled-ja")]
+#[test]
+fn test_rustc_json_error() {
+    if !has_rustc() {
+        eprintln!("rustc not found; skipped");
+        return;
+    }
+    let source = write_source("json", BORROW_OF_MOVED_VALUE);
+    let output = run_wrapper(&source, &["--error-format=json"], None);
+    std::fs::remove_dir_all(source.parent().unwrap()).unwrap();
+
+    // rustc の終了コードをそのまま返す
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let diagnostic = find_diagnostic(&stderr, "E0382");
+    assert_eq!(diagnostic["message"], "移動された値の借用しました: `s1`");
+    let rendered = diagnostic["rendered"].as_str().unwrap();
+    assert!(
+        rendered.contains("移動された値の借用しました"),
+        "{rendered}"
+    );
+}
+
+#[test]
+fn test_rustc_success() {
+    if !has_rustc() {
+        eprintln!("rustc not found; skipped");
+        return;
+    }
+    let source = write_source("success", "fn main() {}\n");
+    let output = run_wrapper(&source, &[], None);
+    std::fs::remove_dir_all(source.parent().unwrap()).unwrap();
+
+    assert_eq!(output.status.code(), Some(0));
+    assert!(
+        output.stderr.is_empty(),
+        "{:?}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+#[test]
+fn test_rustc_json_error_with_translate_file() {
+    if !has_rustc() {
+        eprintln!("rustc not found; skipped");
+        return;
+    }
+    // 組み込みの翻訳データがなくても（feature "bundled-ja" が無効でも）、指定した翻訳データで翻訳する
+    let source = write_source("translate", BORROW_OF_MOVED_VALUE);
+    let translate = source.with_file_name("translate.json");
+    std::fs::write(
+        &translate,
+        r#"[{ "en": "borrow of moved value", "ja": "移動した値を借用しています", "exact": false }]"#,
+    )
+    .unwrap();
+    let output = run_wrapper(&source, &["--error-format=json"], Some(&translate));
+    std::fs::remove_dir_all(source.parent().unwrap()).unwrap();
+
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(!stderr.contains("rustc-ja-wrapper: "), "{stderr}");
+    let diagnostic = find_diagnostic(&stderr, "E0382");
+    assert_eq!(diagnostic["message"], "移動した値を借用しています: `s1`");
+}
+
+#[cfg(not(feature = "bundled-ja"))]
+#[test]
+fn test_rustc_without_translations() {
+    if !has_rustc() {
+        eprintln!("rustc not found; skipped");
+        return;
+    }
+    // 組み込みの翻訳データも翻訳ファイルもなければ、警告して翻訳せずに書き出す
+    let source = write_source("none", BORROW_OF_MOVED_VALUE);
+    let output = run_wrapper(&source, &["--error-format=json"], None);
+    std::fs::remove_dir_all(source.parent().unwrap()).unwrap();
+
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("rustc-ja-wrapper: no translations: "),
+        "{stderr}"
+    );
+    let diagnostic = find_diagnostic(&stderr, "E0382");
+    assert_eq!(diagnostic["message"], "borrow of moved value: `s1`");
+}
+
+// "--doctor" を実行する（rustc を指定すれば "--doctor <rustc>"、環境変数 RUSTC には rustc_env を設定する）
+fn run_doctor(rustc: Option<&str>, rustc_env: &str) -> (Option<i32>, String) {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_rustc-ja-wrapper"));
+    command.arg("--doctor").args(rustc);
+    for (name, _) in std::env::vars_os() {
+        if name.to_string_lossy().starts_with("RUSTC_JA_") {
+            command.env_remove(name);
+        }
+    }
+    let output = command.env("RUSTC", rustc_env).output().unwrap();
+    (
+        output.status.code(),
+        String::from_utf8(output.stdout).unwrap(),
+    )
+}
+
+#[test]
+fn test_doctor_rustc_command() {
+    let missing = std::env::temp_dir().join(format!(
+        "rustc-ja-wrapper-test-{}-doctor-missing/rustc",
+        std::process::id()
+    ));
+    let missing = missing.to_str().unwrap();
+
+    // 指定した rustc がなければ、環境変数 RUSTC があっても見つからない
+    let (code, stdout) = run_doctor(Some(missing), "rustc");
+    assert_eq!(code, Some(1));
+    assert!(stdout.ends_with("rustc: not found\n"), "{stdout}");
+
+    // 指定しなければ環境変数 RUSTC を調べる
+    let (code, stdout) = run_doctor(None, missing);
+    assert_eq!(code, Some(1));
+    assert!(stdout.ends_with("rustc: not found\n"), "{stdout}");
+
+    if !has_rustc() {
+        eprintln!("rustc not found; skipped");
+        return;
+    }
+    // 指定したコマンドが rustc でなければ、環境変数 RUSTC を調べる
+    let (_, stdout) = run_doctor(Some("cargo"), "rustc");
+    assert!(stdout.contains("\nrustc: rustc "), "{stdout}");
+    let (code, stdout) = run_doctor(Some("rustc"), missing);
+    assert!(stdout.contains("\nrustc: rustc "), "{stdout}");
+    #[cfg(feature = "bundled-ja")]
+    assert_eq!(code, Some(0));
+    #[cfg(not(feature = "bundled-ja"))]
+    assert_eq!(code, Some(1));
+}
@@ -0,0 +1,31 @@
+//! "--wrapper-selfcheck" をラッパー経由で実行する確認
+
+use std::process::Command;
+
+// feature "bundled-ja" が無効でも、自己診断は自分の翻訳データで確認する
+#[test]
+fn test_selfcheck_ignores_user_config() {
+    // カレントディレクトリの設定ファイルや RUSTC_JA_* の環境変数があっても、自己診断には影響しない
+    let dir = std::env::temp_dir().join(format!(
+        "rustc-ja-wrapper-test-{}-selfcheck",
+        std::process::id()
+    ));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("rustc-ja-wrapper.toml"), "disable = true\n").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_rustc-ja-wrapper"))
+        .current_dir(&dir)
+        .arg("--wrapper-selfcheck")
+        .env("RUSTC_JA_DISABLE", "1")
+        .env("RUSTC_JA_LANG", "fr")
+        .output()
+        .unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(
+        output.status.code(),
+        Some(0),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "selfcheck: ok\n");
+}